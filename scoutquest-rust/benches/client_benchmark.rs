@@ -143,7 +143,7 @@ fn benchmark_serialization(c: &mut Criterion) {
 
 fn benchmark_service_discovery_options(c: &mut Criterion) {
     c.bench_function("create_default_options", |b| {
-        b.iter(|| ServiceDiscoveryOptions::default())
+        b.iter(ServiceDiscoveryOptions::default)
     });
 
     c.bench_function("create_complex_options", |b| {
@@ -0,0 +1,341 @@
+//! scoutquestctl - command-line client for ScoutQuest operators
+
+mod output;
+mod profile;
+
+use clap::{Parser, Subcommand};
+use output::{list, timestamp, OutputFormat, Table};
+use profile::ProfilesFile;
+use scoutquest_rust::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "scoutquestctl")]
+#[command(about = "Command-line client for ScoutQuest Service Discovery")]
+#[command(version = VERSION)]
+struct Cli {
+    /// Server URL (overrides the profile)
+    #[arg(long, env = "SCOUTQUEST_URL", global = true)]
+    url: Option<String>,
+
+    /// API key (overrides the profile)
+    #[arg(
+        long,
+        env = "SCOUTQUEST_API_KEY",
+        hide_env_values = true,
+        global = true
+    )]
+    api_key: Option<String>,
+
+    /// Profile of the profiles file
+    #[arg(long, env = "SCOUTQUEST_PROFILE", global = true)]
+    profile: Option<String>,
+
+    /// Profiles file [default: ~/.config/scoutquest/profiles.toml]
+    #[arg(long, env = "SCOUTQUEST_PROFILES", global = true)]
+    profiles_file: Option<PathBuf>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    output: OutputFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List services
+    Services,
+    /// Show a service and its instances
+    Describe { service: String },
+    /// List the instances of a service
+    Instances {
+        service: String,
+        /// Only instances with status Up
+        #[arg(long)]
+        healthy: bool,
+        /// Only instances with this tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Only instances with this status
+        #[arg(long, value_enum)]
+        status: Option<InstanceStatus>,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Register an instance. Its heartbeats are up to the instance.
+    Register {
+        service: String,
+        #[arg(long)]
+        host: String,
+        #[arg(long)]
+        port: u16,
+        /// Tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Metadata as KEY=VALUE (repeatable)
+        #[arg(long = "meta", value_parser = parse_key_value)]
+        metadata: Vec<(String, String)>,
+        /// The instance serves HTTPS
+        #[arg(long)]
+        secure: bool,
+        /// Health check URL, polled by the server
+        #[arg(long)]
+        health_check: Option<String>,
+    },
+    /// Remove an instance
+    Deregister {
+        service: String,
+        instance_id: String,
+    },
+    /// Send a heartbeat on behalf of an instance
+    Heartbeat {
+        service: String,
+        instance_id: String,
+    },
+    /// Set the status of an instance
+    SetStatus {
+        service: String,
+        instance_id: String,
+        #[arg(value_enum)]
+        status: InstanceStatus,
+    },
+    /// Follow registry events until interrupted
+    Events {
+        #[arg(long)]
+        service: Option<String>,
+        #[arg(long = "type", value_enum)]
+        event_type: Option<EventType>,
+        #[arg(long)]
+        tag: Option<String>,
+        /// Replay the events recorded after this sequence number first
+        #[arg(long)]
+        since: Option<u64>,
+    },
+    /// Show the server metrics
+    Stats,
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    let connection = ProfilesFile::load(cli.profiles_file.as_deref())?.resolve(
+        cli.profile.as_deref(),
+        cli.url.clone(),
+        cli.api_key.clone(),
+    )?;
+    let mut client = ServiceDiscoveryClient::new(&connection.url)?;
+    if let Some(api_key) = &connection.api_key {
+        client = client.with_api_key(api_key);
+    }
+
+    run(&client, cli.command, cli.output).await
+}
+
+async fn run(
+    client: &ServiceDiscoveryClient,
+    command: Command,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    match command {
+        Command::Services => {
+            let services = client.list_services().await?;
+            output::print(format, &services, |services| {
+                let mut table = Table::new(vec!["NAME", "INSTANCES", "HEALTHY", "TAGS", "UPDATED"]);
+                for service in services {
+                    table.row(vec![
+                        service.name.clone(),
+                        service.instances.len().to_string(),
+                        healthy_count(&service.instances).to_string(),
+                        list(&service.tags),
+                        timestamp(&service.updated_at),
+                    ]);
+                }
+                table.to_string()
+            })?;
+        }
+        Command::Describe { service } => {
+            let service = client.get_service(&service).await?;
+            output::print(format, &service, |service| {
+                format!(
+                    "Name:      {}\nTags:      {}\nCreated:   {}\nUpdated:   {}\nInstances: {} ({} healthy)\n\n{}",
+                    service.name,
+                    list(&service.tags),
+                    timestamp(&service.created_at),
+                    timestamp(&service.updated_at),
+                    service.instances.len(),
+                    healthy_count(&service.instances),
+                    instance_table(&service.instances),
+                )
+            })?;
+        }
+        Command::Instances {
+            service,
+            healthy,
+            tags,
+            status,
+            limit,
+        } => {
+            let mut options = ServiceDiscoveryOptions::new().with_healthy_only(healthy);
+            if !tags.is_empty() {
+                options = options.with_tags(tags);
+            }
+            // The status filter applies after the server's limit
+            if let (Some(limit), None) = (limit, &status) {
+                options = options.with_limit(limit);
+            }
+
+            let mut instances = client.get_instances(&service, Some(options)).await?;
+            if let Some(status) = status {
+                instances.retain(|instance| instance.status == status);
+                instances.truncate(limit.unwrap_or(usize::MAX));
+            }
+            output::print(format, &instances, |instances| instance_table(instances))?;
+        }
+        Command::Register {
+            service,
+            host,
+            port,
+            tags,
+            metadata,
+            secure,
+            health_check,
+        } => {
+            let mut options = ServiceRegistrationOptions::new()
+                .with_tags(tags)
+                .with_metadata(metadata.into_iter().collect::<HashMap<_, _>>())
+                .with_secure(secure);
+            if let Some(url) = health_check {
+                options = options.with_health_check(HealthCheck {
+                    url,
+                    ..Default::default()
+                });
+            }
+
+            let instance = client
+                .register_instance(&service, &host, port, Some(options))
+                .await?;
+            output::print(format, &instance, |instance| {
+                format!(
+                    "✅ Registered {} as {}\n",
+                    instance.service_name, instance.id
+                )
+            })?;
+        }
+        Command::Deregister {
+            service,
+            instance_id,
+        } => {
+            client.deregister_instance(&service, &instance_id).await?;
+            println!("✅ Deregistered {} from {}", instance_id, service);
+        }
+        Command::Heartbeat {
+            service,
+            instance_id,
+        } => {
+            client.send_heartbeat(&service, &instance_id).await?;
+            println!("💓 Heartbeat sent for {}", instance_id);
+        }
+        Command::SetStatus {
+            service,
+            instance_id,
+            status,
+        } => {
+            client
+                .update_instance_status(&service, &instance_id, status.clone())
+                .await?;
+            println!("✅ {} is now {:?}", instance_id, status);
+        }
+        Command::Events {
+            service,
+            event_type,
+            tag,
+            since,
+        } => {
+            let options = EventSubscriptionOptions {
+                service,
+                event_type,
+                tag,
+                since,
+            };
+            let mut events = client.subscribe_events(Some(options)).await?;
+            if format == OutputFormat::Table {
+                println!(
+                    "{:<20}  {:>8}  {:<21}  {:<24}  INSTANCE",
+                    "TIME", "SEQUENCE", "TYPE", "SERVICE"
+                );
+            }
+            while let Some(item) = events.next_event().await? {
+                let event = match item {
+                    StreamItem::Event(event) => event,
+                    StreamItem::Resync { last_sequence, .. } => {
+                        eprintln!(
+                            "⚠️ Requested events are no longer available, resuming at sequence {}",
+                            last_sequence
+                        );
+                        continue;
+                    }
+                    StreamItem::EventsDropped { count } => {
+                        eprintln!(
+                            "⚠️ {} events were dropped, the client read too slowly",
+                            count
+                        );
+                        continue;
+                    }
+                };
+                match format {
+                    OutputFormat::Table => println!(
+                        "{:<20}  {:>8}  {:<21}  {:<24}  {}",
+                        timestamp(&event.timestamp),
+                        event.sequence,
+                        format!("{:?}", event.event_type),
+                        event.service_name,
+                        event.instance_id.as_deref().unwrap_or("-")
+                    ),
+                    // One document per event, so the output can be piped
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&event)?),
+                    OutputFormat::Yaml => print!("---\n{}", serde_norway::to_string(&event)?),
+                }
+            }
+        }
+        Command::Stats => {
+            let metrics = client.get_metrics().await?;
+            output::print(format, &metrics, |metrics| {
+                let mut table = Table::new(vec!["METRIC", "VALUE"]);
+                for (metric, value) in output::flatten(metrics) {
+                    table.row(vec![metric, value]);
+                }
+                table.to_string()
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn healthy_count(instances: &[ServiceInstance]) -> usize {
+    instances.iter().filter(|i| i.is_healthy()).count()
+}
+
+fn instance_table(instances: &[ServiceInstance]) -> String {
+    let mut table = Table::new(vec!["ID", "ADDRESS", "STATUS", "TAGS", "LAST HEARTBEAT"]);
+    for instance in instances {
+        table.row(vec![
+            instance.id.clone(),
+            format!("{}:{}", instance.host, instance.port),
+            format!("{:?}", instance.status),
+            list(&instance.tags),
+            timestamp(&instance.last_heartbeat),
+        ]);
+    }
+    table.to_string()
+}
@@ -0,0 +1,176 @@
+//! Table, JSON and YAML rendering
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Yaml,
+}
+
+/// Left-aligned columns separated by two spaces
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<&'static str>) -> Self {
+        Self {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_string()).collect();
+        let widths: Vec<usize> = (0..headers.len())
+            .map(|column| {
+                std::iter::once(&headers)
+                    .chain(&self.rows)
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        for row in std::iter::once(&headers).chain(&self.rows) {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints `value` as JSON or YAML, or as rendered by `table`
+pub fn print<T: Serialize>(
+    format: OutputFormat,
+    value: &T,
+    table: impl FnOnce(&T) -> String,
+) -> anyhow::Result<()> {
+    print!("{}", render(format, value, table)?);
+    Ok(())
+}
+
+fn render<T: Serialize>(
+    format: OutputFormat,
+    value: &T,
+    table: impl FnOnce(&T) -> String,
+) -> anyhow::Result<String> {
+    Ok(match format {
+        OutputFormat::Table => table(value),
+        OutputFormat::Json => format!("{}\n", serde_json::to_string_pretty(value)?),
+        OutputFormat::Yaml => serde_norway::to_string(value)?,
+    })
+}
+
+pub fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn list(items: &[String]) -> String {
+    if items.is_empty() {
+        "-".to_string()
+    } else {
+        items.join(",")
+    }
+}
+
+/// `(path, value)` rows of the leaves of a JSON document, paths joined with
+/// dots
+pub fn flatten(value: &serde_json::Value) -> Vec<(String, String)> {
+    fn walk(prefix: String, value: &serde_json::Value, rows: &mut Vec<(String, String)>) {
+        let key = |name: &str| {
+            if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", prefix, name)
+            }
+        };
+        match value {
+            serde_json::Value::Object(map) => {
+                for (name, value) in map {
+                    walk(key(name), value, rows);
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for (index, value) in items.iter().enumerate() {
+                    walk(key(&index.to_string()), value, rows);
+                }
+            }
+            serde_json::Value::String(s) => rows.push((prefix, s.clone())),
+            other => rows.push((prefix, other.to_string())),
+        }
+    }
+
+    let mut rows = Vec::new();
+    walk(String::new(), value, &mut rows);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_alignment() {
+        let mut table = Table::new(vec!["NAME", "INSTANCES"]);
+        table.row(vec!["user-service".to_string(), "3".to_string()]);
+        table.row(vec!["api".to_string(), "12".to_string()]);
+        assert_eq!(
+            table.to_string(),
+            "NAME          INSTANCES\nuser-service  3\napi           12\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml_output() {
+        let value = serde_json::json!({
+            "name": "user-service",
+            "tags": ["api", "v2"],
+            "instances": 3
+        });
+        let yaml = render(OutputFormat::Yaml, &value, |_| unreachable!()).unwrap();
+        assert_eq!(
+            yaml,
+            "instances: 3\nname: user-service\ntags:\n- api\n- v2\n"
+        );
+        assert_eq!(
+            serde_norway::from_str::<serde_json::Value>(&yaml).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_flatten() {
+        let rows = flatten(&serde_json::json!({
+            "registry": { "services": 2, "healthy": 5 },
+            "system": { "uptime_seconds": 60, "memory_usage": "TODO" }
+        }));
+        assert_eq!(
+            rows,
+            vec![
+                ("registry.healthy".to_string(), "5".to_string()),
+                ("registry.services".to_string(), "2".to_string()),
+                ("system.memory_usage".to_string(), "TODO".to_string()),
+                ("system.uptime_seconds".to_string(), "60".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Server URL and API key resolution: command line and environment first,
+//! then the selected profile of the profiles file.
+//!
+//! ```toml
+//! default_profile = "staging"
+//!
+//! [profiles.staging]
+//! url = "https://scoutquest.staging.example.com"
+//! api_key = "..."
+//! ```
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_URL: &str = "http://localhost:8080";
+const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ProfilesFile {
+    /// Profile used when none is selected
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Profile {
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+}
+
+/// Server to talk to and the key to present
+#[derive(Debug, PartialEq, Eq)]
+pub struct Connection {
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+/// `$XDG_CONFIG_HOME/scoutquest/profiles.toml`, or under `~/.config`
+pub fn default_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("scoutquest").join("profiles.toml"))
+}
+
+impl ProfilesFile {
+    /// Reads `path`, or the default location when `None`. Only a file given
+    /// explicitly has to exist.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        if !required && !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Resolves the connection. `url` and `api_key` come from the command
+    /// line or environment and take precedence over the profile.
+    ///
+    /// The profile's key is only sent to the profile's URL: with another
+    /// `url`, the key has to be given explicitly.
+    pub fn resolve(
+        &self,
+        profile: Option<&str>,
+        url: Option<String>,
+        api_key: Option<String>,
+    ) -> anyhow::Result<Connection> {
+        let (name, selected) = match profile {
+            Some(name) => (
+                name,
+                Some(
+                    self.profiles
+                        .get(name)
+                        .ok_or_else(|| anyhow::anyhow!("Unknown profile: {}", name))?,
+                ),
+            ),
+            None => match &self.default_profile {
+                Some(name) => (
+                    name.as_str(),
+                    Some(
+                        self.profiles
+                            .get(name)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown default_profile: {}", name))?,
+                    ),
+                ),
+                None => (DEFAULT_PROFILE, self.profiles.get(DEFAULT_PROFILE)),
+            },
+        };
+        let selected = selected.cloned().unwrap_or_default();
+        let profile_url = selected.url.unwrap_or_else(|| DEFAULT_URL.to_string());
+
+        let url = match url {
+            Some(url) if !same_url(&url, &profile_url) => {
+                if api_key.is_none() && selected.api_key.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "The api_key of profile {} is only sent to {}, pass --api-key for {}",
+                        name,
+                        profile_url,
+                        url
+                    ));
+                }
+                return Ok(Connection { url, api_key });
+            }
+            _ => profile_url,
+        };
+
+        Ok(Connection {
+            url,
+            api_key: api_key.or(selected.api_key),
+        })
+    }
+}
+
+fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles() -> ProfilesFile {
+        toml::from_str(
+            r#"
+[profiles.default]
+url = "http://localhost:9090"
+
+[profiles.prod]
+url = "https://scoutquest.example.com"
+api_key = "prod-key"
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let profiles = profiles();
+        assert_eq!(
+            profiles.resolve(None, None, None).unwrap(),
+            Connection {
+                url: "http://localhost:9090".to_string(),
+                api_key: None,
+            }
+        );
+        assert_eq!(
+            profiles.resolve(Some("prod"), None, None).unwrap(),
+            Connection {
+                url: "https://scoutquest.example.com".to_string(),
+                api_key: Some("prod-key".to_string()),
+            }
+        );
+
+        // Command line and environment values win over the profile
+        let connection = profiles
+            .resolve(
+                Some("prod"),
+                Some("http://10.0.0.5:8080".to_string()),
+                Some("other-key".to_string()),
+            )
+            .unwrap();
+        assert_eq!(connection.url, "http://10.0.0.5:8080");
+        assert_eq!(connection.api_key.as_deref(), Some("other-key"));
+
+        assert!(profiles.resolve(Some("staging"), None, None).is_err());
+    }
+
+    #[test]
+    fn test_profile_key_only_sent_to_profile_url() {
+        let profiles = profiles();
+
+        // Another server would get the profile's key
+        let error = profiles
+            .resolve(Some("prod"), Some("http://anything".to_string()), None)
+            .unwrap_err();
+        assert!(error.to_string().contains("--api-key"));
+
+        let connection = profiles
+            .resolve(
+                Some("prod"),
+                Some("https://scoutquest.example.com/".to_string()),
+                None,
+            )
+            .unwrap();
+        assert_eq!(connection.api_key.as_deref(), Some("prod-key"));
+
+        // Profiles without a key have nothing to leak
+        let connection = profiles
+            .resolve(None, Some("http://anything".to_string()), None)
+            .unwrap();
+        assert_eq!(connection.url, "http://anything");
+        assert_eq!(connection.api_key, None);
+        assert_eq!(
+            ProfilesFile::default()
+                .resolve(None, None, None)
+                .unwrap()
+                .url,
+            DEFAULT_URL
+        );
+    }
+}
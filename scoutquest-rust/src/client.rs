@@ -1,6 +1,7 @@
 use crate::error::{Result, ScoutQuestError};
+use crate::events::EventStream;
 use crate::models::*;
-use reqwest::{Client as HttpClient, Method};
+use reqwest::{Client as HttpClient, Method, RequestBuilder, Response};
 use serde_json::Value;
 use std::sync::Arc;
 use std::time::Duration;
@@ -37,10 +38,14 @@ use url::Url;
 pub struct ServiceDiscoveryClient {
     discovery_url: String,
     http_client: HttpClient,
+    /// Same settings as `http_client` without the request timeout, which
+    /// would end event streams
+    stream_client: HttpClient,
     registered_instance: Arc<RwLock<Option<ServiceInstance>>>,
     heartbeat_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
     retry_attempts: usize,
     retry_delay: Duration,
+    api_key: Option<String>,
 }
 
 impl ServiceDiscoveryClient {
@@ -97,17 +102,50 @@ impl ServiceDiscoveryClient {
             .timeout(timeout)
             .build()
             .map_err(ScoutQuestError::NetworkError)?;
+        let stream_client = HttpClient::builder()
+            .connect_timeout(timeout)
+            .build()
+            .map_err(ScoutQuestError::NetworkError)?;
 
         Ok(Self {
             discovery_url,
             http_client,
+            stream_client,
             registered_instance: Arc::new(RwLock::new(None)),
             heartbeat_handle: Arc::new(Mutex::new(None)),
             retry_attempts,
             retry_delay,
+            api_key: None,
         })
     }
 
+    /// Sets the API key sent to the discovery server as a bearer token.
+    ///
+    /// The key is only sent to the discovery server, never to the services
+    /// called through [`call_service`](Self::call_service).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use scoutquest_rust::ServiceDiscoveryClient;
+    ///
+    /// let client = ServiceDiscoveryClient::new("http://localhost:8080")?
+    ///     .with_api_key("my-secret-key");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Builds a request to the discovery server, with the API key if set.
+    fn discovery_request(&self, method: Method, url: &str) -> RequestBuilder {
+        authorized(
+            self.http_client.request(method, url),
+            self.api_key.as_deref(),
+        )
+    }
+
     /// Registers a service with the ScoutQuest discovery server.
     ///
     /// This method registers a service instance and starts automatic heartbeat
@@ -147,6 +185,47 @@ impl ServiceDiscoveryClient {
         host: &str,
         port: u16,
         options: Option<ServiceRegistrationOptions>,
+    ) -> Result<ServiceInstance> {
+        let instance = self
+            .register_instance(service_name, host, port, options)
+            .await?;
+
+        {
+            let mut registered = self.registered_instance.write().await;
+            *registered = Some(instance.clone());
+        }
+
+        self.start_heartbeat().await;
+
+        info!(
+            "Service {} registered with ID: {}",
+            service_name, instance.id
+        );
+        Ok(instance)
+    }
+
+    /// Registers a service instance without tracking it.
+    ///
+    /// Unlike [`register_service`](Self::register_service), no heartbeats are
+    /// sent and [`deregister`](Self::deregister) does not remove the instance.
+    /// This suits tools registering instances on behalf of others.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The name of the service to register
+    /// * `host` - The hostname or IP address where the service is running
+    /// * `port` - The port number where the service is listening
+    /// * `options` - Optional registration options (metadata, tags, health check, etc.)
+    ///
+    /// # Returns
+    ///
+    /// Returns the registered ServiceInstance or an error if registration fails.
+    pub async fn register_instance(
+        &self,
+        service_name: &str,
+        host: &str,
+        port: u16,
+        options: Option<ServiceRegistrationOptions>,
     ) -> Result<ServiceInstance> {
         let options = options.unwrap_or_default();
 
@@ -162,23 +241,14 @@ impl ServiceDiscoveryClient {
 
         let url = format!("{}/api/services", self.discovery_url);
 
-        let response = self.http_client.post(&url).json(&request).send().await?;
+        let response = self
+            .discovery_request(Method::POST, &url)
+            .json(&request)
+            .send()
+            .await?;
 
         if response.status().is_success() {
-            let instance: ServiceInstance = response.json().await?;
-
-            {
-                let mut registered = self.registered_instance.write().await;
-                *registered = Some(instance.clone());
-            }
-
-            self.start_heartbeat().await;
-
-            info!(
-                "Service {} registered with ID: {}",
-                service_name, instance.id
-            );
-            Ok(instance)
+            Ok(response.json().await?)
         } else {
             let status = response.status().as_u16();
             let message = response.text().await.unwrap_or_default();
@@ -236,7 +306,10 @@ impl ServiceDiscoveryClient {
             }
         }
 
-        let response = self.http_client.get(url).send().await?;
+        let response = self
+            .discovery_request(Method::GET, url.as_str())
+            .send()
+            .await?;
 
         if response.status().is_success() {
             let instance: ServiceInstance = response.json().await?;
@@ -274,7 +347,7 @@ impl ServiceDiscoveryClient {
     pub async fn get_services_by_tag(&self, tag: &str) -> Result<Vec<Service>> {
         let url = format!("{}/api/services/tags/{}", self.discovery_url, tag);
 
-        let response = self.http_client.get(&url).send().await?;
+        let response = self.discovery_request(Method::GET, &url).send().await?;
 
         if response.status().is_success() {
             let services: Vec<Service> = response.json().await?;
@@ -285,6 +358,211 @@ impl ServiceDiscoveryClient {
         }
     }
 
+    /// Lists every service known to the discovery server.
+    ///
+    /// # Returns
+    ///
+    /// Returns the services with all their instances.
+    pub async fn list_services(&self) -> Result<Vec<Service>> {
+        let url = format!("{}/api/services", self.discovery_url);
+
+        let response = self.discovery_request(Method::GET, &url).send().await?;
+        Ok(check_response(response).await?.json().await?)
+    }
+
+    /// Retrieves a service and all its instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The name of the service
+    ///
+    /// # Returns
+    ///
+    /// Returns the Service or `ServiceNotFound` if it is not registered.
+    pub async fn get_service(&self, service_name: &str) -> Result<Service> {
+        let url = format!("{}/api/services/{}", self.discovery_url, service_name);
+
+        let response = self.discovery_request(Method::GET, &url).send().await?;
+        if response.status().as_u16() == 404 {
+            return Err(ScoutQuestError::ServiceNotFound {
+                service_name: service_name.to_string(),
+            });
+        }
+        Ok(check_response(response).await?.json().await?)
+    }
+
+    /// Lists the instances of a service matching the options.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The name of the service
+    /// * `options` - Filters (healthy only, tags, limit); all instances when `None`
+    ///
+    /// # Returns
+    ///
+    /// Returns the matching instances, empty when the service is unknown.
+    pub async fn get_instances(
+        &self,
+        service_name: &str,
+        options: Option<ServiceDiscoveryOptions>,
+    ) -> Result<Vec<ServiceInstance>> {
+        let options = options.unwrap_or_default();
+
+        let mut url = Url::parse(&format!(
+            "{}/api/services/{}/instances",
+            self.discovery_url, service_name
+        ))?;
+
+        {
+            let mut query_pairs = url.query_pairs_mut();
+            query_pairs.append_pair("healthy_only", &options.healthy_only.to_string());
+
+            if let Some(tags) = &options.tags {
+                query_pairs.append_pair("tags", &tags.join(","));
+            }
+
+            if let Some(limit) = options.limit {
+                query_pairs.append_pair("limit", &limit.to_string());
+            }
+        }
+
+        let response = self
+            .discovery_request(Method::GET, url.as_str())
+            .send()
+            .await?;
+        Ok(check_response(response).await?.json().await?)
+    }
+
+    /// Removes an instance from the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The name of the service the instance belongs to
+    /// * `instance_id` - The ID of the instance
+    pub async fn deregister_instance(&self, service_name: &str, instance_id: &str) -> Result<()> {
+        let url = self.instance_url(service_name, instance_id, "");
+        let response = self.discovery_request(Method::DELETE, &url).send().await?;
+        check_instance_response(response, instance_id).await
+    }
+
+    /// Sends a heartbeat on behalf of an instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The name of the service the instance belongs to
+    /// * `instance_id` - The ID of the instance
+    pub async fn send_heartbeat(&self, service_name: &str, instance_id: &str) -> Result<()> {
+        let url = self.instance_url(service_name, instance_id, "/heartbeat");
+        let response = self.discovery_request(Method::POST, &url).send().await?;
+        check_instance_response(response, instance_id).await
+    }
+
+    /// Sets the status of an instance, such as `OutOfService` to take it out
+    /// of rotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The name of the service the instance belongs to
+    /// * `instance_id` - The ID of the instance
+    /// * `status` - The new status
+    pub async fn update_instance_status(
+        &self,
+        service_name: &str,
+        instance_id: &str,
+        status: InstanceStatus,
+    ) -> Result<()> {
+        let url = self.instance_url(service_name, instance_id, "/status");
+        let response = self
+            .discovery_request(Method::PUT, &url)
+            .json(&UpdateStatusRequest { status })
+            .send()
+            .await?;
+        check_instance_response(response, instance_id).await
+    }
+
+    /// Subscribes to registry events.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Filters, and the sequence number to replay events from
+    ///
+    /// # Returns
+    ///
+    /// Returns the stream of events, which has no request timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use scoutquest_rust::*;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = ServiceDiscoveryClient::new("http://localhost:8080")?;
+    ///
+    /// let options = EventSubscriptionOptions::new().with_service("user-service");
+    /// let mut events = client.subscribe_events(Some(options)).await?;
+    /// while let Some(item) = events.next_event().await? {
+    ///     match item {
+    ///         StreamItem::Event(event) => {
+    ///             println!("{:?} {}", event.event_type, event.service_name)
+    ///         }
+    ///         // Events were missed, re-list the registry
+    ///         StreamItem::Resync { .. } | StreamItem::EventsDropped { .. } => {}
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn subscribe_events(
+        &self,
+        options: Option<EventSubscriptionOptions>,
+    ) -> Result<EventStream> {
+        let options = options.unwrap_or_default();
+
+        let mut url = Url::parse(&format!("{}/api/events", self.discovery_url))?;
+        {
+            let mut query_pairs = url.query_pairs_mut();
+            if let Some(service) = &options.service {
+                query_pairs.append_pair("service", service);
+            }
+            if let Some(event_type) = &options.event_type {
+                let event_type = serde_json::to_value(event_type)?;
+                query_pairs.append_pair("type", event_type.as_str().unwrap_or_default());
+            }
+            if let Some(tag) = &options.tag {
+                query_pairs.append_pair("tag", tag);
+            }
+            if let Some(since) = options.since {
+                query_pairs.append_pair("since", &since.to_string());
+            }
+        }
+
+        let response = authorized(
+            self.stream_client.get(url.as_str()),
+            self.api_key.as_deref(),
+        )
+        .header(reqwest::header::ACCEPT, "text/event-stream")
+        .send()
+        .await?;
+        Ok(EventStream::new(check_response(response).await?))
+    }
+
+    /// Retrieves the discovery server metrics: registry counts, rate
+    /// limiting, TLS and uptime.
+    pub async fn get_metrics(&self) -> Result<Value> {
+        let url = format!("{}/metrics", self.discovery_url);
+
+        let response = self.discovery_request(Method::GET, &url).send().await?;
+        Ok(check_response(response).await?.json().await?)
+    }
+
+    fn instance_url(&self, service_name: &str, instance_id: &str, suffix: &str) -> String {
+        format!(
+            "{}/api/services/{}/instances/{}{}",
+            self.discovery_url, service_name, instance_id, suffix
+        )
+    }
+
     /// Calls a REST API endpoint on a discovered service with retry logic.
     ///
     /// # Arguments
@@ -514,7 +792,7 @@ impl ServiceDiscoveryClient {
                 self.discovery_url, instance.service_name, instance.id
             );
 
-            let response = self.http_client.delete(&url).send().await?;
+            let response = self.discovery_request(Method::DELETE, &url).send().await?;
 
             if response.status().is_success() {
                 info!("Service {} deregistered", instance.service_name);
@@ -541,6 +819,7 @@ impl ServiceDiscoveryClient {
         let discovery_url = self.discovery_url.clone();
         let http_client = self.http_client.clone();
         let registered_instance = self.registered_instance.clone();
+        let api_key = self.api_key.clone();
 
         let handle = tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(30));
@@ -559,7 +838,10 @@ impl ServiceDiscoveryClient {
                         discovery_url, instance.service_name, instance.id
                     );
 
-                    match http_client.post(&url).send().await {
+                    match authorized(http_client.post(&url), api_key.as_deref())
+                        .send()
+                        .await
+                    {
                         Ok(response) => {
                             if !response.status().is_success() {
                                 warn!("Heartbeat failed: {}", response.status());
@@ -608,6 +890,35 @@ impl ServiceDiscoveryClient {
     }
 }
 
+/// Adds the API key, if any, as a bearer token.
+fn authorized(request: RequestBuilder, api_key: Option<&str>) -> RequestBuilder {
+    match api_key {
+        Some(api_key) => request.bearer_auth(api_key),
+        None => request,
+    }
+}
+
+/// Turns an unsuccessful response into `RequestFailed`.
+async fn check_response(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        let status = response.status().as_u16();
+        let message = response.text().await.unwrap_or_default();
+        Err(ScoutQuestError::RequestFailed { status, message })
+    }
+}
+
+/// Like [`check_response`], with 404 meaning the instance is unknown.
+async fn check_instance_response(response: Response, instance_id: &str) -> Result<()> {
+    if response.status().as_u16() == 404 {
+        return Err(ScoutQuestError::InstanceNotFound {
+            instance_id: instance_id.to_string(),
+        });
+    }
+    check_response(response).await.map(|_| ())
+}
+
 /// Service discovery client for interacting with the ScoutQuest server.
 impl Drop for ServiceDiscoveryClient {
     /// This method is called when the ServiceDiscoveryClient is dropped.
@@ -22,6 +22,10 @@ pub enum ScoutQuestError {
     #[error("Registration failed: {status} - {message}")]
     RegistrationFailed { status: u16, message: String },
 
+    /// The discovery server rejected a request
+    #[error("Request failed: {status} - {message}")]
+    RequestFailed { status: u16, message: String },
+
     /// JSON serialization/deserialization error
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
@@ -75,6 +79,12 @@ mod tests {
             "Registration failed: 500 - Internal server error"
         );
 
+        let error = ScoutQuestError::RequestFailed {
+            status: 403,
+            message: "Missing scope".to_string(),
+        };
+        assert_eq!(error.to_string(), "Request failed: 403 - Missing scope");
+
         let error = ScoutQuestError::NoHealthyInstances {
             service_name: "api-service".to_string(),
         };
@@ -0,0 +1,167 @@
+use crate::error::Result;
+use crate::models::ServiceEvent;
+use serde::Deserialize;
+use tracing::warn;
+
+/// Item read from an [`EventStream`]
+#[derive(Debug, Clone)]
+pub enum StreamItem {
+    Event(ServiceEvent),
+    /// The requested events are no longer available on the server: re-list
+    /// the registry before relying on the stream again
+    Resync {
+        oldest_sequence: u64,
+        last_sequence: u64,
+    },
+    /// The client read too slowly and `count` events were skipped
+    EventsDropped {
+        count: u64,
+    },
+}
+
+#[derive(Deserialize)]
+struct ResyncNotice {
+    oldest_sequence: u64,
+    last_sequence: u64,
+}
+
+#[derive(Deserialize)]
+struct DroppedNotice {
+    count: u64,
+}
+
+/// Stream of registry events read from the server-sent events endpoint.
+///
+/// Created by [`ServiceDiscoveryClient::subscribe_events`](crate::ServiceDiscoveryClient::subscribe_events).
+pub struct EventStream {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+}
+
+impl EventStream {
+    pub(crate) fn new(response: reqwest::Response) -> Self {
+        Self {
+            response,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Waits for the next event, or notice of missed events.
+    ///
+    /// # Returns
+    ///
+    /// Returns the next item, or `None` once the server closed the stream.
+    pub async fn next_event(&mut self) -> Result<Option<StreamItem>> {
+        loop {
+            while let Some(end) = find_message_end(&self.buffer) {
+                let message: Vec<u8> = self.buffer.drain(..end).collect();
+                if let Some(item) = parse_message(&String::from_utf8_lossy(&message))? {
+                    return Ok(Some(item));
+                }
+            }
+
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Length of the first complete message in `buffer`, blank line included
+fn find_message_end(buffer: &[u8]) -> Option<usize> {
+    buffer
+        .windows(2)
+        .position(|window| window == b"\n\n")
+        .map(|position| position + 2)
+}
+
+/// Parses one server-sent message. Keep-alive comments and unknown notices,
+/// which are logged, yield `None`.
+fn parse_message(message: &str) -> Result<Option<StreamItem>> {
+    let mut name = None;
+    let mut data = Vec::new();
+    for line in message.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            name = Some(value.trim());
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+
+    if data.is_empty() {
+        return Ok(None);
+    }
+    let data = data.join("\n");
+
+    match name {
+        None | Some("message") => Ok(Some(StreamItem::Event(serde_json::from_str(&data)?))),
+        Some("resync") => {
+            let notice: ResyncNotice = serde_json::from_str(&data)?;
+            Ok(Some(StreamItem::Resync {
+                oldest_sequence: notice.oldest_sequence,
+                last_sequence: notice.last_sequence,
+            }))
+        }
+        Some("events_dropped") => {
+            let notice: DroppedNotice = serde_json::from_str(&data)?;
+            Ok(Some(StreamItem::EventsDropped {
+                count: notice.count,
+            }))
+        }
+        Some(name) => {
+            warn!("Server notice {}: {}", name, data);
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EventType;
+
+    #[test]
+    fn test_parse_message() {
+        let item = parse_message(
+            "id: 7\ndata: {\"event_type\":\"InstanceRegistered\",\"service_name\":\"api\",\"instance_id\":\"api-1\",\"sequence\":7,\"timestamp\":\"2024-01-01T00:00:00Z\",\"details\":{}}\n\n",
+        )
+        .unwrap()
+        .unwrap();
+        let StreamItem::Event(event) = item else {
+            panic!("expected an event, got {:?}", item);
+        };
+        assert_eq!(event.event_type, EventType::InstanceRegistered);
+        assert_eq!(event.sequence, 7);
+
+        assert!(parse_message(":\n\n").unwrap().is_none());
+        assert!(parse_message("event: upgraded\ndata: {}\n\n")
+            .unwrap()
+            .is_none());
+        assert!(parse_message("data: not json\n\n").is_err());
+    }
+
+    #[test]
+    fn test_missed_events_surfaced() {
+        assert!(matches!(
+            parse_message(
+                "event: resync\nid: 42\ndata: {\"oldest_sequence\":30,\"last_sequence\":42}\n\n"
+            )
+            .unwrap(),
+            Some(StreamItem::Resync {
+                oldest_sequence: 30,
+                last_sequence: 42
+            })
+        ));
+        assert!(matches!(
+            parse_message("event: events_dropped\ndata: {\"count\":3}\n\n").unwrap(),
+            Some(StreamItem::EventsDropped { count: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_find_message_end() {
+        assert_eq!(find_message_end(b"data: 1\n\ndata: 2"), Some(9));
+        assert_eq!(find_message_end(b"data: 1\n"), None);
+    }
+}
@@ -32,10 +32,12 @@
 
 pub mod client;
 pub mod error;
+pub mod events;
 pub mod models;
 
 pub use client::ServiceDiscoveryClient;
 pub use error::ScoutQuestError;
+pub use events::{EventStream, StreamItem};
 pub use models::*;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -86,6 +86,7 @@ impl ServiceInstance {
 
 /// Represents the operational status of a service instance.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 pub enum InstanceStatus {
     /// Service is running and ready to accept requests
     Up,
@@ -232,6 +233,97 @@ pub struct Service {
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct UpdateStatusRequest {
+    pub status: InstanceStatus,
+}
+
+/// A change in the registry, as streamed by the discovery server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceEvent {
+    /// What happened
+    pub event_type: EventType,
+    /// Service the event is about
+    pub service_name: String,
+    /// Instance the event is about, if any
+    pub instance_id: Option<String>,
+    /// Position of the event in the server's event stream, starting at 1
+    #[serde(default)]
+    pub sequence: u64,
+    /// Tags of the instance the event is about
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When the event occurred
+    pub timestamp: DateTime<Utc>,
+    /// Event-specific details
+    pub details: serde_json::Value,
+}
+
+/// Kinds of registry events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum EventType {
+    ServiceRegistered,
+    ServiceDeregistered,
+    InstanceRegistered,
+    InstanceDeregistered,
+    InstanceStatusChanged,
+    HealthCheckFailed,
+    HealthCheckRecovered,
+    /// The service has no healthy instance left
+    ServiceUnavailable,
+    /// The service has a healthy instance again
+    ServiceAvailable,
+    /// An instance, or the whole service when `instance_id` is empty, went
+    /// into maintenance
+    MaintenanceStarted,
+    /// A maintenance window ended, by request or on expiry
+    MaintenanceEnded,
+    /// The instance's status changed too often within the flap window
+    InstanceFlapping,
+}
+
+/// Filters of an event subscription.
+#[derive(Debug, Clone, Default)]
+pub struct EventSubscriptionOptions {
+    pub service: Option<String>,
+    pub event_type: Option<EventType>,
+    pub tag: Option<String>,
+    /// Replay the events recorded after this sequence number first
+    pub since: Option<u64>,
+}
+
+/// Event subscription options.
+impl EventSubscriptionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only receive the events of this service.
+    pub fn with_service(mut self, service: &str) -> Self {
+        self.service = Some(service.to_string());
+        self
+    }
+
+    /// Only receive events of this type.
+    pub fn with_event_type(mut self, event_type: EventType) -> Self {
+        self.event_type = Some(event_type);
+        self
+    }
+
+    /// Only receive the events of instances with this tag.
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Replay the events recorded after this sequence number.
+    pub fn with_since(mut self, since: u64) -> Self {
+        self.since = Some(since);
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -345,6 +437,23 @@ mod tests {
         assert_eq!(options.limit, Some(10));
     }
 
+    #[test]
+    fn test_service_event_deserialization() {
+        let event: ServiceEvent = serde_json::from_value(serde_json::json!({
+            "event_type": "InstanceStatusChanged",
+            "service_name": "user-service",
+            "instance_id": "user-123",
+            "sequence": 42,
+            "timestamp": "2024-01-01T00:00:00Z",
+            "details": { "old_status": "Up", "new_status": "Down" }
+        }))
+        .unwrap();
+
+        assert_eq!(event.event_type, EventType::InstanceStatusChanged);
+        assert_eq!(event.sequence, 42);
+        assert!(event.tags.is_empty());
+    }
+
     #[test]
     fn test_service_discovery_options_default() {
         let options = ServiceDiscoveryOptions::new();
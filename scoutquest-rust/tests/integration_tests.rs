@@ -3,7 +3,7 @@ mod tests {
     use scoutquest_rust::*;
     use serde_json::json;
     use std::collections::HashMap;
-    use wiremock::matchers::{method, path};
+    use wiremock::matchers::{header, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
@@ -296,4 +296,92 @@ mod tests {
         let registered = client.get_registered_instance().await;
         assert!(registered.is_none());
     }
+
+    #[tokio::test]
+    async fn test_operator_requests_send_api_key() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/api/services"))
+            .and(header("authorization", "Bearer ops-secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+                "name": "user-service",
+                "instances": [],
+                "tags": ["api"],
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z"
+            }])))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("PUT"))
+            .and(path("/api/services/user-service/instances/user-123/status"))
+            .and(header("authorization", "Bearer ops-secret"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/services/user-service/instances/gone/heartbeat"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let client = ServiceDiscoveryClient::new(&mock_server.uri())
+            .unwrap()
+            .with_api_key("ops-secret");
+
+        let services = client.list_services().await.unwrap();
+        assert_eq!(services[0].name, "user-service");
+
+        client
+            .update_instance_status("user-service", "user-123", InstanceStatus::OutOfService)
+            .await
+            .unwrap();
+
+        let result = client.send_heartbeat("user-service", "gone").await;
+        assert!(matches!(
+            result,
+            Err(ScoutQuestError::InstanceNotFound { .. })
+        ));
+
+        // Without the key the mocks do not match
+        let anonymous = ServiceDiscoveryClient::new(&mock_server.uri()).unwrap();
+        let result = anonymous.list_services().await;
+        assert!(matches!(
+            result,
+            Err(ScoutQuestError::RequestFailed { status: 404, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_events() {
+        let mock_server = MockServer::start().await;
+
+        let body = concat!(
+            ":\n\n",
+            "id: 1\ndata: {\"event_type\":\"InstanceRegistered\",\"service_name\":\"user-service\",",
+            "\"instance_id\":\"user-123\",\"sequence\":1,\"timestamp\":\"2024-01-01T00:00:00Z\",\"details\":{}}\n\n",
+        );
+        Mock::given(method("GET"))
+            .and(path("/api/events"))
+            .and(query_param("service", "user-service"))
+            .and(query_param("since", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&mock_server)
+            .await;
+
+        let client = ServiceDiscoveryClient::new(&mock_server.uri()).unwrap();
+        let options = EventSubscriptionOptions::new()
+            .with_service("user-service")
+            .with_since(0);
+        let mut events = client.subscribe_events(Some(options)).await.unwrap();
+
+        let Some(StreamItem::Event(event)) = events.next_event().await.unwrap() else {
+            panic!("expected an event");
+        };
+        assert_eq!(event.event_type, EventType::InstanceRegistered);
+        assert_eq!(event.instance_id.as_deref(), Some("user-123"));
+        assert!(events.next_event().await.unwrap().is_none());
+    }
 }
@@ -1,23 +1,62 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    extract::{Extension, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 
-use crate::{models::*, AppState};
+use std::time::Duration;
 
-pub async fn list_services(State(state): State<AppState>) -> Json<Vec<Service>> {
-    let services = state.registry.get_all_services().await;
-    Json(services)
+use crate::audit::{Actor, AuditAction, AuditEntry, AuditQuery, AuditTarget};
+use crate::events::{EventFilter, EventReplay};
+use crate::middleware::auth::AuthenticatedKey;
+use crate::middleware::scopes::{authorize, Scope};
+use crate::tls::ca::IssuedCertificate;
+use crate::webhooks::WebhookReport;
+use crate::{models::*, sse, AppState};
+
+/// Registry index the response reflects, to pass back as `?index=` in a
+/// blocking query
+pub const INDEX_HEADER: &str = "x-scoutquest-index";
+
+const DEFAULT_BLOCKING_WAIT: Duration = Duration::from_secs(5 * 60);
+const MAX_BLOCKING_WAIT: Duration = Duration::from_secs(10 * 60);
+
+pub async fn list_services(
+    State(state): State<AppState>,
+    Query(blocking): Query<BlockingQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let index = block_on_index(&state, None, &blocking).await?;
+    let services = state
+        .registry
+        .get_all_services()
+        .await
+        .map_err(registry_failure)?;
+    Ok(([(INDEX_HEADER, index.to_string())], Json(services)))
 }
 
 pub async fn register_service(
     State(state): State<AppState>,
+    actor: Actor,
+    key: Option<Extension<AuthenticatedKey>>,
     Json(request): Json<RegisterServiceRequest>,
-) -> Result<(StatusCode, Json<ServiceInstance>), StatusCode> {
+) -> Result<(StatusCode, Json<ServiceInstance>), (StatusCode, String)> {
+    authorize(
+        key.as_deref(),
+        &Scope::Register(request.service_name.clone()),
+    )?;
+
     match state.registry.register_instance(request).await {
-        Ok(instance) => Ok((StatusCode::CREATED, Json(instance))),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Ok(instance) => {
+            state.audit.record(
+                actor,
+                AuditAction::RegisterInstance,
+                instance_target(&instance),
+                None,
+                to_audit_value(&instance),
+            );
+            Ok((StatusCode::CREATED, Json(instance)))
+        }
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -25,46 +64,82 @@ pub async fn get_service(
     State(state): State<AppState>,
     Path(name): Path<String>,
 ) -> Result<Json<Service>, StatusCode> {
-    let services = state.registry.get_all_services().await;
-
-    match services.into_iter().find(|s| s.name == name) {
-        Some(service) => Ok(Json(service)),
-        None => Err(StatusCode::NOT_FOUND),
-    }
+    state
+        .registry
+        .get_service(&name)
+        .await
+        .map_err(registry_error)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
 }
 
-pub async fn delete_service(State(state): State<AppState>, Path(name): Path<String>) -> StatusCode {
+pub async fn delete_service(
+    State(state): State<AppState>,
+    actor: Actor,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let before = state
+        .registry
+        .get_service(&name)
+        .await
+        .map_err(registry_error)?;
+
     let instances: Vec<_> = state
         .registry
         .get_all_instances()
-        .iter()
+        .map_err(registry_error)?
+        .into_iter()
         .filter(|entry| entry.service_name == name)
-        .map(|entry| entry.id.clone())
+        .map(|entry| entry.id)
         .collect();
 
     for instance_id in instances {
-        state.registry.deregister_instance(&instance_id).await;
+        state
+            .registry
+            .deregister_instance(&instance_id)
+            .await
+            .map_err(registry_error)?;
+    }
+
+    if let Some(service) = before {
+        state.audit.record(
+            actor,
+            AuditAction::DeleteService,
+            service_target(&name),
+            to_audit_value(&service),
+            None,
+        );
     }
 
-    StatusCode::NO_CONTENT
+    Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn get_instances(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Query(query): Query<DiscoveryQuery>,
-) -> Json<Vec<ServiceInstance>> {
-    let instances = state.registry.get_service_instances(&name, &query).await;
-    Json(instances)
+) -> Result<Json<Vec<ServiceInstance>>, StatusCode> {
+    let instances = state
+        .registry
+        .get_service_instances(&name, &query)
+        .await
+        .map_err(registry_error)?;
+    Ok(Json(instances))
 }
 
 pub async fn discover_service(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Query(query): Query<DiscoveryQuery>,
-) -> Json<Vec<ServiceInstance>> {
-    let instances = state.registry.get_service_instances(&name, &query).await;
-    Json(instances)
+    Query(blocking): Query<BlockingQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let index = block_on_index(&state, Some(&name), &blocking).await?;
+    let instances = state
+        .registry
+        .get_service_instances(&name, &query)
+        .await
+        .map_err(registry_failure)?;
+    Ok(([(INDEX_HEADER, index.to_string())], Json(instances)))
 }
 
 pub async fn load_balance_service(
@@ -74,82 +149,593 @@ pub async fn load_balance_service(
 ) -> Result<Json<ServiceInstance>, StatusCode> {
     let strategy = query.strategy.unwrap_or(LoadBalancingStrategy::Random);
 
-    match state.registry.load_balance_service(&name, strategy).await {
-        Some(instance) => Ok(Json(instance)),
-        None => Err(StatusCode::NOT_FOUND),
-    }
+    state
+        .registry
+        .load_balance_service(&name, strategy)
+        .await
+        .map_err(registry_error)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
 }
 
 pub async fn deregister_instance(
     State(state): State<AppState>,
-    Path((_, id)): Path<(String, String)>,
-) -> StatusCode {
-    if state.registry.deregister_instance(&id).await {
-        StatusCode::NO_CONTENT
+    actor: Actor,
+    Path((name, id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    // The route's register scope is checked against the service in the path
+    let Some(before) = instance_of_service(&state, &name, &id).map_err(registry_error)? else {
+        return Ok(StatusCode::NOT_FOUND);
+    };
+
+    if state
+        .registry
+        .deregister_instance(&id)
+        .await
+        .map_err(registry_error)?
+    {
+        state.audit.record(
+            actor,
+            AuditAction::DeregisterInstance,
+            instance_target(&before),
+            to_audit_value(&before),
+            None,
+        );
+        Ok(StatusCode::NO_CONTENT)
     } else {
-        StatusCode::NOT_FOUND
+        Ok(StatusCode::NOT_FOUND)
     }
 }
 
 pub async fn heartbeat(
     State(state): State<AppState>,
-    Path((_, id)): Path<(String, String)>,
-) -> StatusCode {
-    if state.registry.update_heartbeat(&id).await {
-        StatusCode::OK
+    Path((name, id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    if instance_of_service(&state, &name, &id)
+        .map_err(registry_error)?
+        .is_none()
+    {
+        return Ok(StatusCode::NOT_FOUND);
+    }
+
+    if state
+        .registry
+        .update_heartbeat(&id)
+        .await
+        .map_err(registry_error)?
+    {
+        Ok(StatusCode::OK)
     } else {
-        StatusCode::NOT_FOUND
+        Ok(StatusCode::NOT_FOUND)
     }
 }
 
 pub async fn update_status(
     State(state): State<AppState>,
+    actor: Actor,
     Path((_, id)): Path<(String, String)>,
     Json(request): Json<UpdateStatusRequest>,
-) -> StatusCode {
+) -> Result<StatusCode, StatusCode> {
+    let before = state.registry.get_instance(&id).map_err(registry_error)?;
+
     if state
         .registry
         .update_instance_status(&id, request.status)
         .await
+        .map_err(registry_error)?
     {
-        StatusCode::OK
+        if let Some(instance) = before {
+            state.audit.record(
+                actor,
+                AuditAction::UpdateStatus,
+                instance_target(&instance),
+                to_audit_value(&instance),
+                current_instance(&state, &id),
+            );
+        }
+        Ok(StatusCode::OK)
     } else {
-        StatusCode::NOT_FOUND
+        Ok(StatusCode::NOT_FOUND)
     }
 }
 
-pub async fn get_service_tags(
+pub async fn start_instance_maintenance(
+    State(state): State<AppState>,
+    actor: Actor,
+    Path((name, id)): Path<(String, String)>,
+    Json(request): Json<MaintenanceRequest>,
+) -> Result<Json<ServiceInstance>, (StatusCode, String)> {
+    let maintenance = maintenance_window(request, &actor)?;
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Instance {} is not registered in service {}", id, name),
+        )
+    };
+    let before = instance_of_service(&state, &name, &id)
+        .map_err(registry_failure)?
+        .ok_or_else(not_found)?;
+
+    if !state
+        .registry
+        .start_maintenance(&name, Some(&id), maintenance)
+        .await
+        .map_err(registry_failure)?
+    {
+        return Err(not_found());
+    }
+
+    let after = state
+        .registry
+        .get_instance(&id)
+        .map_err(registry_failure)?
+        .ok_or_else(not_found)?;
+    state.audit.record(
+        actor,
+        AuditAction::StartMaintenance,
+        instance_target(&before),
+        to_audit_value(&before),
+        to_audit_value(&after),
+    );
+    Ok(Json(after))
+}
+
+pub async fn end_instance_maintenance(
     State(state): State<AppState>,
+    actor: Actor,
+    Path((name, id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let Some(before) = instance_of_service(&state, &name, &id).map_err(registry_error)? else {
+        return Ok(StatusCode::NOT_FOUND);
+    };
+
+    if state
+        .registry
+        .end_maintenance(&name, Some(&id), false)
+        .await
+        .map_err(registry_error)?
+    {
+        state.audit.record(
+            actor,
+            AuditAction::EndMaintenance,
+            instance_target(&before),
+            to_audit_value(&before),
+            current_instance(&state, &id),
+        );
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
+}
+
+pub async fn start_service_maintenance(
+    State(state): State<AppState>,
+    actor: Actor,
     Path(name): Path<String>,
-) -> Result<Json<Vec<String>>, StatusCode> {
-    let services = state.registry.get_all_services().await;
+    Json(request): Json<MaintenanceRequest>,
+) -> Result<Json<Service>, (StatusCode, String)> {
+    let maintenance = maintenance_window(request, &actor)?;
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Service {} is not registered", name),
+        )
+    };
+    let before = state
+        .registry
+        .get_service(&name)
+        .await
+        .map_err(registry_failure)?
+        .ok_or_else(not_found)?;
+
+    if !state
+        .registry
+        .start_maintenance(&name, None, maintenance)
+        .await
+        .map_err(registry_failure)?
+    {
+        return Err(not_found());
+    }
 
-    match services.into_iter().find(|s| s.name == name) {
-        Some(service) => Ok(Json(service.tags)),
-        None => Err(StatusCode::NOT_FOUND),
+    let after = state
+        .registry
+        .get_service(&name)
+        .await
+        .map_err(registry_failure)?
+        .ok_or_else(not_found)?;
+    state.audit.record(
+        actor,
+        AuditAction::StartMaintenance,
+        service_target(&name),
+        to_audit_value(&before),
+        to_audit_value(&after),
+    );
+    Ok(Json(after))
+}
+
+pub async fn end_service_maintenance(
+    State(state): State<AppState>,
+    actor: Actor,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let Some(before) = state
+        .registry
+        .get_service(&name)
+        .await
+        .map_err(registry_error)?
+    else {
+        return Ok(StatusCode::NOT_FOUND);
+    };
+
+    if state
+        .registry
+        .end_maintenance(&name, None, false)
+        .await
+        .map_err(registry_error)?
+    {
+        let after = state.registry.get_service(&name).await.ok().flatten();
+        state.audit.record(
+            actor,
+            AuditAction::EndMaintenance,
+            service_target(&name),
+            to_audit_value(&before),
+            after.as_ref().and_then(to_audit_value),
+        );
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
     }
 }
 
+pub async fn get_service_tags(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    state
+        .registry
+        .get_service(&name)
+        .await
+        .map_err(registry_error)?
+        .map(|service| Json(service.tags))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
 pub async fn get_services_by_tag(
     State(state): State<AppState>,
     Path(tag): Path<String>,
-) -> Json<Vec<Service>> {
-    let services = state.registry.get_services_by_tag(&tag).await;
-    Json(services)
+) -> Result<Json<Vec<Service>>, StatusCode> {
+    let services = state
+        .registry
+        .get_services_by_tag(&tag)
+        .await
+        .map_err(registry_error)?;
+    Ok(Json(services))
 }
 
-pub async fn get_events(State(_state): State<AppState>) -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "message": "Real-time events available via WebSocket at /ws"
-    }))
+/// Replays missed events as JSON when `since` is given, otherwise streams
+/// events as SSE
+pub async fn get_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<EventQuery>,
+) -> Response {
+    let filter = EventFilter::from(&query);
+    let wants_stream = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+
+    match query.since {
+        Some(since) if !wants_stream => {
+            let body = match state.registry.events_since(since) {
+                EventReplay::Events {
+                    events,
+                    last_sequence,
+                } => serde_json::json!({
+                    "resync": false,
+                    "events": events
+                        .into_iter()
+                        .filter(|event| filter.matches(event))
+                        .collect::<Vec<_>>(),
+                    "last_sequence": last_sequence
+                }),
+                EventReplay::TooOld {
+                    oldest_sequence,
+                    last_sequence,
+                } => serde_json::json!({
+                    "resync": true,
+                    "events": [],
+                    "oldest_sequence": oldest_sequence,
+                    "last_sequence": last_sequence
+                }),
+            };
+            Json(body).into_response()
+        }
+        since => sse::event_stream(
+            &state.registry,
+            sse::last_event_id(&headers).or(since),
+            filter,
+        )
+        .into_response(),
+    }
 }
 
 pub async fn watch_service(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "message": format!("Service {} monitoring available via WebSocket", name),
-        "websocket_url": "/ws"
-    }))
+    headers: HeaderMap,
+    Query(query): Query<EventQuery>,
+) -> Response {
+    let filter = EventFilter {
+        services: vec![name],
+        ..EventFilter::from(&query)
+    };
+
+    let since = sse::last_event_id(&headers).or(query.since);
+    sse::event_stream(&state.registry, since, filter).into_response()
+}
+
+pub async fn cluster_status(State(state): State<AppState>) -> Json<serde_json::Value> {
+    match &state.cluster {
+        Some(node) => Json(serde_json::json!({
+            "enabled": true,
+            "status": node.status()
+        })),
+        None => Json(serde_json::json!({
+            "enabled": false
+        })),
+    }
+}
+
+/// Recent deliveries and counters of the configured webhooks
+pub async fn webhook_deliveries(
+    State(state): State<AppState>,
+    Query(query): Query<WebhookQuery>,
+) -> Json<Vec<WebhookReport>> {
+    let mut reports = state.webhooks.status();
+    if let Some(name) = &query.webhook {
+        reports.retain(|report| &report.name == name);
+    }
+    Json(reports)
+}
+
+/// Audit entries matching the time, actor, action and service filters
+pub async fn audit_entries(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, (StatusCode, String)> {
+    if !state.audit.is_enabled() {
+        return Err((StatusCode::NOT_FOUND, "Audit log disabled".to_string()));
+    }
+
+    state.audit.query(&query).map(Json).map_err(|e| {
+        tracing::error!("❌ Failed to read audit log: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })
+}
+
+/// Issues a short-lived certificate from the internal CA to a registered
+/// instance, naming its service under the configured `pki.domain`
+pub async fn issue_certificate(
+    State(state): State<AppState>,
+    actor: Actor,
+    key: Option<Extension<AuthenticatedKey>>,
+    Json(request): Json<IssueCertificateRequest>,
+) -> Result<(StatusCode, Json<IssuedCertificate>), (StatusCode, String)> {
+    let Some(ca) = &state.pki else {
+        return Err((StatusCode::NOT_FOUND, "Internal CA disabled".to_string()));
+    };
+
+    authorize(
+        key.as_deref(),
+        &Scope::Register(request.service_name.clone()),
+    )?;
+
+    let instance = instance_of_service(&state, &request.service_name, &request.instance_id)
+        .map_err(registry_failure)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!(
+                    "Instance {} is not registered in service {}",
+                    request.instance_id, request.service_name
+                ),
+            )
+        })?;
+
+    let validity = match request.validity_hours {
+        Some(0) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "validity_hours must be positive".to_string(),
+            ))
+        }
+        Some(hours) => time::Duration::hours(hours.into()),
+        None => ca.leaf_validity(),
+    };
+
+    // Only names the CA controls, never the host the caller registered
+    let issued = ca
+        .issue(&instance.service_name, validity, request.csr.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    tracing::info!(
+        "🏛️ Issued certificate {} to {} ({}), valid until {}",
+        issued.serial_number,
+        instance.service_name,
+        instance.id,
+        issued.not_after
+    );
+    state.audit.record(
+        actor,
+        AuditAction::IssueCertificate,
+        instance_target(&instance),
+        None,
+        Some(serde_json::json!({
+            "serial_number": issued.serial_number,
+            "not_after": issued.not_after,
+        })),
+    );
+
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+/// Looks up an instance addressed as `/services/{name}/instances/{id}`
+fn instance_of_service(
+    state: &AppState,
+    name: &str,
+    id: &str,
+) -> anyhow::Result<Option<ServiceInstance>> {
+    Ok(state
+        .registry
+        .get_instance(id)?
+        .filter(|instance| instance.service_name == name))
+}
+
+/// State of an instance after a change, for the audit trail
+fn current_instance(state: &AppState, id: &str) -> Option<serde_json::Value> {
+    state
+        .registry
+        .get_instance(id)
+        .ok()
+        .flatten()
+        .as_ref()
+        .and_then(to_audit_value)
+}
+
+/// Logs a registry failure and answers 500; the details stay in the log
+pub(crate) fn registry_error(e: anyhow::Error) -> StatusCode {
+    tracing::error!("❌ Registry unavailable: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+fn registry_failure(e: anyhow::Error) -> (StatusCode, String) {
+    (registry_error(e), "Registry unavailable".to_string())
+}
+
+fn instance_target(instance: &ServiceInstance) -> AuditTarget {
+    AuditTarget {
+        service: instance.service_name.clone(),
+        instance_id: Some(instance.id.clone()),
+    }
+}
+
+fn service_target(name: &str) -> AuditTarget {
+    AuditTarget {
+        service: name.to_string(),
+        instance_id: None,
+    }
+}
+
+/// Validates a maintenance request into the window to start now
+fn maintenance_window(
+    request: MaintenanceRequest,
+    actor: &Actor,
+) -> Result<Maintenance, (StatusCode, String)> {
+    let bad_request = |message: &str| Err((StatusCode::BAD_REQUEST, message.to_string()));
+
+    let reason = request.reason.trim();
+    if reason.is_empty() {
+        return bad_request("reason must not be empty");
+    }
+
+    let now = chrono::Utc::now();
+    let expires_at = match (request.expires_at, request.duration_seconds) {
+        (Some(_), Some(_)) => {
+            return bad_request("expires_at and duration_seconds are mutually exclusive")
+        }
+        (Some(expires_at), None) if expires_at <= now => {
+            return bad_request("expires_at must be in the future")
+        }
+        (None, Some(0)) => return bad_request("duration_seconds must be positive"),
+        (Some(expires_at), None) => Some(expires_at),
+        (None, Some(seconds)) => i64::try_from(seconds)
+            .ok()
+            .and_then(chrono::TimeDelta::try_seconds)
+            .and_then(|duration| now.checked_add_signed(duration))
+            .map(Some)
+            .ok_or((
+                StatusCode::BAD_REQUEST,
+                "duration_seconds is too large".to_string(),
+            ))?,
+        (None, None) => None,
+    };
+
+    Ok(Maintenance {
+        reason: reason.to_string(),
+        author: request
+            .author
+            .map(|author| author.trim().to_string())
+            .filter(|author| !author.is_empty())
+            .unwrap_or_else(|| actor.name()),
+        started_at: now,
+        expires_at,
+        service_wide: false,
+        previous_status: None,
+    })
+}
+
+fn to_audit_value<T: serde::Serialize>(value: &T) -> Option<serde_json::Value> {
+    serde_json::to_value(value).ok()
+}
+
+/// Holds a blocking query until the index moves past the client's, and
+/// returns the index to report
+async fn block_on_index(
+    state: &AppState,
+    service_name: Option<&str>,
+    query: &BlockingQuery,
+) -> Result<u64, (StatusCode, String)> {
+    let Some(index) = query.index else {
+        return Ok(state.registry.index(service_name));
+    };
+
+    let wait = match &query.wait {
+        Some(wait) => parse_wait(wait).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid wait duration: {}", wait),
+            )
+        })?,
+        None => DEFAULT_BLOCKING_WAIT,
+    };
+
+    Ok(state
+        .registry
+        .wait_for_index(service_name, index, wait.min(MAX_BLOCKING_WAIT))
+        .await)
+}
+
+fn parse_wait(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Some(millis) = value.strip_suffix("ms") {
+        return millis.parse().ok().map(Duration::from_millis);
+    }
+    if let Some(seconds) = value.strip_suffix('s') {
+        return seconds.parse().ok().map(Duration::from_secs);
+    }
+    if let Some(minutes) = value.strip_suffix('m') {
+        return minutes
+            .parse::<u64>()
+            .ok()
+            .and_then(|minutes| minutes.checked_mul(60))
+            .map(Duration::from_secs);
+    }
+    value.parse().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wait() {
+        assert_eq!(parse_wait("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_wait("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_wait("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_wait("15"), Some(Duration::from_secs(15)));
+        assert_eq!(parse_wait("soon"), None);
+        assert_eq!(parse_wait("307445734561825861m"), None);
+    }
 }
@@ -0,0 +1,399 @@
+//! Audit trail of the registry changes made through the API
+//!
+//! Every registration, deregistration, service deletion, forced status
+//! change, maintenance window and certificate issued by the internal CA is
+//! appended as one JSON document per line to the audit file, with the actor
+//! that made the call and the state before and after the change. Heartbeats
+//! and health check results are not audited.
+//!
+//! The file is only ever appended to. In a cluster, each node records the
+//! calls it received, since only that node knows who made them.
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::middleware::auth::AuthenticatedKey;
+use crate::middleware::ip_restriction::ClientIp;
+use crate::tls::ClientIdentity;
+use crate::AuditConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    RegisterInstance,
+    DeregisterInstance,
+    DeleteService,
+    UpdateStatus,
+    IssueCertificate,
+    StartMaintenance,
+    EndMaintenance,
+}
+
+/// Who made an API call
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+    /// Id of the API key the request was authenticated with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Name of the verified client certificate, when mutual TLS is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    pub ip: Option<IpAddr>,
+}
+
+impl Actor {
+    /// Most specific identity known: API key, client certificate, then address
+    pub fn name(&self) -> String {
+        self.api_key
+            .clone()
+            .or_else(|| self.client_cert.clone())
+            .or_else(|| self.ip.map(|ip| ip.to_string()))
+            .unwrap_or_else(|| "anonymous".to_string())
+    }
+
+    fn matches(&self, actor: &str) -> bool {
+        self.api_key.as_deref() == Some(actor)
+            || self.client_cert.as_deref() == Some(actor)
+            || self.ip.is_some_and(|ip| ip.to_string() == actor)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Prefer the address resolved by the IP restriction middleware, which
+        // honours the trusted proxy headers
+        let ip = parts
+            .extensions
+            .get::<ClientIp>()
+            .map(|client_ip| client_ip.0)
+            .or_else(|| {
+                parts
+                    .extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|connect_info| connect_info.0.ip())
+            });
+
+        let api_key = parts
+            .extensions
+            .get::<AuthenticatedKey>()
+            .map(|key| key.id.clone());
+
+        let client_cert = parts
+            .extensions
+            .get::<ClientIdentity>()
+            .and_then(|identity| identity.name())
+            .map(str::to_string);
+
+        Ok(Self {
+            api_key,
+            client_cert,
+            ip,
+        })
+    }
+}
+
+/// What a call changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditTarget {
+    pub service: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub actor: Actor,
+    pub action: AuditAction,
+    pub target: AuditTarget,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Filters accepted by `GET /api/admin/audit`
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    /// Entries recorded at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Entries recorded before this time
+    pub to: Option<DateTime<Utc>>,
+    /// API key id, client certificate name or client IP
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub service: Option<String>,
+    /// Maximum number of entries returned, the most recent ones
+    pub limit: Option<usize>,
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    /// `None` when auditing is disabled
+    file: Option<Mutex<File>>,
+    max_results: usize,
+}
+
+impl AuditLog {
+    pub fn open(config: &AuditConfig) -> anyhow::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if !config.enabled {
+            return Ok(Self {
+                path,
+                file: None,
+                max_results: config.max_results,
+            });
+        }
+
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to create audit log directory {}: {}",
+                    parent.display(),
+                    e
+                )
+            })?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to open audit log {}: {}", path.display(), e))?;
+
+        Ok(Self {
+            path,
+            file: Some(Mutex::new(file)),
+            max_results: config.max_results,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends an entry, logging instead of failing the API call on errors
+    pub fn record(
+        &self,
+        actor: Actor,
+        action: AuditAction,
+        target: AuditTarget,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+    ) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        let entry = AuditEntry {
+            id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            actor,
+            action,
+            target,
+            before,
+            after,
+        };
+
+        let result = serde_json::to_string(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut line| {
+                line.push('\n');
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                file.write_all(line.as_bytes())?;
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            tracing::error!("❌ Failed to write audit entry {:?}: {}", entry, e);
+        }
+    }
+
+    /// Most recent entries matching `query`, oldest first
+    pub fn query(&self, query: &AuditQuery) -> anyhow::Result<Vec<AuditEntry>> {
+        if !self.is_enabled() {
+            return Ok(vec![]);
+        }
+
+        let limit = query
+            .limit
+            .unwrap_or(self.max_results)
+            .min(self.max_results);
+        let mut entries = VecDeque::with_capacity(limit);
+
+        let file = File::open(&self.path)?;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry: AuditEntry = match serde_json::from_str(&line) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    tracing::warn!("⚠️ Skipping unreadable audit entry: {}", e);
+                    continue;
+                }
+            };
+            if !matches_query(&entry, query) {
+                continue;
+            }
+
+            if entries.len() == limit {
+                entries.pop_front();
+            }
+            if limit > 0 {
+                entries.push_back(entry);
+            }
+        }
+
+        Ok(entries.into())
+    }
+}
+
+fn matches_query(entry: &AuditEntry, query: &AuditQuery) -> bool {
+    query.from.is_none_or(|from| entry.timestamp >= from)
+        && query.to.is_none_or(|to| entry.timestamp < to)
+        && query
+            .actor
+            .as_deref()
+            .is_none_or(|actor| entry.actor.matches(actor))
+        && query.action.is_none_or(|action| entry.action == action)
+        && query
+            .service
+            .as_deref()
+            .is_none_or(|service| entry.target.service == service)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn open_log(dir: &TempDir) -> AuditLog {
+        AuditLog::open(&AuditConfig {
+            enabled: true,
+            path: dir
+                .path()
+                .join("audit/audit.jsonl")
+                .to_string_lossy()
+                .to_string(),
+            max_results: 100,
+        })
+        .unwrap()
+    }
+
+    fn actor(ip: &str) -> Actor {
+        Actor {
+            api_key: None,
+            client_cert: None,
+            ip: Some(ip.parse().unwrap()),
+        }
+    }
+
+    fn target(service: &str) -> AuditTarget {
+        AuditTarget {
+            service: service.to_string(),
+            instance_id: Some("instance-1".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_entries_filtered_by_actor_action_and_time() {
+        let dir = TempDir::new().unwrap();
+        let log = open_log(&dir);
+
+        log.record(
+            actor("10.0.0.1"),
+            AuditAction::RegisterInstance,
+            target("users"),
+            None,
+            Some(serde_json::json!({"status": "Up"})),
+        );
+        let between = Utc::now();
+        log.record(
+            actor("10.0.0.2"),
+            AuditAction::UpdateStatus,
+            target("users"),
+            Some(serde_json::json!({"status": "Up"})),
+            Some(serde_json::json!({"status": "Maintenance"})),
+        );
+        log.record(
+            actor("10.0.0.1"),
+            AuditAction::DeregisterInstance,
+            target("orders"),
+            Some(serde_json::json!({"status": "Up"})),
+            None,
+        );
+
+        let by_actor = log
+            .query(&AuditQuery {
+                actor: Some("10.0.0.1".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(by_actor.len(), 2);
+        assert_eq!(by_actor[0].action, AuditAction::RegisterInstance);
+
+        let recent = log
+            .query(&AuditQuery {
+                from: Some(between),
+                service: Some("users".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].after.as_ref().unwrap()["status"], "Maintenance");
+
+        let last = log
+            .query(&AuditQuery {
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].action, AuditAction::DeregisterInstance);
+    }
+
+    #[test]
+    fn test_entries_kept_across_reopen() {
+        let dir = TempDir::new().unwrap();
+        open_log(&dir).record(
+            actor("10.0.0.1"),
+            AuditAction::DeleteService,
+            target("users"),
+            None,
+            None,
+        );
+
+        let log = open_log(&dir);
+        log.record(
+            actor("10.0.0.1"),
+            AuditAction::DeleteService,
+            target("orders"),
+            None,
+            None,
+        );
+
+        assert_eq!(log.query(&AuditQuery::default()).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,1439 @@
+//! Multi-node clustering with Raft replication
+//!
+//! Every registry mutation is proposed to the cluster leader, appended to a
+//! replicated log and applied on each node once a majority has stored it.
+//! Followers forward writes to the leader; reads are served locally,
+//! subject to the configured [`ReadConsistency`].
+//!
+//! Nodes talk to each other over HTTP under the `/cluster` prefix of the
+//! regular listener (see [`rpc`]).
+
+pub mod raft;
+pub mod rpc;
+pub mod storage;
+
+use axum::{
+    extract::{OriginalUri, Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Redirect, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, watch, Notify};
+use tokio::task::JoinSet;
+
+use crate::models::RegistryMutation;
+use crate::persistence::RegistrySnapshot;
+use crate::registry::ServiceRegistry;
+use crate::ClusterConfig;
+use raft::{Command, LogEntry, PendingProposal, RaftState, Role};
+use rpc::{
+    AppendEntriesRequest, AppendEntriesResponse, InstallSnapshotRequest, InstallSnapshotResponse,
+    NotDelivered, PeerClient, ProposeRequest, ProposeResponse, VoteRequest, VoteResponse,
+};
+use storage::{RaftStorage, RecoveredRaft, StoredSnapshot};
+
+const ELECTION_TICK: Duration = Duration::from_millis(10);
+const MAX_ENTRIES_PER_APPEND: usize = 512;
+
+/// How up to date a node must be to answer reads locally
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadConsistency {
+    /// Always serve reads locally, possibly from a stale follower
+    Stale,
+    /// Serve locally only if the leader was heard from within `max_staleness_ms`
+    Bounded,
+    /// Redirect reads to the leader
+    Leader,
+}
+
+/// Why a read cannot be served by this node
+#[derive(Debug)]
+pub enum ReadRejection {
+    Redirect(String),
+    Unavailable(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClusterStatus {
+    pub node_id: String,
+    pub advertise_url: String,
+    pub role: Role,
+    pub term: u64,
+    pub leader_id: Option<String>,
+    pub leader_url: Option<String>,
+    pub commit_index: u64,
+    pub last_applied: u64,
+    pub last_log_index: u64,
+    pub snapshot_index: u64,
+    pub read_consistency: ReadConsistency,
+    pub members: Vec<ClusterMember>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClusterMember {
+    pub id: String,
+    pub url: String,
+    pub is_self: bool,
+    pub is_leader: bool,
+    /// Replication progress, only known by the leader
+    pub match_index: Option<u64>,
+    /// Milliseconds since the leader last exchanged with this member
+    pub last_contact_ms: Option<u64>,
+}
+
+struct Peer {
+    id: String,
+    url: String,
+    notify: Notify,
+}
+
+enum Replication {
+    Append(AppendEntriesRequest),
+    Snapshot(InstallSnapshotRequest),
+}
+
+/// A member of a ScoutQuest cluster
+pub struct ClusterNode {
+    config: ClusterConfig,
+    peers: Vec<Peer>,
+    registry: Arc<ServiceRegistry>,
+    state: Mutex<RaftState>,
+    client: PeerClient,
+    applied_index: watch::Sender<u64>,
+    shutdown: watch::Sender<bool>,
+    storage: Option<Mutex<RaftStorage>>,
+}
+
+impl ClusterNode {
+    /// Creates a node and attaches it to the registry, so every registry
+    /// write goes through the replicated log from now on.
+    ///
+    /// When `data_dir` is set the term, vote, log and snapshots are persisted
+    /// there, and the registry is rebuilt from the last persisted snapshot;
+    /// the entries after it are applied again once committed.
+    pub fn new(
+        config: ClusterConfig,
+        registry: Arc<ServiceRegistry>,
+        data_dir: Option<&Path>,
+        peer_api_key: Option<String>,
+    ) -> anyhow::Result<Arc<Self>> {
+        validate_config(&config)?;
+
+        let peers = config
+            .peers
+            .iter()
+            .filter(|peer| peer.id != config.node_id)
+            .map(|peer| Peer {
+                id: peer.id.clone(),
+                url: peer.url.trim_end_matches('/').to_string(),
+                notify: Notify::new(),
+            })
+            .collect();
+
+        let (storage, recovered) = match data_dir {
+            Some(dir) => {
+                let (storage, recovered) = RaftStorage::open(dir)?;
+                (Some(Mutex::new(storage)), recovered)
+            }
+            None => (None, RecoveredRaft::default()),
+        };
+
+        let mut state = RaftState::new(
+            recovered.hard_state,
+            Instant::now() + random_election_timeout(&config),
+        );
+        // The store may hold state from before the restart, start over from
+        // the snapshot; later entries are applied again once committed
+        if storage.is_some() {
+            let snapshot = recovered
+                .snapshot
+                .as_ref()
+                .map(|stored| stored.snapshot.clone())
+                .unwrap_or_else(|| RegistrySnapshot {
+                    sequence: 0,
+                    event_sequence: 0,
+                    taken_at: chrono::Utc::now(),
+                    services: Vec::new(),
+                    instances: Vec::new(),
+                });
+            registry.restore_snapshot(&snapshot)?;
+        }
+        if let Some(stored) = recovered.snapshot {
+            state.snapshot_index = stored.index;
+            state.snapshot_term = stored.term;
+            state.snapshot = Some(stored.snapshot);
+            state.commit_index = stored.index;
+            state.last_applied = stored.index;
+        }
+        state.log = recovered.log;
+
+        if state.last_log_index() > 0 {
+            tracing::info!(
+                "💾 Cluster log recovered up to index {} (snapshot at {})",
+                state.last_log_index(),
+                state.snapshot_index
+            );
+        }
+
+        let (applied_index, _) = watch::channel(state.last_applied);
+        let (shutdown, _) = watch::channel(false);
+
+        let node = Arc::new(Self {
+            client: PeerClient::new(Duration::from_millis(config.rpc_timeout_ms), peer_api_key)?,
+            state: Mutex::new(state),
+            config,
+            peers,
+            registry,
+            applied_index,
+            shutdown,
+            storage,
+        });
+
+        node.registry.attach_cluster(&node);
+        Ok(node)
+    }
+
+    /// Starts the election timer and one replication task per peer
+    pub fn start(self: &Arc<Self>) {
+        tokio::spawn(self.clone().run_election_timer());
+        for peer_index in 0..self.peers.len() {
+            tokio::spawn(self.clone().run_replicator(peer_index));
+        }
+
+        tracing::info!(
+            "🕸️ Cluster node {} started with {} peer(s)",
+            self.config.node_id,
+            self.peers.len()
+        );
+    }
+
+    /// Stops the background tasks; the node stops taking part in the cluster
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+        self.lock_state().fail_pending("Node is shutting down");
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.config.node_id
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.lock_state().role == Role::Leader
+    }
+
+    pub fn status(&self) -> ClusterStatus {
+        let state = self.lock_state();
+        let is_leader = state.role == Role::Leader;
+
+        let mut members = vec![ClusterMember {
+            id: self.config.node_id.clone(),
+            url: self.config.advertise_url.clone(),
+            is_self: true,
+            is_leader,
+            match_index: is_leader.then(|| state.last_log_index()),
+            last_contact_ms: None,
+        }];
+
+        for peer in &self.peers {
+            members.push(ClusterMember {
+                id: peer.id.clone(),
+                url: peer.url.clone(),
+                is_self: false,
+                is_leader: state.leader_id.as_deref() == Some(peer.id.as_str()),
+                match_index: is_leader
+                    .then(|| state.match_index.get(&peer.id).copied().unwrap_or(0)),
+                last_contact_ms: state
+                    .peer_contact
+                    .get(&peer.id)
+                    .filter(|_| is_leader)
+                    .map(|contact| contact.elapsed().as_millis() as u64),
+            });
+        }
+
+        ClusterStatus {
+            node_id: self.config.node_id.clone(),
+            advertise_url: self.config.advertise_url.clone(),
+            role: state.role,
+            term: state.current_term,
+            leader_id: state.leader_id.clone(),
+            leader_url: state
+                .leader_id
+                .as_deref()
+                .and_then(|id| self.member_url(id)),
+            commit_index: state.commit_index,
+            last_applied: state.last_applied,
+            last_log_index: state.last_log_index(),
+            snapshot_index: state.snapshot_index,
+            read_consistency: self.config.read_consistency,
+            members,
+        }
+    }
+
+    /// Replicates a mutation through the cluster and returns the result of
+    /// applying it, forwarding to the leader when this node is a follower.
+    pub async fn propose(&self, mutation: RegistryMutation) -> anyhow::Result<bool> {
+        // There is briefly no leader during an election, or a stale one that
+        // cannot be reached, give a new one time to emerge
+        let deadline = Instant::now() + self.propose_timeout();
+
+        loop {
+            let (is_leader, leader_id) = {
+                let state = self.lock_state();
+                (state.role == Role::Leader, state.leader_id.clone())
+            };
+
+            if is_leader {
+                return self
+                    .propose_as_leader(mutation)
+                    .await
+                    .map(|(applied, _)| applied);
+            }
+            if let Some(leader_id) = leader_id {
+                match self.forward_to_leader(&leader_id, mutation.clone()).await {
+                    Err(e) if e.is::<NotDelivered>() && Instant::now() < deadline => {
+                        tracing::debug!("Write not delivered to {}: {}", leader_id, e);
+                    }
+                    result => return result,
+                }
+            } else if Instant::now() >= deadline {
+                return Err(anyhow::anyhow!("No cluster leader elected"));
+            }
+            tokio::time::sleep(ELECTION_TICK).await;
+        }
+    }
+
+    /// Appends a mutation to the log and waits until it is committed and
+    /// applied. Fails if this node is not the leader.
+    pub async fn propose_as_leader(
+        &self,
+        mutation: RegistryMutation,
+    ) -> anyhow::Result<(bool, u64)> {
+        let (receiver, index) = {
+            let mut state = self.lock_state();
+            if state.role != Role::Leader {
+                return Err(anyhow::anyhow!(
+                    "Node {} is not the cluster leader",
+                    self.config.node_id
+                ));
+            }
+
+            let index = state.append(Command::Mutation { mutation });
+            self.persist_entries(&mut state, index)?;
+            let (sender, receiver) = oneshot::channel();
+            let term = state.current_term;
+            state
+                .pending
+                .insert(index, PendingProposal { term, sender });
+
+            if self.peers.is_empty() {
+                self.advance_commit_index(&mut state);
+            }
+            (receiver, index)
+        };
+
+        self.notify_peers();
+
+        match tokio::time::timeout(self.propose_timeout(), receiver).await {
+            Ok(Ok(result)) => result.map(|applied| (applied, index)),
+            Ok(Err(_)) => Err(anyhow::anyhow!("Write was dropped before being committed")),
+            Err(_) => Err(anyhow::anyhow!(
+                "Timed out waiting for the cluster to commit the write"
+            )),
+        }
+    }
+
+    /// Checks whether a read may be served locally under the configured
+    /// read consistency
+    pub fn check_read(&self) -> Result<(), ReadRejection> {
+        let state = self.lock_state();
+        if state.role == Role::Leader && self.config.read_consistency != ReadConsistency::Bounded {
+            return Ok(());
+        }
+
+        match self.config.read_consistency {
+            ReadConsistency::Stale => Ok(()),
+            ReadConsistency::Leader => match state
+                .leader_id
+                .as_deref()
+                .and_then(|id| self.member_url(id))
+            {
+                Some(leader_url) => Err(ReadRejection::Redirect(leader_url)),
+                None => Err(ReadRejection::Unavailable(
+                    "No cluster leader elected".to_string(),
+                )),
+            },
+            ReadConsistency::Bounded => {
+                let max_staleness = Duration::from_millis(self.config.max_staleness_ms);
+
+                let fresh = if state.role == Role::Leader {
+                    // A leader is only current while a majority still follows it
+                    let reachable = state
+                        .peer_contact
+                        .values()
+                        .filter(|contact| contact.elapsed() <= max_staleness)
+                        .count();
+                    reachable + 1 >= self.quorum()
+                } else {
+                    state
+                        .last_leader_contact
+                        .is_some_and(|contact| contact.elapsed() <= max_staleness)
+                };
+
+                if fresh {
+                    Ok(())
+                } else {
+                    Err(ReadRejection::Unavailable(format!(
+                        "Node {} has not been in contact with the cluster for more than {}ms",
+                        self.config.node_id, self.config.max_staleness_ms
+                    )))
+                }
+            }
+        }
+    }
+
+    pub fn handle_vote(&self, request: VoteRequest) -> VoteResponse {
+        let mut state = self.lock_state();
+
+        if request.term > state.current_term {
+            self.become_follower(&mut state, request.term);
+        }
+
+        let vote_granted = request.term == state.current_term
+            && state
+                .voted_for
+                .as_ref()
+                .is_none_or(|voted_for| *voted_for == request.candidate_id)
+            && state.is_log_up_to_date(request.last_log_index, request.last_log_term);
+
+        if vote_granted {
+            state.voted_for = Some(request.candidate_id.clone());
+            state.election_deadline = self.next_election_deadline();
+            self.persist_hard_state(&state);
+            tracing::debug!(
+                "🗳️ Node {} voted for {} in term {}",
+                self.config.node_id,
+                request.candidate_id,
+                request.term
+            );
+        }
+
+        VoteResponse {
+            term: state.current_term,
+            vote_granted,
+        }
+    }
+
+    pub fn handle_append_entries(&self, request: AppendEntriesRequest) -> AppendEntriesResponse {
+        let mut state = self.lock_state();
+
+        if request.term < state.current_term {
+            return AppendEntriesResponse {
+                term: state.current_term,
+                success: false,
+                match_index: 0,
+                conflict_index: None,
+            };
+        }
+
+        self.follow_leader(&mut state, request.term, &request.leader_id);
+
+        let reject = |state: &RaftState, conflict_index: u64| AppendEntriesResponse {
+            term: state.current_term,
+            success: false,
+            match_index: 0,
+            conflict_index: Some(conflict_index),
+        };
+
+        if request.prev_log_index > state.last_log_index() {
+            return reject(&state, state.last_log_index() + 1);
+        }
+        if request.prev_log_index >= state.snapshot_index
+            && state.term_at(request.prev_log_index) != Some(request.prev_log_term)
+        {
+            let conflict_index = state.first_index_of_term_at(request.prev_log_index);
+            return reject(&state, conflict_index);
+        }
+
+        let last_new_index = request.prev_log_index + request.entries.len() as u64;
+
+        let mut first_new_index = None;
+        for entry in request.entries {
+            if entry.index <= state.snapshot_index {
+                continue;
+            }
+            match state.term_at(entry.index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => state.truncate_from(entry.index),
+                None => {}
+            }
+            first_new_index.get_or_insert(entry.index);
+            state.log.push(entry);
+        }
+
+        // Entries are only acknowledged once they are on disk
+        if let Some(index) = first_new_index {
+            if let Err(e) = self.persist_entries(&mut state, index) {
+                tracing::error!("❌ Failed to persist cluster log: {}", e);
+                return reject(&state, index);
+            }
+        }
+
+        if request.leader_commit > state.commit_index {
+            state.commit_index = request
+                .leader_commit
+                .min(last_new_index)
+                .max(state.commit_index);
+            self.apply_committed(&mut state);
+        }
+
+        AppendEntriesResponse {
+            term: state.current_term,
+            success: true,
+            match_index: last_new_index,
+            conflict_index: None,
+        }
+    }
+
+    pub fn handle_install_snapshot(
+        &self,
+        request: InstallSnapshotRequest,
+    ) -> InstallSnapshotResponse {
+        let mut state = self.lock_state();
+
+        if request.term < state.current_term {
+            return InstallSnapshotResponse {
+                term: state.current_term,
+            };
+        }
+
+        self.follow_leader(&mut state, request.term, &request.leader_id);
+
+        if request.last_included_index <= state.commit_index {
+            return InstallSnapshotResponse {
+                term: state.current_term,
+            };
+        }
+
+        let index = request.last_included_index;
+        let log = if state.term_at(index) == Some(request.last_included_term) {
+            state.entries_from(index + 1, usize::MAX)
+        } else {
+            Vec::new()
+        };
+        let stored = StoredSnapshot {
+            index,
+            term: request.last_included_term,
+            snapshot: request.snapshot,
+        };
+
+        let installed = self
+            .persist_snapshot(&stored, &log)
+            .and_then(|_| self.registry.restore_snapshot(&stored.snapshot));
+        if let Err(e) = installed {
+            tracing::error!("❌ Failed to install cluster snapshot: {}", e);
+            return InstallSnapshotResponse {
+                term: state.current_term,
+            };
+        }
+
+        state.log = log;
+        state.snapshot_index = index;
+        state.snapshot_term = stored.term;
+        state.snapshot = Some(stored.snapshot);
+        state.commit_index = index;
+        state.last_applied = index;
+        self.applied_index.send_replace(index);
+
+        tracing::info!(
+            "🕸️ Node {} installed snapshot up to index {}",
+            self.config.node_id,
+            index
+        );
+
+        InstallSnapshotResponse {
+            term: state.current_term,
+        }
+    }
+
+    async fn forward_to_leader(
+        &self,
+        leader_id: &str,
+        mutation: RegistryMutation,
+    ) -> anyhow::Result<bool> {
+        let leader_url = self
+            .member_url(leader_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown cluster leader {}", leader_id))?;
+
+        let response: ProposeResponse = self
+            .client
+            .call_with_timeout(
+                &leader_url,
+                "/propose",
+                &ProposeRequest { mutation },
+                self.propose_timeout(),
+            )
+            .await?;
+
+        // Give read-your-writes semantics to the client that hit this node
+        let mut applied_index = self.applied_index.subscribe();
+        let _ = tokio::time::timeout(
+            self.propose_timeout(),
+            applied_index.wait_for(|applied| *applied >= response.index),
+        )
+        .await;
+
+        Ok(response.applied)
+    }
+
+    async fn run_election_timer(self: Arc<Self>) {
+        let mut shutdown = self.shutdown.subscribe();
+
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(ELECTION_TICK) => {}
+                _ = shutdown.changed() => break,
+            }
+
+            let timed_out = {
+                let state = self.lock_state();
+                state.role != Role::Leader && Instant::now() >= state.election_deadline
+            };
+
+            if timed_out {
+                self.start_election().await;
+            }
+        }
+    }
+
+    async fn start_election(&self) {
+        let request = {
+            let mut state = self.lock_state();
+            state.role = Role::Candidate;
+            state.current_term += 1;
+            state.voted_for = Some(self.config.node_id.clone());
+            state.leader_id = None;
+            state.election_deadline = self.next_election_deadline();
+            self.persist_hard_state(&state);
+
+            VoteRequest {
+                term: state.current_term,
+                candidate_id: self.config.node_id.clone(),
+                last_log_index: state.last_log_index(),
+                last_log_term: state.last_log_term(),
+            }
+        };
+        let term = request.term;
+
+        tracing::debug!(
+            "🗳️ Node {} starting election for term {}",
+            self.config.node_id,
+            term
+        );
+
+        let mut votes = 1;
+        if votes >= self.quorum() {
+            let mut state = self.lock_state();
+            self.become_leader(&mut state);
+            return;
+        }
+
+        let request = Arc::new(request);
+        let mut calls = JoinSet::new();
+        for peer in &self.peers {
+            let client = self.client.clone();
+            let url = peer.url.clone();
+            let request = request.clone();
+            calls.spawn(async move {
+                client
+                    .call::<_, VoteResponse>(&url, "/raft/vote", request.as_ref())
+                    .await
+            });
+        }
+
+        while let Some(result) = calls.join_next().await {
+            let Ok(Ok(response)) = result else {
+                continue;
+            };
+
+            let mut state = self.lock_state();
+            if response.term > state.current_term {
+                self.become_follower(&mut state, response.term);
+                return;
+            }
+            if state.role != Role::Candidate || state.current_term != term {
+                return;
+            }
+
+            if response.vote_granted {
+                votes += 1;
+                if votes >= self.quorum() {
+                    self.become_leader(&mut state);
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn run_replicator(self: Arc<Self>, peer_index: usize) {
+        let peer = &self.peers[peer_index];
+        let heartbeat_interval = Duration::from_millis(self.config.heartbeat_interval_ms);
+        let mut shutdown = self.shutdown.subscribe();
+        let mut send_now = false;
+
+        loop {
+            if !send_now {
+                tokio::select! {
+                    _ = peer.notify.notified() => {}
+                    _ = tokio::time::sleep(heartbeat_interval) => {}
+                    _ = shutdown.changed() => break,
+                }
+            }
+            if *shutdown.borrow() {
+                break;
+            }
+            send_now = false;
+
+            let Some(replication) = self.prepare_replication(&peer.id) else {
+                continue;
+            };
+
+            match replication {
+                Replication::Append(request) => {
+                    let term = request.term;
+                    match self
+                        .client
+                        .call::<_, AppendEntriesResponse>(&peer.url, "/raft/append", &request)
+                        .await
+                    {
+                        Ok(response) => {
+                            send_now = self.handle_append_response(&peer.id, term, response)
+                        }
+                        Err(e) => tracing::debug!("Append to {} failed: {}", peer.id, e),
+                    }
+                }
+                Replication::Snapshot(request) => {
+                    let term = request.term;
+                    let index = request.last_included_index;
+                    tracing::info!("🕸️ Sending snapshot up to index {} to {}", index, peer.id);
+                    match self
+                        .client
+                        .call::<_, InstallSnapshotResponse>(&peer.url, "/raft/snapshot", &request)
+                        .await
+                    {
+                        Ok(response) => {
+                            send_now =
+                                self.handle_snapshot_response(&peer.id, term, index, response)
+                        }
+                        Err(e) => tracing::debug!("Snapshot to {} failed: {}", peer.id, e),
+                    }
+                }
+            }
+        }
+    }
+
+    fn prepare_replication(&self, peer_id: &str) -> Option<Replication> {
+        let state = self.lock_state();
+        if state.role != Role::Leader {
+            return None;
+        }
+
+        let next_index = state
+            .next_index
+            .get(peer_id)
+            .copied()
+            .unwrap_or(state.last_log_index() + 1);
+
+        if next_index <= state.snapshot_index {
+            if let Some(snapshot) = &state.snapshot {
+                return Some(Replication::Snapshot(InstallSnapshotRequest {
+                    term: state.current_term,
+                    leader_id: self.config.node_id.clone(),
+                    last_included_index: state.snapshot_index,
+                    last_included_term: state.snapshot_term,
+                    snapshot: snapshot.clone(),
+                }));
+            }
+        }
+
+        let prev_log_index = next_index - 1;
+        Some(Replication::Append(AppendEntriesRequest {
+            term: state.current_term,
+            leader_id: self.config.node_id.clone(),
+            prev_log_index,
+            prev_log_term: state.term_at(prev_log_index).unwrap_or(0),
+            entries: state.entries_from(next_index, MAX_ENTRIES_PER_APPEND),
+            leader_commit: state.commit_index,
+        }))
+    }
+
+    /// Returns true when more entries should be sent right away
+    fn handle_append_response(
+        &self,
+        peer_id: &str,
+        term: u64,
+        response: AppendEntriesResponse,
+    ) -> bool {
+        let mut state = self.lock_state();
+
+        if response.term > state.current_term {
+            self.become_follower(&mut state, response.term);
+            return false;
+        }
+        if state.role != Role::Leader || state.current_term != term {
+            return false;
+        }
+
+        state
+            .peer_contact
+            .insert(peer_id.to_string(), Instant::now());
+
+        if response.success {
+            let match_index = state
+                .match_index
+                .get(peer_id)
+                .copied()
+                .unwrap_or(0)
+                .max(response.match_index);
+            state.match_index.insert(peer_id.to_string(), match_index);
+            state
+                .next_index
+                .insert(peer_id.to_string(), match_index + 1);
+            self.advance_commit_index(&mut state);
+            match_index < state.last_log_index()
+        } else {
+            let current = state.next_index.get(peer_id).copied().unwrap_or(1);
+            let next_index = response
+                .conflict_index
+                .unwrap_or(current.saturating_sub(1))
+                .max(1);
+            state.next_index.insert(peer_id.to_string(), next_index);
+            true
+        }
+    }
+
+    fn handle_snapshot_response(
+        &self,
+        peer_id: &str,
+        term: u64,
+        index: u64,
+        response: InstallSnapshotResponse,
+    ) -> bool {
+        let mut state = self.lock_state();
+
+        if response.term > state.current_term {
+            self.become_follower(&mut state, response.term);
+            return false;
+        }
+        if state.role != Role::Leader || state.current_term != term {
+            return false;
+        }
+
+        state
+            .peer_contact
+            .insert(peer_id.to_string(), Instant::now());
+        let match_index = state
+            .match_index
+            .get(peer_id)
+            .copied()
+            .unwrap_or(0)
+            .max(index);
+        state.match_index.insert(peer_id.to_string(), match_index);
+        state
+            .next_index
+            .insert(peer_id.to_string(), match_index + 1);
+        true
+    }
+
+    /// Commits the highest entry of the current term stored on a majority
+    fn advance_commit_index(&self, state: &mut RaftState) {
+        let quorum = self.quorum();
+        let mut index = state.last_log_index();
+
+        while index > state.commit_index {
+            if state.term_at(index) == Some(state.current_term) {
+                let replicated = 1 + self
+                    .peers
+                    .iter()
+                    .filter(|peer| state.match_index.get(&peer.id).copied().unwrap_or(0) >= index)
+                    .count();
+
+                if replicated >= quorum {
+                    state.commit_index = index;
+                    break;
+                }
+            }
+            index -= 1;
+        }
+
+        self.apply_committed(state);
+    }
+
+    /// Applies committed entries to the registry, in log order
+    fn apply_committed(&self, state: &mut RaftState) {
+        while state.last_applied < state.commit_index {
+            let index = state.last_applied + 1;
+            let Some(entry) = state.entry(index).cloned() else {
+                break;
+            };
+
+            let result = match entry.command {
+                Command::Noop => Ok(false),
+                Command::Mutation { mutation } => self.registry.commit(mutation),
+            };
+            state.last_applied = index;
+
+            if let Some(pending) = state.pending.remove(&index) {
+                let result = if pending.term == entry.term {
+                    result
+                } else {
+                    Err(anyhow::anyhow!("Entry overwritten by a new leader"))
+                };
+                let _ = pending.sender.send(result);
+            }
+        }
+
+        self.applied_index.send_replace(state.last_applied);
+        self.maybe_compact(state);
+    }
+
+    /// Folds applied entries into a registry snapshot once the log grows
+    /// past the configured threshold
+    fn maybe_compact(&self, state: &mut RaftState) {
+        if state.log.len() <= self.config.snapshot_threshold
+            || state.last_applied <= state.snapshot_index
+        {
+            return;
+        }
+
+        let index = state.last_applied;
+        let Some(term) = state.term_at(index) else {
+            return;
+        };
+        let compacted = self.registry.capture_snapshot(index).and_then(|snapshot| {
+            let stored = StoredSnapshot {
+                index,
+                term,
+                snapshot,
+            };
+            self.persist_snapshot(&stored, &state.entries_from(index + 1, usize::MAX))?;
+            Ok(stored.snapshot)
+        });
+
+        match compacted {
+            Ok(snapshot) => {
+                state.compact(index, snapshot);
+                tracing::debug!(
+                    "🕸️ Node {} compacted its log up to index {}",
+                    self.config.node_id,
+                    index
+                );
+            }
+            Err(e) => tracing::error!("❌ Failed to compact cluster log: {}", e),
+        }
+    }
+
+    fn become_leader(&self, state: &mut RaftState) {
+        // Committing an entry of our own term also commits everything before it
+        let next_index = state.append(Command::Noop);
+        if let Err(e) = self.persist_entries(state, next_index) {
+            tracing::error!(
+                "❌ Node {} cannot lead, failed to persist its log: {}",
+                self.config.node_id,
+                e
+            );
+            return;
+        }
+
+        state.role = Role::Leader;
+        state.leader_id = Some(self.config.node_id.clone());
+        state.last_leader_contact = Some(Instant::now());
+
+        state.next_index = self
+            .peers
+            .iter()
+            .map(|peer| (peer.id.clone(), next_index))
+            .collect();
+        state.match_index.clear();
+        state.peer_contact.clear();
+
+        if self.peers.is_empty() {
+            self.advance_commit_index(state);
+        }
+
+        tracing::info!(
+            "👑 Node {} elected cluster leader for term {}",
+            self.config.node_id,
+            state.current_term
+        );
+        self.notify_peers();
+    }
+
+    fn become_follower(&self, state: &mut RaftState, term: u64) {
+        if term > state.current_term {
+            state.current_term = term;
+            state.voted_for = None;
+            self.persist_hard_state(state);
+        }
+
+        if state.role == Role::Leader {
+            tracing::info!(
+                "🕸️ Node {} stepping down as leader (term {})",
+                self.config.node_id,
+                state.current_term
+            );
+            state.fail_pending("Leadership lost before the write was committed");
+        }
+
+        state.role = Role::Follower;
+        state.election_deadline = self.next_election_deadline();
+    }
+
+    /// Records contact with the leader of `term`
+    fn follow_leader(&self, state: &mut RaftState, term: u64, leader_id: &str) {
+        if term > state.current_term || state.role != Role::Follower {
+            self.become_follower(state, term);
+        }
+
+        if state.leader_id.as_deref() != Some(leader_id) {
+            tracing::info!(
+                "🕸️ Node {} following leader {} (term {})",
+                self.config.node_id,
+                leader_id,
+                term
+            );
+        }
+
+        state.leader_id = Some(leader_id.to_string());
+        state.last_leader_contact = Some(Instant::now());
+        state.election_deadline = self.next_election_deadline();
+    }
+
+    fn persist_hard_state(&self, state: &RaftState) {
+        let Some(storage) = self.lock_storage() else {
+            return;
+        };
+
+        if let Err(e) = storage.save_hard_state(&state.hard_state()) {
+            tracing::error!("❌ Failed to persist cluster state: {}", e);
+        }
+    }
+
+    /// Syncs the log entries from `index` on to disk. On failure they are
+    /// dropped from the log again, since they were never stored.
+    fn persist_entries(&self, state: &mut RaftState, index: u64) -> anyhow::Result<()> {
+        let Some(mut storage) = self.lock_storage() else {
+            return Ok(());
+        };
+
+        let result = storage.append(&state.entries_from(index, usize::MAX));
+        if result.is_err() {
+            state.truncate_from(index);
+            if let Err(e) = storage.rewrite_log(&state.log) {
+                tracing::error!("❌ Failed to rewrite cluster log: {}", e);
+            }
+        }
+        result
+    }
+
+    fn persist_snapshot(&self, snapshot: &StoredSnapshot, log: &[LogEntry]) -> anyhow::Result<()> {
+        match self.lock_storage() {
+            Some(mut storage) => storage.save_snapshot(snapshot, log),
+            None => Ok(()),
+        }
+    }
+
+    fn notify_peers(&self) {
+        for peer in &self.peers {
+            peer.notify.notify_one();
+        }
+    }
+
+    fn member_url(&self, id: &str) -> Option<String> {
+        if id == self.config.node_id {
+            return Some(self.config.advertise_url.clone());
+        }
+        self.peers
+            .iter()
+            .find(|peer| peer.id == id)
+            .map(|peer| peer.url.clone())
+    }
+
+    fn quorum(&self) -> usize {
+        let members = self.peers.len() + 1;
+        members / 2 + 1
+    }
+
+    fn propose_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.propose_timeout_ms)
+    }
+
+    fn next_election_deadline(&self) -> Instant {
+        Instant::now() + random_election_timeout(&self.config)
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, RaftState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_storage(&self) -> Option<MutexGuard<'_, RaftStorage>> {
+        self.storage
+            .as_ref()
+            .map(|storage| storage.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Checks the settings a node cannot start with
+pub fn validate_config(config: &ClusterConfig) -> anyhow::Result<()> {
+    if config.node_id.is_empty() {
+        return Err(anyhow::anyhow!("cluster.node_id cannot be empty"));
+    }
+    if config.election_timeout_min_ms > config.election_timeout_max_ms {
+        return Err(anyhow::anyhow!(
+            "cluster.election_timeout_min_ms must not exceed election_timeout_max_ms"
+        ));
+    }
+    Ok(())
+}
+
+fn random_election_timeout(config: &ClusterConfig) -> Duration {
+    Duration::from_millis(rand::random_range(
+        config.election_timeout_min_ms..=config.election_timeout_max_ms,
+    ))
+}
+
+/// Applies the configured read consistency to GET requests
+pub async fn read_consistency_layer(
+    State(node): State<Arc<ClusterNode>>,
+    OriginalUri(uri): OriginalUri,
+    req: Request,
+    next: Next,
+) -> Response {
+    if req.method() != Method::GET {
+        return next.run(req).await;
+    }
+
+    match node.check_read() {
+        Ok(()) => next.run(req).await,
+        Err(ReadRejection::Redirect(leader_url)) => {
+            let path_and_query = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+            Redirect::temporary(&format!("{}{}", leader_url, path_and_query)).into_response()
+        }
+        Err(ReadRejection::Unavailable(message)) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DiscoveryQuery, RegisterServiceRequest};
+    use crate::ClusterPeerConfig;
+    use axum::Router;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    struct TestNode {
+        node: Arc<ClusterNode>,
+        registry: Arc<ServiceRegistry>,
+        server: Option<JoinHandle<()>>,
+        listener: Option<TcpListener>,
+    }
+
+    impl TestNode {
+        fn serve(&mut self) {
+            let listener = self.listener.take().expect("node already serving");
+            let app = Router::new().nest_service("/cluster", rpc::router(self.node.clone()));
+            self.server = Some(tokio::spawn(async move {
+                let _ = axum::serve(listener, app).await;
+            }));
+            self.node.start();
+        }
+
+        fn stop(&mut self) {
+            self.node.shutdown();
+            if let Some(server) = self.server.take() {
+                server.abort();
+            }
+        }
+    }
+
+    async fn create_cluster(
+        size: usize,
+        snapshot_threshold: usize,
+        data_dir: Option<&Path>,
+    ) -> Vec<TestNode> {
+        let mut listeners = Vec::new();
+        for _ in 0..size {
+            listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+        }
+
+        let peers: Vec<ClusterPeerConfig> = listeners
+            .iter()
+            .enumerate()
+            .map(|(i, listener)| ClusterPeerConfig {
+                id: format!("node-{}", i + 1),
+                url: format!("http://{}", listener.local_addr().unwrap()),
+            })
+            .collect();
+
+        listeners
+            .into_iter()
+            .enumerate()
+            .map(|(i, listener)| {
+                let config = ClusterConfig {
+                    enabled: true,
+                    node_id: peers[i].id.clone(),
+                    advertise_url: peers[i].url.clone(),
+                    peers: peers.clone(),
+                    heartbeat_interval_ms: 30,
+                    election_timeout_min_ms: 150,
+                    election_timeout_max_ms: 300,
+                    rpc_timeout_ms: 200,
+                    propose_timeout_ms: 2000,
+                    snapshot_threshold,
+                    ..Default::default()
+                };
+                let node_dir = data_dir.map(|dir| dir.join(&config.node_id));
+                let registry = Arc::new(ServiceRegistry::new());
+                let node =
+                    ClusterNode::new(config, registry.clone(), node_dir.as_deref(), None).unwrap();
+                TestNode {
+                    node,
+                    registry,
+                    server: None,
+                    listener: Some(listener),
+                }
+            })
+            .collect()
+    }
+
+    async fn start_cluster(size: usize) -> Vec<TestNode> {
+        let mut nodes = create_cluster(size, 1000, None).await;
+        for node in &mut nodes {
+            node.serve();
+        }
+        nodes
+    }
+
+    async fn wait_for_leader(nodes: &[TestNode], exclude: Option<usize>) -> usize {
+        for _ in 0..200 {
+            let leaders: Vec<usize> = nodes
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| Some(*i) != exclude)
+                .filter(|(_, node)| node.node.is_leader())
+                .map(|(i, _)| i)
+                .collect();
+            if leaders.len() == 1 {
+                return leaders[0];
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        panic!("no leader elected");
+    }
+
+    async fn wait_for_instances(registry: &ServiceRegistry, count: usize) -> bool {
+        for _ in 0..200 {
+            if registry.get_all_instances().unwrap().len() == count {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        false
+    }
+
+    fn register_request(port: u16) -> RegisterServiceRequest {
+        RegisterServiceRequest {
+            service_name: "users".to_string(),
+            host: "localhost".to_string(),
+            port,
+            secure: None,
+            metadata: None,
+            tags: None,
+            health_check: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_node_cluster_accepts_writes() {
+        let mut nodes = start_cluster(1).await;
+        wait_for_leader(&nodes, None).await;
+
+        let instance = nodes[0]
+            .registry
+            .register_instance(register_request(3000))
+            .await
+            .unwrap();
+        assert!(nodes[0]
+            .registry
+            .update_heartbeat(&instance.id)
+            .await
+            .unwrap());
+        assert_eq!(nodes[0].node.status().last_applied, 3);
+
+        nodes[0].stop();
+    }
+
+    #[tokio::test]
+    async fn test_follower_forwards_writes_to_leader() {
+        let mut nodes = start_cluster(3).await;
+        let leader = wait_for_leader(&nodes, None).await;
+        let follower = (leader + 1) % nodes.len();
+
+        let instance = nodes[follower]
+            .registry
+            .register_instance(register_request(3000))
+            .await
+            .unwrap();
+
+        // The follower only answers once it applied the write itself
+        let query = DiscoveryQuery {
+            healthy_only: Some(true),
+            tags: None,
+            limit: None,
+            strategy: None,
+        };
+        let found = nodes[follower]
+            .registry
+            .get_service_instances("users", &query)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, instance.id);
+
+        for node in &nodes {
+            assert!(wait_for_instances(&node.registry, 1).await);
+        }
+
+        let status = nodes[follower].node.status();
+        assert_eq!(
+            status.leader_id.as_deref(),
+            Some(nodes[leader].node.node_id())
+        );
+        assert_eq!(status.members.len(), 3);
+
+        for node in &mut nodes {
+            node.stop();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_leader_elected_after_failure() {
+        let mut nodes = start_cluster(3).await;
+        let leader = wait_for_leader(&nodes, None).await;
+
+        nodes[0]
+            .registry
+            .register_instance(register_request(3000))
+            .await
+            .unwrap();
+
+        nodes[leader].stop();
+        let new_leader = wait_for_leader(&nodes, Some(leader)).await;
+        assert_ne!(new_leader, leader);
+
+        let survivor = (0..nodes.len())
+            .find(|i| *i != leader && *i != new_leader)
+            .unwrap();
+        nodes[survivor]
+            .registry
+            .register_instance(register_request(3001))
+            .await
+            .unwrap();
+
+        assert!(wait_for_instances(&nodes[new_leader].registry, 2).await);
+        assert!(wait_for_instances(&nodes[survivor].registry, 2).await);
+
+        for node in &mut nodes {
+            node.stop();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lagging_node_catches_up_from_snapshot() {
+        let mut nodes = create_cluster(3, 4, None).await;
+        nodes[0].serve();
+        nodes[1].serve();
+
+        let leader = wait_for_leader(&nodes, Some(2)).await;
+        for port in 3000..3010 {
+            nodes[leader]
+                .registry
+                .register_instance(register_request(port))
+                .await
+                .unwrap();
+        }
+        assert!(nodes[leader].node.status().snapshot_index > 0);
+
+        nodes[2].serve();
+        assert!(wait_for_instances(&nodes[2].registry, 10).await);
+
+        for node in &mut nodes {
+            node.stop();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_committed_entries_survive_restart() {
+        let data_dir = tempfile::TempDir::new().unwrap();
+        let mut nodes = create_cluster(3, 4, Some(data_dir.path())).await;
+        for node in &mut nodes {
+            node.serve();
+        }
+
+        let leader = wait_for_leader(&nodes, None).await;
+        for port in 3000..3006 {
+            nodes[leader]
+                .registry
+                .register_instance(register_request(port))
+                .await
+                .unwrap();
+        }
+        assert!(nodes[leader].node.status().snapshot_index > 0);
+        for node in &mut nodes {
+            node.stop();
+        }
+
+        // Every node comes back on a new address, from its data directory only
+        let mut nodes = create_cluster(3, 4, Some(data_dir.path())).await;
+        let status = nodes[leader].node.status();
+        assert!(status.snapshot_index > 0);
+        assert!(status.last_log_index >= 7);
+
+        for node in &mut nodes {
+            node.serve();
+        }
+        wait_for_leader(&nodes, None).await;
+        for node in &nodes {
+            assert!(wait_for_instances(&node.registry, 6).await);
+        }
+
+        for node in &mut nodes {
+            node.stop();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bounded_reads_rejected_without_leader() {
+        let nodes = create_cluster(3, 1000, None).await;
+        let config = ClusterConfig {
+            read_consistency: ReadConsistency::Bounded,
+            ..nodes[0].node.config.clone()
+        };
+        let node = ClusterNode::new(config, Arc::new(ServiceRegistry::new()), None, None).unwrap();
+
+        assert!(matches!(
+            node.check_read(),
+            Err(ReadRejection::Unavailable(_))
+        ));
+        assert!(nodes[0].node.check_read().is_ok());
+    }
+}
@@ -0,0 +1,290 @@
+//! Raft log and node state
+//!
+//! This module only holds the replicated log and the per-node consensus
+//! state. Timers, RPCs and applying committed entries to the registry live
+//! in the parent module.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Instant;
+use tokio::sync::oneshot;
+
+use crate::models::RegistryMutation;
+use crate::persistence::RegistrySnapshot;
+
+/// Role of a node in the current term
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+/// Command carried by a log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+    /// Appended by a new leader to commit entries from previous terms
+    Noop,
+    Mutation {
+        mutation: RegistryMutation,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub index: u64,
+    pub term: u64,
+    pub command: Command,
+}
+
+/// Term and vote, persisted so a restarted node never votes twice in a term
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HardState {
+    pub current_term: u64,
+    pub voted_for: Option<String>,
+}
+
+/// A client write waiting for its log entry to be applied
+pub struct PendingProposal {
+    pub term: u64,
+    pub sender: oneshot::Sender<anyhow::Result<bool>>,
+}
+
+pub struct RaftState {
+    pub role: Role,
+    pub current_term: u64,
+    pub voted_for: Option<String>,
+    pub leader_id: Option<String>,
+
+    /// Entries after `snapshot_index`, in order
+    pub log: Vec<LogEntry>,
+    pub snapshot_index: u64,
+    pub snapshot_term: u64,
+    pub snapshot: Option<RegistrySnapshot>,
+
+    pub commit_index: u64,
+    pub last_applied: u64,
+
+    pub election_deadline: Instant,
+    pub last_leader_contact: Option<Instant>,
+
+    /// Leader only: next log index to send to each peer
+    pub next_index: HashMap<String, u64>,
+    /// Leader only: highest log index known to be replicated on each peer
+    pub match_index: HashMap<String, u64>,
+    /// Leader only: last successful exchange with each peer
+    pub peer_contact: HashMap<String, Instant>,
+
+    pub pending: HashMap<u64, PendingProposal>,
+}
+
+impl RaftState {
+    pub fn new(hard_state: HardState, election_deadline: Instant) -> Self {
+        Self {
+            role: Role::Follower,
+            current_term: hard_state.current_term,
+            voted_for: hard_state.voted_for,
+            leader_id: None,
+            log: Vec::new(),
+            snapshot_index: 0,
+            snapshot_term: 0,
+            snapshot: None,
+            commit_index: 0,
+            last_applied: 0,
+            election_deadline,
+            last_leader_contact: None,
+            next_index: HashMap::new(),
+            match_index: HashMap::new(),
+            peer_contact: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn hard_state(&self) -> HardState {
+        HardState {
+            current_term: self.current_term,
+            voted_for: self.voted_for.clone(),
+        }
+    }
+
+    pub fn last_log_index(&self) -> u64 {
+        self.log
+            .last()
+            .map(|entry| entry.index)
+            .unwrap_or(self.snapshot_index)
+    }
+
+    pub fn last_log_term(&self) -> u64 {
+        self.log
+            .last()
+            .map(|entry| entry.term)
+            .unwrap_or(self.snapshot_term)
+    }
+
+    /// Term of the entry at `index`, if it is still known
+    pub fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot_index {
+            return Some(self.snapshot_term);
+        }
+        self.entry(index).map(|entry| entry.term)
+    }
+
+    pub fn entry(&self, index: u64) -> Option<&LogEntry> {
+        if index <= self.snapshot_index {
+            return None;
+        }
+        self.log.get((index - self.snapshot_index - 1) as usize)
+    }
+
+    /// Entries starting at `index`, at most `limit` of them
+    pub fn entries_from(&self, index: u64, limit: usize) -> Vec<LogEntry> {
+        let start = index.saturating_sub(self.snapshot_index + 1) as usize;
+        self.log.iter().skip(start).take(limit).cloned().collect()
+    }
+
+    /// Appends a new entry in the current term and returns its index
+    pub fn append(&mut self, command: Command) -> u64 {
+        let index = self.last_log_index() + 1;
+        self.log.push(LogEntry {
+            index,
+            term: self.current_term,
+            command,
+        });
+        index
+    }
+
+    /// Drops every entry from `index` onwards, failing their waiters
+    pub fn truncate_from(&mut self, index: u64) {
+        let keep = index.saturating_sub(self.snapshot_index + 1) as usize;
+        self.log.truncate(keep);
+
+        let dropped: Vec<u64> = self
+            .pending
+            .keys()
+            .copied()
+            .filter(|pending_index| *pending_index >= index)
+            .collect();
+        for pending_index in dropped {
+            if let Some(pending) = self.pending.remove(&pending_index) {
+                let _ = pending
+                    .sender
+                    .send(Err(anyhow::anyhow!("Entry overwritten by a new leader")));
+            }
+        }
+    }
+
+    /// First index of the term the entry at `index` belongs to.
+    ///
+    /// Lets a leader skip a whole conflicting term in one round trip instead
+    /// of backing up one entry at a time.
+    pub fn first_index_of_term_at(&self, index: u64) -> u64 {
+        let Some(term) = self.term_at(index) else {
+            return self.last_log_index() + 1;
+        };
+
+        let mut first = index;
+        while first > self.snapshot_index + 1 && self.term_at(first - 1) == Some(term) {
+            first -= 1;
+        }
+        first
+    }
+
+    /// Discards entries up to and including `index`, now covered by `snapshot`
+    pub fn compact(&mut self, index: u64, snapshot: RegistrySnapshot) {
+        let Some(term) = self.term_at(index) else {
+            return;
+        };
+
+        let drop = index.saturating_sub(self.snapshot_index) as usize;
+        self.log.drain(..drop.min(self.log.len()));
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+        self.snapshot = Some(snapshot);
+    }
+
+    /// Candidate log is at least as up to date as ours (Raft §5.4.1)
+    pub fn is_log_up_to_date(&self, last_log_index: u64, last_log_term: u64) -> bool {
+        let our_term = self.last_log_term();
+        last_log_term > our_term
+            || (last_log_term == our_term && last_log_index >= self.last_log_index())
+    }
+
+    /// Fails every pending proposal, used when leadership is lost
+    pub fn fail_pending(&mut self, reason: &str) {
+        for (_, pending) in self.pending.drain() {
+            let _ = pending.sender.send(Err(anyhow::anyhow!("{}", reason)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn state_with_terms(terms: &[u64]) -> RaftState {
+        let mut state = RaftState::new(HardState::default(), Instant::now());
+        for term in terms {
+            state.current_term = *term;
+            state.append(Command::Noop);
+        }
+        state
+    }
+
+    fn empty_snapshot(sequence: u64) -> RegistrySnapshot {
+        RegistrySnapshot {
+            sequence,
+            event_sequence: 0,
+            taken_at: Utc::now(),
+            services: vec![],
+            instances: vec![],
+        }
+    }
+
+    #[test]
+    fn test_log_indexing() {
+        let state = state_with_terms(&[1, 1, 2]);
+        assert_eq!(state.last_log_index(), 3);
+        assert_eq!(state.last_log_term(), 2);
+        assert_eq!(state.term_at(0), Some(0));
+        assert_eq!(state.term_at(2), Some(1));
+        assert_eq!(state.term_at(4), None);
+        assert_eq!(state.entries_from(2, 10).len(), 2);
+        assert_eq!(state.entries_from(2, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_compaction_keeps_indexing() {
+        let mut state = state_with_terms(&[1, 1, 2, 3]);
+        state.compact(2, empty_snapshot(2));
+
+        assert_eq!(state.log.len(), 2);
+        assert_eq!(state.snapshot_term, 1);
+        assert_eq!(state.term_at(2), Some(1));
+        assert_eq!(state.term_at(1), None);
+        assert_eq!(state.entry(3).unwrap().term, 2);
+        assert_eq!(state.entries_from(1, 10).len(), 2);
+        assert_eq!(state.last_log_index(), 4);
+    }
+
+    #[test]
+    fn test_truncate_and_conflict_lookup() {
+        let mut state = state_with_terms(&[1, 2, 2, 2, 3]);
+        assert_eq!(state.first_index_of_term_at(4), 2);
+
+        state.truncate_from(3);
+        assert_eq!(state.last_log_index(), 2);
+        assert_eq!(state.last_log_term(), 2);
+    }
+
+    #[test]
+    fn test_log_up_to_date() {
+        let state = state_with_terms(&[1, 2]);
+        assert!(state.is_log_up_to_date(2, 2));
+        assert!(state.is_log_up_to_date(1, 3));
+        assert!(!state.is_log_up_to_date(5, 1));
+        assert!(!state.is_log_up_to_date(1, 2));
+    }
+}
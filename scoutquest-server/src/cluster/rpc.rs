@@ -0,0 +1,231 @@
+//! HTTP transport between cluster nodes
+//!
+//! Raft messages are exchanged as JSON over the regular server listener,
+//! under the `/cluster` prefix.
+
+use axum::{extract::State, http::StatusCode, response::Json, routing::post, Router};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::raft::LogEntry;
+use super::ClusterNode;
+use crate::models::RegistryMutation;
+use crate::persistence::RegistrySnapshot;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoteRequest {
+    pub term: u64,
+    pub candidate_id: String,
+    pub last_log_index: u64,
+    pub last_log_term: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoteResponse {
+    pub term: u64,
+    pub vote_granted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppendEntriesRequest {
+    pub term: u64,
+    pub leader_id: String,
+    pub prev_log_index: u64,
+    pub prev_log_term: u64,
+    pub entries: Vec<LogEntry>,
+    pub leader_commit: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppendEntriesResponse {
+    pub term: u64,
+    pub success: bool,
+    /// Highest index known to match the leader's log
+    pub match_index: u64,
+    /// Where the leader should resume when `success` is false
+    pub conflict_index: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstallSnapshotRequest {
+    pub term: u64,
+    pub leader_id: String,
+    pub last_included_index: u64,
+    pub last_included_term: u64,
+    pub snapshot: RegistrySnapshot,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstallSnapshotResponse {
+    pub term: u64,
+}
+
+/// Write forwarded by a follower to the leader
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProposeRequest {
+    pub mutation: RegistryMutation,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProposeResponse {
+    pub applied: bool,
+    /// Log index of the write, so the follower can wait until it applied it
+    pub index: u64,
+}
+
+/// Routes served to other cluster nodes
+pub fn router(node: Arc<ClusterNode>) -> Router {
+    Router::new()
+        .route("/raft/vote", post(vote))
+        .route("/raft/append", post(append_entries))
+        .route("/raft/snapshot", post(install_snapshot))
+        .route("/propose", post(propose))
+        .with_state(node)
+}
+
+async fn vote(
+    State(node): State<Arc<ClusterNode>>,
+    Json(request): Json<VoteRequest>,
+) -> Json<VoteResponse> {
+    Json(node.handle_vote(request))
+}
+
+async fn append_entries(
+    State(node): State<Arc<ClusterNode>>,
+    Json(request): Json<AppendEntriesRequest>,
+) -> Json<AppendEntriesResponse> {
+    Json(node.handle_append_entries(request))
+}
+
+async fn install_snapshot(
+    State(node): State<Arc<ClusterNode>>,
+    Json(request): Json<InstallSnapshotRequest>,
+) -> Json<InstallSnapshotResponse> {
+    Json(node.handle_install_snapshot(request))
+}
+
+async fn propose(
+    State(node): State<Arc<ClusterNode>>,
+    Json(request): Json<ProposeRequest>,
+) -> Result<Json<ProposeResponse>, (StatusCode, String)> {
+    if !node.is_leader() {
+        return Err((
+            StatusCode::MISDIRECTED_REQUEST,
+            format!("Node {} is not the cluster leader", node.node_id()),
+        ));
+    }
+
+    match node.propose_as_leader(request.mutation).await {
+        Ok((applied, index)) => Ok(Json(ProposeResponse { applied, index })),
+        Err(e) => Err((StatusCode::SERVICE_UNAVAILABLE, e.to_string())),
+    }
+}
+
+/// A request the peer never processed, so it can be retried on another node
+#[derive(Debug)]
+pub struct NotDelivered(pub String);
+
+impl std::fmt::Display for NotDelivered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NotDelivered {}
+
+/// HTTP client used to reach peer nodes
+#[derive(Clone)]
+pub struct PeerClient {
+    http_client: reqwest::Client,
+}
+
+impl PeerClient {
+    /// `api_key` is sent as bearer token when the cluster requires authentication
+    pub fn new(timeout: Duration, api_key: Option<String>) -> anyhow::Result<Self> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Some(api_key) = api_key {
+            let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", api_key))?;
+            value.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+
+        let http_client = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(timeout)
+            .default_headers(headers)
+            .build()?;
+
+        Ok(Self { http_client })
+    }
+
+    pub async fn call<Req, Resp>(
+        &self,
+        peer_url: &str,
+        path: &str,
+        request: &Req,
+    ) -> anyhow::Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        self.send(peer_url, path, request, None).await
+    }
+
+    /// Same as [`PeerClient::call`], for requests that may legitimately take
+    /// longer than a Raft message, such as forwarded writes
+    pub async fn call_with_timeout<Req, Resp>(
+        &self,
+        peer_url: &str,
+        path: &str,
+        request: &Req,
+        timeout: Duration,
+    ) -> anyhow::Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        self.send(peer_url, path, request, Some(timeout)).await
+    }
+
+    async fn send<Req, Resp>(
+        &self,
+        peer_url: &str,
+        path: &str,
+        request: &Req,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let url = format!("{}/cluster{}", peer_url.trim_end_matches('/'), path);
+        let mut builder = self.http_client.post(&url).json(request);
+        if let Some(timeout) = timeout {
+            builder = builder.timeout(timeout);
+        }
+        let response = match builder.send().await {
+            Ok(response) => response,
+            Err(e) if e.is_connect() => {
+                return Err(NotDelivered(format!("{} unreachable: {}", url, e)).into())
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = format!(
+                "{} returned {}: {}",
+                url,
+                status,
+                response.text().await.unwrap_or_default()
+            );
+            if status == StatusCode::MISDIRECTED_REQUEST {
+                return Err(NotDelivered(message).into());
+            }
+            return Err(anyhow::anyhow!(message));
+        }
+
+        Ok(response.json().await?)
+    }
+}
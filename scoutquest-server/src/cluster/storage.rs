@@ -0,0 +1,257 @@
+//! On-disk Raft state
+//!
+//! A node keeps its term, vote, log and latest snapshot in the data
+//! directory, so a restarted node still holds every entry it acknowledged:
+//!
+//! ```text
+//! data/
+//! ├── raft-state.json     # Current term and vote
+//! ├── raft-snapshot.json  # Registry state up to the snapshot index
+//! └── raft-log.jsonl      # Log entries after the snapshot, one per line
+//! ```
+//!
+//! The log file is append-only: an entry whose index is already in the file
+//! replaces that entry and everything after it, which is how entries
+//! overwritten by a new leader are dropped. The file is only rewritten when
+//! the log is compacted into a snapshot.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use super::raft::{HardState, LogEntry};
+use crate::persistence::{read_segment, RegistrySnapshot};
+
+const HARD_STATE_FILE: &str = "raft-state.json";
+const SNAPSHOT_FILE: &str = "raft-snapshot.json";
+const LOG_FILE: &str = "raft-log.jsonl";
+
+/// Snapshot of the registry and the log position it covers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSnapshot {
+    pub index: u64,
+    pub term: u64,
+    pub snapshot: RegistrySnapshot,
+}
+
+/// State read back from disk when a node starts
+#[derive(Debug, Default)]
+pub struct RecoveredRaft {
+    pub hard_state: HardState,
+    pub snapshot: Option<StoredSnapshot>,
+    /// Entries after the snapshot, in order
+    pub log: Vec<LogEntry>,
+}
+
+pub struct RaftStorage {
+    data_dir: PathBuf,
+    log: File,
+}
+
+impl RaftStorage {
+    /// Opens the data directory and reads back the persisted state.
+    ///
+    /// The log file is rewritten with the recovered entries, so a torn
+    /// write at its end can never be followed by new entries.
+    pub fn open(data_dir: &Path) -> anyhow::Result<(Self, RecoveredRaft)> {
+        fs::create_dir_all(data_dir).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to create data directory {}: {}",
+                data_dir.display(),
+                e
+            )
+        })?;
+
+        let hard_state = read_json(&data_dir.join(HARD_STATE_FILE))?.unwrap_or_default();
+        let snapshot: Option<StoredSnapshot> = read_json(&data_dir.join(SNAPSHOT_FILE))?;
+        let snapshot_index = snapshot.as_ref().map(|s| s.index).unwrap_or(0);
+
+        let log_path = data_dir.join(LOG_FILE);
+        let mut log: Vec<LogEntry> = Vec::new();
+        if log_path.exists() {
+            for entry in read_segment::<LogEntry>(&log_path)? {
+                if entry.index <= snapshot_index {
+                    continue;
+                }
+                let next_index = snapshot_index + log.len() as u64 + 1;
+                if entry.index > next_index {
+                    return Err(anyhow::anyhow!(
+                        "Raft log {} is missing entries before index {}",
+                        log_path.display(),
+                        entry.index
+                    ));
+                }
+                log.truncate((entry.index - snapshot_index - 1) as usize);
+                log.push(entry);
+            }
+        }
+
+        let storage = Self {
+            data_dir: data_dir.to_path_buf(),
+            log: write_log(data_dir, &log)?,
+        };
+
+        Ok((
+            storage,
+            RecoveredRaft {
+                hard_state,
+                snapshot,
+                log,
+            },
+        ))
+    }
+
+    pub fn save_hard_state(&self, hard_state: &HardState) -> anyhow::Result<()> {
+        write_atomically(&self.data_dir.join(HARD_STATE_FILE), hard_state)
+    }
+
+    /// Appends entries and syncs them to disk. Entries with an index already
+    /// in the log replace it from that index on.
+    pub fn append(&mut self, entries: &[LogEntry]) -> anyhow::Result<()> {
+        let mut data = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut data, entry)?;
+            data.push(b'\n');
+        }
+
+        let length = self.log.metadata()?.len();
+        let written = self.log.write_all(&data).and_then(|_| self.log.sync_data());
+
+        if let Err(e) = written {
+            // Drop the partial write so later entries never follow it
+            self.log.set_len(length)?;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Writes a snapshot, then replaces the log with the entries after it
+    pub fn save_snapshot(
+        &mut self,
+        snapshot: &StoredSnapshot,
+        log: &[LogEntry],
+    ) -> anyhow::Result<()> {
+        write_atomically(&self.data_dir.join(SNAPSHOT_FILE), snapshot)?;
+        self.rewrite_log(log)
+    }
+
+    /// Replaces the log file with `log`
+    pub fn rewrite_log(&mut self, log: &[LogEntry]) -> anyhow::Result<()> {
+        self.log = write_log(&self.data_dir, log)?;
+        Ok(())
+    }
+}
+
+/// Atomically writes a new log file and returns it opened for appending
+fn write_log(data_dir: &Path, log: &[LogEntry]) -> anyhow::Result<File> {
+    let path = data_dir.join(LOG_FILE);
+    let tmp_path = data_dir.join(format!("{}.tmp", LOG_FILE));
+
+    {
+        let mut file = File::create(&tmp_path)?;
+        for entry in log {
+            serde_json::to_writer(&mut file, entry)?;
+            file.write_all(b"\n")?;
+        }
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, &path)?;
+
+    Ok(OpenOptions::new().append(true).open(&path)?)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = File::open(path)?;
+    let value = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| anyhow::anyhow!("Corrupted cluster state {}: {}", path.display(), e))?;
+    Ok(Some(value))
+}
+
+fn write_atomically<T: Serialize>(path: &Path, value: &T) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+
+    {
+        let mut file = File::create(&tmp_path)?;
+        serde_json::to_writer(&mut file, value)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::raft::Command;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn entry(index: u64, term: u64) -> LogEntry {
+        LogEntry {
+            index,
+            term,
+            command: Command::Noop,
+        }
+    }
+
+    fn terms(log: &[LogEntry]) -> Vec<(u64, u64)> {
+        log.iter().map(|entry| (entry.index, entry.term)).collect()
+    }
+
+    #[test]
+    fn test_overwritten_entries_dropped_on_reload() {
+        let dir = TempDir::new().unwrap();
+        let (mut storage, recovered) = RaftStorage::open(dir.path()).unwrap();
+        assert!(recovered.log.is_empty());
+
+        storage
+            .append(&[entry(1, 1), entry(2, 1), entry(3, 1)])
+            .unwrap();
+        storage.append(&[entry(2, 2)]).unwrap();
+        storage
+            .save_hard_state(&HardState {
+                current_term: 2,
+                voted_for: Some("node-2".to_string()),
+            })
+            .unwrap();
+        drop(storage);
+
+        let (_, recovered) = RaftStorage::open(dir.path()).unwrap();
+        assert_eq!(terms(&recovered.log), vec![(1, 1), (2, 2)]);
+        assert_eq!(recovered.hard_state.current_term, 2);
+        assert_eq!(recovered.hard_state.voted_for.as_deref(), Some("node-2"));
+    }
+
+    #[test]
+    fn test_snapshot_replaces_compacted_entries() {
+        let dir = TempDir::new().unwrap();
+        let (mut storage, _) = RaftStorage::open(dir.path()).unwrap();
+        storage
+            .append(&[entry(1, 1), entry(2, 1), entry(3, 2)])
+            .unwrap();
+
+        let snapshot = StoredSnapshot {
+            index: 2,
+            term: 1,
+            snapshot: RegistrySnapshot {
+                sequence: 2,
+                event_sequence: 0,
+                taken_at: Utc::now(),
+                services: vec![],
+                instances: vec![],
+            },
+        };
+        storage.save_snapshot(&snapshot, &[entry(3, 2)]).unwrap();
+        storage.append(&[entry(4, 2)]).unwrap();
+        drop(storage);
+
+        let (_, recovered) = RaftStorage::open(dir.path()).unwrap();
+        assert_eq!(recovered.snapshot.unwrap().index, 2);
+        assert_eq!(terms(&recovered.log), vec![(3, 2), (4, 2)]);
+    }
+}
@@ -0,0 +1,195 @@
+//! `check-config` and `print-config` subcommands
+
+use clap::{Subcommand, ValueEnum};
+use std::net::IpAddr;
+
+use crate::config_reload::live_config_errors;
+use crate::middleware::auth::ApiKeyAuth;
+use crate::middleware::ip_restriction::IpRestrictionMiddleware;
+use crate::tls::validate_tls_config;
+use crate::AppConfig;
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Validate every configuration section and report all errors
+    CheckConfig,
+    /// Print the merged configuration (file, environment and command line)
+    /// with secrets redacted
+    PrintConfig {
+        #[arg(long, value_enum, default_value_t = OutputFormat::Toml)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Toml,
+    Json,
+}
+
+/// Errors of every section of `config`, as `(section, error)`
+pub fn check_config(config: &AppConfig) -> Vec<(&'static str, String)> {
+    let mut errors = Vec::new();
+    let mut check = |section: &'static str, result: anyhow::Result<()>| {
+        if let Err(e) = result {
+            errors.push((section, e.to_string()));
+        }
+    };
+
+    check(
+        "server",
+        config
+            .server
+            .host
+            .parse::<IpAddr>()
+            .map(|_| ())
+            .map_err(|e| anyhow::anyhow!("Invalid host '{}': {}", config.server.host, e)),
+    );
+    for (section, e) in live_config_errors(config) {
+        check(section, Err(e));
+    }
+
+    let auth = ApiKeyAuth::new(&config.security);
+    let auth_enabled = auth.as_ref().is_ok_and(|auth| auth.is_enabled());
+    check(
+        "security",
+        auth.as_ref()
+            .map(|_| ())
+            .map_err(|e| anyhow::anyhow!("{}", e)),
+    );
+
+    check("storage", crate::store::validate_config(&config.storage));
+
+    if let Some(network) = &config.network {
+        check("network", IpRestrictionMiddleware::new(network).map(|_| ()));
+    }
+
+    if let Some(tls) = config.tls.as_ref().filter(|tls| tls.enabled) {
+        let mut tls = tls.clone();
+        // The internal CA is the default client CA, and may not exist yet
+        if config.pki.enabled && tls.client_ca_path.is_none() {
+            tls.verify_peer = false;
+        }
+        check("tls", validate_tls_config(&tls).map_err(Into::into));
+    }
+
+    if let Some(cluster) = config.cluster.as_ref().filter(|cluster| cluster.enabled) {
+        check("cluster", crate::cluster::validate_config(cluster));
+        let api_key = match (&cluster.api_key_id, &auth) {
+            (Some(id), Ok(auth)) if auth.secret(id).is_none() => Err(anyhow::anyhow!(
+                "cluster.api_key_id {} is not a configured API key",
+                id
+            )),
+            (None, _) if auth_enabled => Err(anyhow::anyhow!(
+                "cluster.api_key_id is required when authentication is enabled"
+            )),
+            _ => Ok(()),
+        };
+        check("cluster", api_key);
+    }
+
+    if config.pki.enabled {
+        check(
+            "pki",
+            crate::tls::ca::validate_config(&config.pki, &config.security.client_certs),
+        );
+    }
+
+    errors
+}
+
+/// Copy of `config` with the API keys and webhook secrets replaced
+pub fn redacted(config: &AppConfig) -> AppConfig {
+    let mut config = config.clone();
+    let redact = |secret: &mut Option<String>| {
+        if secret.is_some() {
+            *secret = Some(REDACTED.to_string());
+        }
+    };
+
+    redact(&mut config.security.api_key);
+    for api_key in &mut config.security.api_keys {
+        redact(&mut api_key.key);
+    }
+    for webhook in &mut config.webhooks {
+        redact(&mut webhook.secret);
+    }
+    config
+}
+
+pub fn print_config(config: &AppConfig, format: OutputFormat) -> anyhow::Result<String> {
+    let config = redacted(config);
+    Ok(match format {
+        OutputFormat::Toml => toml::to_string_pretty(&config)?,
+        OutputFormat::Json => serde_json::to_string_pretty(&config)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::ApiKeyConfig;
+    use crate::{NetworkConfig, ScoutQuestTlsConfig, WebhookConfig};
+
+    #[test]
+    fn test_check_config_reports_every_error() {
+        assert!(check_config(&AppConfig::default()).is_empty());
+
+        let mut config = AppConfig::default();
+        config.server.cors_origins = vec!["https://ok.example.com\n".to_string()];
+        config.logging.level = "verbose".to_string();
+        config.network = Some(NetworkConfig {
+            enabled: true,
+            allowed_cidrs: vec!["10.0.0.0/33".to_string()],
+            ..Default::default()
+        });
+        config.tls = Some(ScoutQuestTlsConfig {
+            enabled: true,
+            cert_path: Some("/nonexistent/server.crt".to_string()),
+            key_path: Some("/nonexistent/server.key".to_string()),
+            auto_generate: false,
+            ..Default::default()
+        });
+        config.storage.backend = crate::store::StorageBackend::Sqlite;
+        config.storage.enabled = true;
+
+        let sections: Vec<_> = check_config(&config)
+            .into_iter()
+            .map(|(section, _)| section)
+            .collect();
+        assert_eq!(
+            sections,
+            vec!["server", "logging", "storage", "network", "tls"]
+        );
+    }
+
+    #[test]
+    fn test_print_config_redacts_secrets() {
+        let mut config = AppConfig::default();
+        config.security.api_keys = vec![ApiKeyConfig {
+            id: "deployer".to_string(),
+            key: Some("sk-live-123".to_string()),
+            ..Default::default()
+        }];
+        config.webhooks = vec![WebhookConfig {
+            url: "https://hooks.example.com".to_string(),
+            secret: Some("whsec-456".to_string()),
+            ..Default::default()
+        }];
+
+        for format in [OutputFormat::Toml, OutputFormat::Json] {
+            let output = print_config(&config, format).unwrap();
+            assert!(output.contains("deployer"));
+            assert!(output.contains(REDACTED));
+            assert!(!output.contains("sk-live-123"));
+            assert!(!output.contains("whsec-456"));
+        }
+
+        // The TOML output is a valid configuration file
+        let output = print_config(&config, OutputFormat::Toml).unwrap();
+        let parsed: AppConfig = toml::from_str(&output).unwrap();
+        assert_eq!(parsed.webhooks[0].url, "https://hooks.example.com");
+    }
+}
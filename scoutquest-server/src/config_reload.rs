@@ -0,0 +1,380 @@
+//! Live configuration reload
+//!
+//! The configuration file is re-read on SIGHUP and when it changes on disk.
+//! A new configuration is fully validated before anything is applied, so
+//! an invalid file leaves the running configuration untouched. The network
+//! restrictions, CORS origins, health check interval and timeout, log level
+//! and webhooks are applied live; other changed sections are logged as
+//! requiring a restart.
+
+use axum::http::HeaderValue;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::health_checker::HealthChecker;
+use crate::middleware::ip_restriction::IpRestrictionMiddleware;
+use crate::webhooks::WebhookDispatcher;
+use crate::{load_config, log_network_config, AppConfig, Args};
+
+/// Interval between two checks of the configuration file for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Handle replacing the log filter installed by `setup_logging`
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Value replaced as a whole on reload. Readers keep the version they
+/// loaded until they load again.
+pub struct Live<T>(RwLock<Arc<T>>);
+
+impl<T> Live<T> {
+    pub fn new(value: T) -> Self {
+        Self(RwLock::new(Arc::new(value)))
+    }
+
+    pub fn load(&self) -> Arc<T> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn store(&self, value: T) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(value);
+    }
+}
+
+/// Log filter of `level`, on top of the `RUST_LOG` directives
+pub fn log_filter(level: &str) -> anyhow::Result<EnvFilter> {
+    let level = level
+        .parse::<tracing::Level>()
+        .map_err(|_| anyhow::anyhow!("Invalid log level: {}", level))?;
+    Ok(EnvFilter::from_default_env().add_directive(level.into()))
+}
+
+/// CORS layer following the `server.enable_cors` and `server.cors_origins`
+/// of the current configuration
+pub fn cors_layer(config: Arc<Live<AppConfig>>) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            let config = config.load();
+            config.server.enable_cors
+                && config
+                    .server
+                    .cors_origins
+                    .iter()
+                    .any(|allowed| allowed == "*" || allowed.as_bytes() == origin.as_bytes())
+        }))
+        .allow_methods(Any)
+        .allow_headers(Any)
+}
+
+/// Errors of the settings applied live, as `(section, error)`
+pub fn live_config_errors(config: &AppConfig) -> Vec<(&'static str, anyhow::Error)> {
+    let mut errors = Vec::new();
+    for origin in &config.server.cors_origins {
+        if let Err(e) = origin.parse::<HeaderValue>() {
+            errors.push((
+                "server",
+                anyhow::anyhow!("Invalid CORS origin '{}': {}", origin, e),
+            ));
+        }
+    }
+
+    if config.health_check.interval_seconds == 0 {
+        errors.push((
+            "health_check",
+            anyhow::anyhow!("health_check.interval_seconds must be greater than 0"),
+        ));
+    }
+
+    for webhook in &config.webhooks {
+        if let Err(e) = reqwest::Url::parse(&webhook.url) {
+            errors.push((
+                "webhooks",
+                anyhow::anyhow!("Invalid webhook URL '{}': {}", webhook.url, e),
+            ));
+        }
+    }
+
+    if let Err(e) = log_filter(&config.logging.level) {
+        errors.push(("logging", e));
+    }
+    errors
+}
+
+/// Checks the settings applied live, so a reload never applies part of an
+/// invalid configuration
+pub fn validate_live_config(config: &AppConfig) -> anyhow::Result<()> {
+    match live_config_errors(config).into_iter().next() {
+        Some((_, e)) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Settings whose changes only apply after a restart
+const RESTART_REQUIRED: [&str; 10] = [
+    "server.host",
+    "server.port",
+    "logging.format",
+    "security",
+    "tls",
+    "storage",
+    "events",
+    "audit",
+    "cluster",
+    "pki",
+];
+
+/// Settings of [`RESTART_REQUIRED`] that differ between the configurations
+fn restart_required(previous: &AppConfig, config: &AppConfig) -> Vec<&'static str> {
+    let previous = serde_json::json!(previous);
+    let config = serde_json::json!(config);
+
+    RESTART_REQUIRED
+        .into_iter()
+        .filter(|setting| {
+            let pointer = format!("/{}", setting.replace('.', "/"));
+            previous.pointer(&pointer) != config.pointer(&pointer)
+        })
+        .collect()
+}
+
+pub struct ConfigReloader {
+    args: Args,
+    config: Arc<Live<AppConfig>>,
+    ip_restriction: Arc<Live<IpRestrictionMiddleware>>,
+    health_checker: Arc<HealthChecker>,
+    webhooks: Arc<WebhookDispatcher>,
+    log_filter: LogFilterHandle,
+    /// Modification time of the configuration file when last read
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        args: &Args,
+        config: Arc<Live<AppConfig>>,
+        ip_restriction: Arc<Live<IpRestrictionMiddleware>>,
+        health_checker: Arc<HealthChecker>,
+        webhooks: Arc<WebhookDispatcher>,
+        log_filter: LogFilterHandle,
+    ) -> Self {
+        let reloader = Self {
+            args: args.clone(),
+            config,
+            ip_restriction,
+            health_checker,
+            webhooks,
+            log_filter,
+            modified: Mutex::new(None),
+        };
+        *reloader.modified.lock().unwrap_or_else(|e| e.into_inner()) = reloader.modified_time();
+        reloader
+    }
+
+    fn modified_time(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.args.config)
+            .and_then(|m| m.modified())
+            .ok()
+    }
+
+    /// Re-reads the configuration file and applies it, or leaves the
+    /// running configuration untouched when the new one is invalid
+    pub async fn reload(&self) -> anyhow::Result<()> {
+        if !std::path::Path::new(&self.args.config).exists() {
+            return Err(anyhow::anyhow!(
+                "Configuration file {} not found",
+                self.args.config
+            ));
+        }
+
+        let config = load_config(&self.args)?;
+        validate_live_config(&config)?;
+        let ip_restriction =
+            IpRestrictionMiddleware::new(&config.network.clone().unwrap_or_default())?;
+        let filter = log_filter(&config.logging.level)?;
+
+        self.health_checker
+            .reconfigure(&config.health_check)
+            .await?;
+        self.webhooks.reload(&config.webhooks)?;
+        self.ip_restriction.store(ip_restriction);
+        if let Err(e) = self.log_filter.reload(filter) {
+            tracing::warn!("⚠️ Failed to apply the log level: {}", e);
+        }
+
+        let previous = self.config.load();
+        let restart_required = restart_required(&previous, &config);
+        if config.network != previous.network {
+            log_network_config(config.network.as_ref());
+        }
+        self.config.store(config);
+
+        tracing::info!("🔄 Configuration reloaded from {}", self.args.config);
+        if !restart_required.is_empty() {
+            tracing::warn!(
+                "⚠️ Changes to {} only apply after a restart",
+                restart_required.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Reloads when the configuration file changed since it was last read.
+    /// A rejected file is only retried once it changes again.
+    pub async fn reload_if_changed(&self) -> anyhow::Result<bool> {
+        let modified = self.modified_time();
+        {
+            let mut last = self.modified.lock().unwrap_or_else(|e| e.into_inner());
+            // A deleted file keeps the running configuration
+            if modified.is_none() || *last == modified {
+                return Ok(false);
+            }
+            *last = modified;
+        }
+
+        self.reload().await.map(|_| true)
+    }
+
+    /// Reloads on SIGHUP and when the configuration file changes
+    pub fn start(self: Arc<Self>) {
+        #[cfg(unix)]
+        {
+            let reloader = self.clone();
+            tokio::spawn(async move {
+                use tokio::signal::unix::{signal, SignalKind};
+
+                let mut hangups = match signal(SignalKind::hangup()) {
+                    Ok(hangups) => hangups,
+                    Err(e) => {
+                        tracing::error!("❌ Failed to listen for SIGHUP: {}", e);
+                        return;
+                    }
+                };
+                while hangups.recv().await.is_some() {
+                    tracing::info!("🔄 SIGHUP received, reloading the configuration");
+                    *reloader.modified.lock().unwrap_or_else(|e| e.into_inner()) =
+                        reloader.modified_time();
+                    if let Err(e) = reloader.reload().await {
+                        tracing::error!("❌ Configuration reload rejected: {}", e);
+                    }
+                }
+            });
+        }
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.reload_if_changed().await {
+                    tracing::error!("❌ Configuration reload rejected: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::ServiceRegistry;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn touch(path: &std::path::Path, seconds: u64) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_valid_config_only() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("scoutquest.toml");
+        std::fs::write(&path, "[health_check]\ninterval_seconds = 30\n").unwrap();
+
+        let args = Args::parse_from(["scoutquest-server", "--config", path.to_str().unwrap()]);
+        let config = load_config(&args).unwrap();
+        let live = Arc::new(Live::new(config.clone()));
+        let ip_restriction = Arc::new(Live::new(
+            IpRestrictionMiddleware::new(&Default::default()).unwrap(),
+        ));
+        let health_checker = Arc::new(HealthChecker::new(
+            Arc::new(ServiceRegistry::new()),
+            &config.health_check,
+        ));
+        let webhooks = Arc::new(WebhookDispatcher::new(&[]).unwrap());
+        let (_filter, log_filter) = reload::Layer::new(EnvFilter::new("info"));
+        let reloader = ConfigReloader::new(
+            &args,
+            live.clone(),
+            ip_restriction,
+            health_checker,
+            webhooks.clone(),
+            log_filter,
+        );
+        assert!(!reloader.reload_if_changed().await.unwrap());
+
+        std::fs::write(
+            &path,
+            r#"
+[server]
+cors_origins = ["https://dashboard.example.com"]
+
+[health_check]
+interval_seconds = 10
+
+[[webhooks]]
+url = "http://127.0.0.1:9/hook"
+"#,
+        )
+        .unwrap();
+        touch(&path, 1);
+        assert!(reloader.reload_if_changed().await.unwrap());
+        let config = live.load();
+        assert_eq!(config.health_check.interval_seconds, 10);
+        assert_eq!(
+            config.server.cors_origins,
+            vec!["https://dashboard.example.com"]
+        );
+        assert_eq!(webhooks.status().len(), 1);
+
+        // An invalid allow-list is rejected as a whole
+        std::fs::write(
+            &path,
+            r#"
+[health_check]
+interval_seconds = 20
+
+[network]
+enabled = true
+allowed_cidrs = ["not-a-cidr"]
+deny_action = "reject"
+trust_proxy_headers = false
+"#,
+        )
+        .unwrap();
+        touch(&path, 2);
+        assert!(reloader.reload_if_changed().await.is_err());
+        assert_eq!(live.load().health_check.interval_seconds, 10);
+        assert_eq!(webhooks.status().len(), 1);
+    }
+
+    #[test]
+    fn test_restart_required_sections() {
+        let previous = AppConfig::default();
+        let mut config = AppConfig::default();
+        config.server.cors_origins = vec![];
+        config.health_check.interval_seconds = 5;
+        assert!(restart_required(&previous, &config).is_empty());
+
+        config.server.port = 9090;
+        config.audit.enabled = true;
+        assert_eq!(
+            restart_required(&previous, &config),
+            vec!["server.port", "audit"]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Filtering and recent history of registry events for real-time consumers
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+use crate::models::{EventQuery, EventType, ServiceEvent};
+
+/// Selects the events a consumer is interested in.
+///
+/// Empty criteria match everything. An event must match one of the listed
+/// services, one of the listed event types and carry all the listed tags.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct EventFilter {
+    #[serde(default)]
+    pub services: Vec<String>,
+    #[serde(default)]
+    pub event_types: Vec<EventType>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &ServiceEvent) -> bool {
+        (self.services.is_empty() || self.services.contains(&event.service_name))
+            && (self.event_types.is_empty() || self.event_types.contains(&event.event_type))
+            && self.tags.iter().all(|tag| event.tags.contains(tag))
+    }
+}
+
+impl From<&EventQuery> for EventFilter {
+    fn from(query: &EventQuery) -> Self {
+        Self {
+            services: query.service.iter().cloned().collect(),
+            event_types: query.event_type.iter().cloned().collect(),
+            tags: query.tag.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Recent events, kept so clients can resume a stream after a disconnect
+pub struct EventHistory {
+    events: VecDeque<ServiceEvent>,
+    capacity: usize,
+    last_sequence: u64,
+}
+
+/// Result of looking up the events following a sequence number
+#[derive(Debug)]
+pub enum EventReplay {
+    Events {
+        events: Vec<ServiceEvent>,
+        /// Cursor to resume from next time
+        last_sequence: u64,
+    },
+    /// Events after the requested sequence are no longer available, the
+    /// client has to re-list the registry
+    TooOld {
+        oldest_sequence: u64,
+        last_sequence: u64,
+    },
+}
+
+impl EventHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            last_sequence: 0,
+        }
+    }
+
+    /// Sequence number of the last recorded event
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Numbers the next events after `sequence`, the last one recorded
+    /// before a restart. Cursors from before the restart then get a resync
+    /// instead of unrelated events.
+    pub fn resume_after(&mut self, sequence: u64) {
+        self.last_sequence = self.last_sequence.max(sequence);
+    }
+
+    /// Assigns the next sequence number to `event` and records it
+    pub fn record(&mut self, event: &mut ServiceEvent) {
+        self.last_sequence += 1;
+        event.sequence = self.last_sequence;
+
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.clone());
+    }
+
+    /// Events with a sequence number greater than `sequence`
+    pub fn since(&self, sequence: u64) -> EventReplay {
+        let oldest_sequence = self
+            .events
+            .front()
+            .map(|event| event.sequence)
+            .unwrap_or(self.last_sequence + 1);
+
+        // A cursor from the future comes from before a restart
+        if sequence > self.last_sequence || sequence + 1 < oldest_sequence {
+            return EventReplay::TooOld {
+                oldest_sequence,
+                last_sequence: self.last_sequence,
+            };
+        }
+
+        EventReplay::Events {
+            events: self
+                .events
+                .iter()
+                .filter(|event| event.sequence > sequence)
+                .cloned()
+                .collect(),
+            last_sequence: self.last_sequence,
+        }
+    }
+}
+
+/// Spots instances whose status changes too often
+pub struct FlapDetector {
+    /// Status changes within `window` that make an instance flapping, 0
+    /// disables detection
+    threshold: usize,
+    window: chrono::Duration,
+    changes: HashMap<String, VecDeque<DateTime<Utc>>>,
+}
+
+impl FlapDetector {
+    pub fn new(threshold: usize, window: std::time::Duration) -> Self {
+        Self {
+            threshold,
+            window: chrono::Duration::from_std(window).unwrap_or(chrono::Duration::MAX),
+            changes: HashMap::new(),
+        }
+    }
+
+    /// Records a status change and returns true when it is the
+    /// `threshold`-th one within the window. Counting then starts over, so
+    /// an instance that keeps flapping is reported once per `threshold`
+    /// changes.
+    pub fn record(&mut self, instance_id: &str, timestamp: DateTime<Utc>) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+
+        let changes = self.changes.entry(instance_id.to_string()).or_default();
+        changes.push_back(timestamp);
+        while changes
+            .front()
+            .is_some_and(|first| timestamp - *first > self.window)
+        {
+            changes.pop_front();
+        }
+
+        if changes.len() >= self.threshold {
+            changes.clear();
+            return true;
+        }
+        false
+    }
+
+    pub fn forget(&mut self, instance_id: &str) {
+        self.changes.remove(instance_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn event(service_name: &str, event_type: EventType, tags: &[&str]) -> ServiceEvent {
+        ServiceEvent {
+            event_type,
+            service_name: service_name.to_string(),
+            instance_id: Some("instance-1".to_string()),
+            sequence: 0,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            timestamp: Utc::now(),
+            details: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = EventFilter::default();
+        assert!(filter.matches(&event("users", EventType::InstanceRegistered, &[])));
+    }
+
+    #[test]
+    fn test_filter_criteria() {
+        let filter = EventFilter {
+            services: vec!["users".to_string(), "orders".to_string()],
+            event_types: vec![EventType::InstanceStatusChanged],
+            tags: vec!["prod".to_string()],
+        };
+
+        assert!(filter.matches(&event(
+            "orders",
+            EventType::InstanceStatusChanged,
+            &["prod", "api"]
+        )));
+        assert!(!filter.matches(&event(
+            "billing",
+            EventType::InstanceStatusChanged,
+            &["prod"]
+        )));
+        assert!(!filter.matches(&event("users", EventType::InstanceRegistered, &["prod"])));
+        assert!(!filter.matches(&event(
+            "users",
+            EventType::InstanceStatusChanged,
+            &["staging"]
+        )));
+    }
+
+    #[test]
+    fn test_flapping_needs_threshold_changes_within_window() {
+        let mut detector = FlapDetector::new(3, std::time::Duration::from_secs(60));
+        let start = Utc::now();
+        let at = |seconds: i64| start + chrono::Duration::seconds(seconds);
+
+        assert!(!detector.record("instance-1", at(0)));
+        assert!(!detector.record("instance-1", at(30)));
+        // The first change fell out of the window
+        assert!(!detector.record("instance-1", at(70)));
+        assert!(!detector.record("instance-2", at(75)));
+        assert!(detector.record("instance-1", at(80)));
+
+        // Reported once, then counting starts over
+        assert!(!detector.record("instance-1", at(81)));
+        assert!(
+            !FlapDetector::new(0, std::time::Duration::from_secs(60)).record("instance-1", at(0))
+        );
+    }
+
+    #[test]
+    fn test_history_replays_from_cursor() {
+        let mut history = EventHistory::new(3);
+        for _ in 0..5 {
+            history.record(&mut event("users", EventType::InstanceRegistered, &[]));
+        }
+
+        let EventReplay::Events { events, .. } = history.since(3) else {
+            panic!("expected events");
+        };
+        let sequences: Vec<u64> = events.iter().map(|event| event.sequence).collect();
+        assert_eq!(sequences, vec![4, 5]);
+
+        // Sequence 2 was the last one evicted, so 2 is still a valid cursor
+        assert!(
+            matches!(history.since(2), EventReplay::Events { ref events, .. } if events.len() == 3)
+        );
+        assert!(matches!(
+            history.since(1),
+            EventReplay::TooOld {
+                oldest_sequence: 3,
+                last_sequence: 5
+            }
+        ));
+        assert!(matches!(history.since(9), EventReplay::TooOld { .. }));
+        assert!(
+            matches!(history.since(5), EventReplay::Events { ref events, last_sequence: 5 } if events.is_empty())
+        );
+    }
+}
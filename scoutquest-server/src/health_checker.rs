@@ -1,45 +1,60 @@
 use reqwest::Client;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio_cron_scheduler::{Job, JobScheduler};
+use uuid::Uuid;
 
 use crate::{models::InstanceStatus, registry::ServiceRegistry, HealthCheckConfig};
 
 pub struct HealthChecker {
     registry: Arc<ServiceRegistry>,
-    http_client: Client,
-    config: HealthCheckConfig,
+    config: Mutex<HealthCheckConfig>,
+    /// Scheduler and the id of the probe job, once monitoring started
+    scheduler: tokio::sync::Mutex<Option<(JobScheduler, Uuid)>>,
 }
 
 impl HealthChecker {
     pub fn new(registry: Arc<ServiceRegistry>, config: &HealthCheckConfig) -> Self {
-        let http_client = Client::builder()
-            .timeout(Duration::from_secs(config.timeout_seconds))
-            .build()
-            .expect("Failed to create HTTP client");
-
         Self {
             registry,
-            http_client,
-            config: config.clone(),
+            config: Mutex::new(config.clone()),
+            scheduler: tokio::sync::Mutex::new(None),
         }
     }
 
-    pub async fn start_monitoring(&self) -> anyhow::Result<()> {
-        let scheduler = JobScheduler::new().await?;
+    fn current_config(&self) -> HealthCheckConfig {
+        self.config
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
 
+    /// Job probing every instance at the configured interval
+    fn probe_job(&self, config: &HealthCheckConfig) -> anyhow::Result<Job> {
         let registry = self.registry.clone();
-        let client = self.http_client.clone();
-        let interval = self.config.interval_seconds;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()?;
+
+        let job = Job::new_async(
+            format!("0/{} * * * * *", config.interval_seconds),
+            move |_uuid, _l| {
+                let registry = registry.clone();
+                let client = client.clone();
+
+                Box::pin(async move {
+                    Self::check_all_instances(registry, client).await;
+                })
+            },
+        )?;
+        Ok(job)
+    }
 
-        let health_job = Job::new_async(format!("0/{} * * * * *", interval), move |_uuid, _l| {
-            let registry = registry.clone();
-            let client = client.clone();
+    pub async fn start_monitoring(&self) -> anyhow::Result<()> {
+        let scheduler = JobScheduler::new().await?;
+        let config = self.current_config();
 
-            Box::pin(async move {
-                Self::check_all_instances(registry, client).await;
-            })
-        })?;
+        let health_job = self.probe_job(&config)?;
 
         let registry_cleanup = self.registry.clone();
         let cleanup_job = Job::new_async("0 */5 * * * *", move |_uuid, _l| {
@@ -50,16 +65,74 @@ impl HealthChecker {
             })
         })?;
 
-        scheduler.add(health_job).await?;
+        let registry_maintenance = self.registry.clone();
+        let maintenance_job = Job::new_async("0/10 * * * * *", move |_uuid, _l| {
+            let registry = registry_maintenance.clone();
+
+            Box::pin(async move {
+                if registry.is_leader() {
+                    if let Err(e) = registry.end_expired_maintenance().await {
+                        tracing::error!("❌ Failed to end expired maintenance: {}", e);
+                    }
+                }
+            })
+        })?;
+
+        let health_job_id = scheduler.add(health_job).await?;
         scheduler.add(cleanup_job).await?;
+        scheduler.add(maintenance_job).await?;
         scheduler.start().await?;
+        *self.scheduler.lock().await = Some((scheduler, health_job_id));
 
-        tracing::info!("🏥 Health checker started (interval: {}s)", interval);
+        tracing::info!(
+            "🏥 Health checker started (interval: {}s)",
+            config.interval_seconds
+        );
+        Ok(())
+    }
+
+    /// Applies a new probe interval and timeout. The probe job is only
+    /// replaced once the new one is scheduled.
+    pub async fn reconfigure(&self, config: &HealthCheckConfig) -> anyhow::Result<()> {
+        let current = self.current_config();
+        if current.interval_seconds == config.interval_seconds
+            && current.timeout_seconds == config.timeout_seconds
+        {
+            *self.config.lock().unwrap_or_else(|e| e.into_inner()) = config.clone();
+            return Ok(());
+        }
+
+        let job = self.probe_job(config)?;
+        let mut scheduler = self.scheduler.lock().await;
+        if let Some((scheduler, job_id)) = scheduler.as_mut() {
+            let new_job_id = scheduler.add(job).await?;
+            scheduler.remove(job_id).await?;
+            *job_id = new_job_id;
+        }
+        *self.config.lock().unwrap_or_else(|e| e.into_inner()) = config.clone();
+
+        tracing::info!(
+            "🏥 Health checker reconfigured (interval: {}s, timeout: {}s)",
+            config.interval_seconds,
+            config.timeout_seconds
+        );
         Ok(())
     }
 
     async fn check_all_instances(registry: Arc<ServiceRegistry>, client: Client) {
-        let instances: Vec<_> = registry.get_all_instances();
+        // In a cluster only the leader probes instances, followers get the
+        // resulting status changes through replication
+        if !registry.is_leader() {
+            return;
+        }
+
+        let instances = match registry.get_all_instances() {
+            Ok(instances) => instances,
+            Err(e) => {
+                tracing::error!("❌ Failed to list instances to probe: {}", e);
+                return;
+            }
+        };
 
         for instance in instances {
             if let Some(health_check) = &instance.health_check {
@@ -71,33 +144,59 @@ impl HealthChecker {
                     InstanceStatus::Down
                 };
 
+                // In maintenance, the result only sets the status the
+                // instance returns to
+                let current_status = instance
+                    .maintenance
+                    .as_ref()
+                    .and_then(|maintenance| maintenance.previous_status.clone())
+                    .unwrap_or(instance.status);
+
                 if !matches!(
-                    (instance.status.clone(), &new_status),
+                    (current_status, &new_status),
                     (InstanceStatus::Up, InstanceStatus::Up)
                         | (InstanceStatus::Down, InstanceStatus::Down)
                 ) {
-                    registry
+                    if let Err(e) = registry
                         .update_instance_status(&instance.id, new_status)
-                        .await;
+                        .await
+                    {
+                        tracing::error!("❌ Failed to update {}: {}", instance.id, e);
+                    }
                 }
             }
         }
     }
 
     async fn cleanup_stale_instances(registry: Arc<ServiceRegistry>) {
+        if !registry.is_leader() {
+            return;
+        }
+
         let now = chrono::Utc::now();
         let stale_threshold = chrono::Duration::minutes(5);
 
-        let stale_instances: Vec<String> = registry
-            .get_all_instances()
+        let instances = match registry.get_all_instances() {
+            Ok(instances) => instances,
+            Err(e) => {
+                tracing::error!("❌ Failed to list instances: {}", e);
+                return;
+            }
+        };
+
+        let stale_instances: Vec<String> = instances
             .iter()
+            // Instances in maintenance may be stopped on purpose
+            .filter(|entry| entry.maintenance.is_none())
             .filter(|entry| now.signed_duration_since(entry.last_heartbeat) > stale_threshold)
             .map(|entry| entry.id.clone())
             .collect();
 
         for instance_id in stale_instances {
             tracing::warn!("Removing stale instance: {}", instance_id);
-            registry.deregister_instance(&instance_id).await;
+            if let Err(e) = registry.deregister_instance(&instance_id).await {
+                tracing::error!("❌ Failed to remove {}: {}", instance_id, e);
+            }
         }
     }
 
@@ -1,28 +1,55 @@
 use axum::{
     extract::State,
-    response::Json,
+    http::StatusCode,
+    response::{IntoResponse, Json},
     routing::{delete, get, post, put},
     Router,
 };
 use clap::Parser;
 use config::{Config, Environment, File};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tower_http::trace::TraceLayer;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod api;
+mod audit;
+mod cluster;
+mod commands;
+mod config_reload;
+mod events;
 mod health_checker;
 pub mod middleware;
 mod models;
+mod persistence;
 mod registry;
+mod sse;
+mod store;
 mod tls;
+mod webhooks;
+mod websocket;
 
+use audit::AuditLog;
+use cluster::{ClusterNode, ReadConsistency};
+use config_reload::{ConfigReloader, Live, LogFilterHandle};
 use health_checker::HealthChecker;
-use middleware::ip_restriction::{ip_restriction_layer, IpRestrictionMiddleware};
+use middleware::auth::{auth_layer, ApiKeyAuth, ApiKeyConfig, ClientCertConfig};
+use middleware::ip_restriction::{
+    ip_restriction_layer, IpRestrictionMiddleware, NetworkPolicyConfig, NetworkRoutesConfig,
+};
+use middleware::jwt::JwtConfig;
+use middleware::rate_limit::{
+    failed_auth_limit_layer, rate_limit_layer, RateLimitConfig, RateLimiter,
+};
+use middleware::scopes::{require_scope, RequiredScope};
 pub use models::*;
+use persistence::RegistryPersistence;
 use registry::ServiceRegistry;
-use tls::start_server;
+use store::StorageBackend;
+use tls::reload::CertificateReloadStats;
+use tls::{start_server, CertificateAuthority, TlsPolicy};
+use webhooks::WebhookDispatcher;
 
 /// SquoutQuest server configuration
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -33,6 +60,14 @@ pub struct AppConfig {
     pub security: SecurityConfig,
     pub network: Option<NetworkConfig>,
     pub tls: Option<ScoutQuestTlsConfig>,
+    pub storage: StorageConfig,
+    pub events: EventsConfig,
+    pub audit: AuditConfig,
+    pub cluster: Option<ClusterConfig>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub pki: PkiConfig,
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -59,17 +94,224 @@ pub struct HealthCheckConfig {
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct SecurityConfig {
     pub enable_auth: bool,
+    /// Single key accepted with the id `default`, prefer `api_keys`
     pub api_key: Option<String>,
+    /// Named keys, each read inline, from a file or from an environment variable
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// Bearer JWTs accepted in addition to the API keys
+    #[serde(default)]
+    pub jwt: Option<JwtConfig>,
+    /// Client certificate identities accepted in place of a key when mutual
+    /// TLS is enabled
+    #[serde(default)]
+    pub client_certs: Vec<ClientCertConfig>,
+    /// Paths served without authentication, prefixes when ending with `*`
+    #[serde(default = "default_exempt_paths")]
+    pub exempt_paths: Vec<String>,
+    /// Requests per minute allowed to each client, 0 to disable rate limiting
     pub rate_limit_per_minute: u32,
+    /// Limits overriding `rate_limit_per_minute` per route class
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+}
+
+fn default_exempt_paths() -> Vec<String> {
+    vec!["/health".to_string(), "/metrics".to_string()]
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct StorageConfig {
+    /// Registry storage backend (memory or sqlite)
+    pub backend: StorageBackend,
+    /// Persist the in-memory registry to disk (write-ahead log + snapshots)
+    pub enabled: bool,
+    /// Directory holding the snapshot, log segments and SQLite database
+    pub data_dir: String,
+    /// Interval between log compactions into a snapshot
+    pub snapshot_interval_seconds: u64,
+    /// fsync every log append instead of relying on the OS page cache
+    pub sync_writes: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct EventsConfig {
+    /// Number of recent events kept for replay (`/api/events?since=`, `Last-Event-ID`)
+    pub history_size: usize,
+    /// Status changes within `flap_window_seconds` that make an instance
+    /// flapping (`InstanceFlapping` event), 0 disables detection
+    pub flap_threshold: usize,
+    pub flap_window_seconds: u64,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct AuditConfig {
+    /// Record registrations, deregistrations and status changes made through the API
+    pub enabled: bool,
+    /// Append-only JSONL file holding the audit entries
+    pub path: String,
+    /// Maximum number of entries returned by `GET /api/admin/audit`
+    pub max_results: usize,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(default)]
+pub struct PkiConfig {
+    /// Run an internal CA that signs the server certificate and issues
+    /// certificates to registered instances
+    pub enabled: bool,
+    /// Directory holding the CA certificate and private key
+    pub ca_dir: String,
+    /// Common name of the generated CA certificate
+    pub common_name: String,
+    pub ca_validity_days: u32,
+    /// Longest validity of the certificates issued by `POST /api/pki/issue`
+    pub leaf_validity_hours: u32,
+    /// Domain of the names in issued certificates: `<service>.<domain>` and
+    /// `spiffe://<domain>/service/<service>`
+    pub domain: String,
+}
+
+impl Default for PkiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ca_dir: "./data/pki".to_string(),
+            common_name: "ScoutQuest Internal CA".to_string(),
+            ca_validity_days: 3650,
+            leaf_validity_hours: 24,
+            domain: "scoutquest.internal".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(default)]
+pub struct ClusterConfig {
+    pub enabled: bool,
+    /// Unique id of this node, must match one of `peers` (or be added to them)
+    pub node_id: String,
+    /// URL other nodes and redirected clients use to reach this node
+    pub advertise_url: String,
+    /// Every cluster member, this node included
+    pub peers: Vec<ClusterPeerConfig>,
+    pub heartbeat_interval_ms: u64,
+    pub election_timeout_min_ms: u64,
+    pub election_timeout_max_ms: u64,
+    /// Timeout of a single Raft message between nodes
+    pub rpc_timeout_ms: u64,
+    /// How long a write waits to be committed by a majority
+    pub propose_timeout_ms: u64,
+    /// How reads are served: stale, bounded or leader
+    pub read_consistency: ReadConsistency,
+    /// Maximum time since the last leader contact for bounded reads
+    pub max_staleness_ms: u64,
+    /// Number of log entries kept before compacting into a snapshot
+    pub snapshot_threshold: usize,
+    /// `[[security.api_keys]]` entry used to authenticate to peers when auth is enabled
+    pub api_key_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct ClusterPeerConfig {
+    pub id: String,
+    pub url: String,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            node_id: String::new(),
+            advertise_url: String::new(),
+            peers: Vec::new(),
+            heartbeat_interval_ms: 50,
+            election_timeout_min_ms: 150,
+            election_timeout_max_ms: 300,
+            rpc_timeout_ms: 1000,
+            propose_timeout_ms: 5000,
+            read_consistency: ReadConsistency::Stale,
+            max_staleness_ms: 1000,
+            snapshot_threshold: 10000,
+            api_key_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Name shown in delivery reports, defaults to the URL
+    pub name: Option<String>,
+    pub url: String,
+    /// Event types to deliver, all of them when empty
+    pub event_types: Vec<EventType>,
+    /// Services whose events are delivered, all of them when empty
+    pub services: Vec<String>,
+    /// Signs payloads with HMAC-SHA256 in the `X-ScoutQuest-Signature` header
+    pub secret: Option<String>,
+    /// Retries after the first failed attempt before giving up on an event
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after each failure
+    pub initial_backoff_ms: u64,
+    pub timeout_seconds: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            url: String::new(),
+            event_types: Vec::new(),
+            services: Vec::new(),
+            secret: None,
+            max_retries: 5,
+            initial_backoff_ms: 1000,
+            timeout_seconds: 10,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq)]
 pub struct NetworkConfig {
     pub enabled: bool,
     pub allowed_cidrs: Vec<String>,
     pub denied_cidrs: Option<Vec<String>>,
     pub deny_action: String,
     pub trust_proxy_headers: bool,
+    /// Proxies whose forwarded headers are believed, when
+    /// `trust_proxy_headers` is set
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    /// Header the trusted proxies set: "x-forwarded-for", "forwarded" or
+    /// "x-real-ip". Other forwarded headers are ignored.
+    #[serde(default = "default_proxy_header")]
+    pub proxy_header: String,
+    /// Named CIDR policies that `routes` bind to route groups
+    #[serde(default)]
+    pub policies: HashMap<String, NetworkPolicyConfig>,
+    #[serde(default)]
+    pub routes: NetworkRoutesConfig,
+}
+
+fn default_proxy_header() -> String {
+    "x-forwarded-for".to_string()
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_cidrs: vec!["0.0.0.0/0".to_string(), "::/0".to_string()],
+            denied_cidrs: None,
+            deny_action: "reject".to_string(),
+            trust_proxy_headers: false,
+            trusted_proxies: Vec::new(),
+            proxy_header: default_proxy_header(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
+        }
+    }
 }
 
 impl Default for AppConfig {
@@ -93,16 +335,41 @@ impl Default for AppConfig {
             security: SecurityConfig {
                 enable_auth: false,
                 api_key: None,
+                api_keys: Vec::new(),
+                jwt: None,
+                client_certs: Vec::new(),
+                exempt_paths: default_exempt_paths(),
                 rate_limit_per_minute: 1000,
+                rate_limits: RateLimitConfig::default(),
             },
             network: None,
             tls: None,
+            storage: StorageConfig {
+                backend: StorageBackend::Memory,
+                enabled: false,
+                data_dir: "./data".to_string(),
+                snapshot_interval_seconds: 300,
+                sync_writes: false,
+            },
+            events: EventsConfig {
+                history_size: 1000,
+                flap_threshold: 5,
+                flap_window_seconds: 300,
+            },
+            audit: AuditConfig {
+                enabled: false,
+                path: "./data/audit.jsonl".to_string(),
+                max_results: 1000,
+            },
+            cluster: None,
+            webhooks: Vec::new(),
+            pki: PkiConfig::default(),
         }
     }
 }
 
 /// Command line arguments
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(name = "scoutquest-server")]
 #[command(about = "SquoutQuest - Universal Service Discovery for microservices")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
@@ -122,13 +389,43 @@ struct Args {
     /// Log level (overrides configuration)
     #[arg(long)]
     log_level: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<commands::Command>,
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub registry: Arc<ServiceRegistry>,
     pub health_checker: Arc<HealthChecker>,
-    pub config: AppConfig,
+    /// Current configuration, replaced on reload
+    pub config: Arc<Live<AppConfig>>,
+    pub cluster: Option<Arc<ClusterNode>>,
+    pub webhooks: Arc<WebhookDispatcher>,
+    pub audit: Arc<AuditLog>,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Internal CA, when `[pki]` is enabled
+    pub pki: Option<Arc<CertificateAuthority>>,
+    pub certificate_reloads: Arc<CertificateReloadStats>,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// Single node state with the default configuration
+    pub fn for_tests(registry: Arc<ServiceRegistry>) -> Self {
+        let config = AppConfig::default();
+        Self {
+            health_checker: Arc::new(HealthChecker::new(registry.clone(), &config.health_check)),
+            registry,
+            cluster: None,
+            webhooks: Arc::new(WebhookDispatcher::new(&[]).expect("no webhooks to validate")),
+            audit: Arc::new(AuditLog::open(&config.audit).expect("audit log disabled")),
+            rate_limiter: Arc::new(RateLimiter::new(&config.security)),
+            pki: None,
+            certificate_reloads: Arc::default(),
+            config: Arc::new(Live::new(config)),
+        }
+    }
 }
 
 #[tokio::main]
@@ -142,112 +439,257 @@ async fn main() -> anyhow::Result<()> {
 
     let config = load_config(&args)?;
 
-    setup_logging(&config.logging)?;
+    match &args.command {
+        Some(commands::Command::CheckConfig) => {
+            let errors = commands::check_config(&apply_cli_overrides(&args, config));
+            if errors.is_empty() {
+                println!("✅ Configuration {} is valid", args.config);
+                return Ok(());
+            }
+            for (section, error) in &errors {
+                println!("❌ [{}] {}", section, error);
+            }
+            std::process::exit(1);
+        }
+        Some(commands::Command::PrintConfig { format }) => {
+            let config = apply_cli_overrides(&args, config);
+            println!("{}", commands::print_config(&config, *format)?);
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let log_filter = setup_logging(&config.logging)?;
 
     tracing::info!(
         "🔍 Starting SquoutQuest Server v{}",
         env!("CARGO_PKG_VERSION")
     );
 
-    let registry = Arc::new(ServiceRegistry::new());
-    let health_checker = Arc::new(HealthChecker::new(registry.clone(), &config.health_check));
-
-    health_checker.start_monitoring().await?;
+    let cluster_config = config.cluster.clone().filter(|cluster| cluster.enabled);
 
-    let app_state = AppState {
-        registry,
-        health_checker,
-        config: config.clone(),
+    store::validate_config(&config.storage)?;
+    let registry = match config.storage.backend {
+        // Cluster members rebuild the registry from the replicated log
+        _ if cluster_config.is_some() => {
+            tracing::info!("💾 Cluster mode: registry replicated through the cluster log");
+            ServiceRegistry::with_store(store::open_store(&config.storage)?)
+        }
+        StorageBackend::Sqlite => {
+            let store = store::open_store(&config.storage)?;
+            tracing::info!(
+                "💾 Registry stored in SQLite under {}",
+                config.storage.data_dir
+            );
+            ServiceRegistry::with_store(store)
+        }
+        StorageBackend::Memory if config.storage.enabled => {
+            let (persistence, recovered) = RegistryPersistence::open(&config.storage)?;
+            ServiceRegistry::with_persistence(persistence, recovered)?
+        }
+        StorageBackend::Memory => {
+            tracing::info!("💾 Registry persistence disabled (in-memory only)");
+            ServiceRegistry::new()
+        }
     };
+    let registry = Arc::new(
+        registry
+            .with_event_history(config.events.history_size)
+            .with_flap_detection(
+                config.events.flap_threshold,
+                std::time::Duration::from_secs(config.events.flap_window_seconds),
+            ),
+    );
 
-    // Create IP restriction middleware if enabled
-    let ip_restriction_middleware = if let Some(network_config) = &config.network {
-        match IpRestrictionMiddleware::new(network_config) {
-            Ok(middleware) => {
-                if network_config.enabled {
-                    tracing::info!("🛡️ Network IP restrictions enabled");
-                    tracing::info!("   Allowed CIDRs: {:?}", network_config.allowed_cidrs);
-                    if let Some(denied) = &network_config.denied_cidrs {
-                        tracing::info!("   Denied CIDRs: {:?}", denied);
-                    }
-                    tracing::info!("   Action: {}", network_config.deny_action);
-                    tracing::info!(
-                        "   Trust proxy headers: {}",
-                        network_config.trust_proxy_headers
-                    );
-                } else {
-                    tracing::info!("🛡️ Network IP restrictions disabled");
+    if registry.is_persistent() {
+        persistence::start_snapshot_task(
+            registry.clone(),
+            config.storage.snapshot_interval_seconds,
+        );
+    }
+
+    let auth = Arc::new(ApiKeyAuth::new(&config.security)?);
+    if auth.is_enabled() {
+        tracing::info!("🔑 API key authentication enabled");
+        tracing::info!("   Keys: {:?}", auth.describe_keys());
+        if auth.accepts_jwt() {
+            tracing::info!("   JWT bearer tokens accepted");
+        }
+        let client_certs = auth.describe_client_certs();
+        if !client_certs.is_empty() {
+            tracing::info!("   Client certificates: {:?}", client_certs);
+        }
+        tracing::info!("   Exempt paths: {:?}", config.security.exempt_paths);
+    } else {
+        tracing::info!("🔑 API key authentication disabled");
+    }
+
+    auth.start_key_reload();
+
+    let rate_limiter = Arc::new(RateLimiter::new(&config.security));
+    if rate_limiter.is_enabled() {
+        tracing::info!("🚦 Rate limiting enabled");
+        tracing::info!("   Limits per client: {:?}", rate_limiter.describe_limits());
+    } else {
+        tracing::info!("🚦 Rate limiting disabled");
+    }
+    rate_limiter.start_cleanup();
+
+    let cluster = match cluster_config {
+        Some(cluster_config) => {
+            let data_dir = (config.storage.enabled
+                || config.storage.backend == StorageBackend::Sqlite)
+                .then(|| std::path::PathBuf::from(&config.storage.data_dir));
+            let peer_api_key = match &cluster_config.api_key_id {
+                Some(id) => Some(auth.secret(id).ok_or_else(|| {
+                    anyhow::anyhow!("cluster.api_key_id {} is not a configured API key", id)
+                })?),
+                None if auth.is_enabled() => {
+                    return Err(anyhow::anyhow!(
+                        "cluster.api_key_id is required when authentication is enabled"
+                    ))
                 }
-                Some(Arc::new(middleware))
-            }
-            Err(e) => {
-                tracing::error!("❌ Failed to initialize IP restriction middleware: {}", e);
-                return Err(e);
-            }
+                None => None,
+            };
+            let node = ClusterNode::new(
+                cluster_config,
+                registry.clone(),
+                data_dir.as_deref(),
+                peer_api_key,
+            )?;
+            node.start();
+            Some(node)
         }
+        None => None,
+    };
+
+    let health_checker = Arc::new(HealthChecker::new(registry.clone(), &config.health_check));
+
+    health_checker.start_monitoring().await?;
+
+    let webhooks = Arc::new(WebhookDispatcher::new(&config.webhooks)?);
+    webhooks.start(registry.clone());
+
+    let audit = Arc::new(AuditLog::open(&config.audit)?);
+    if audit.is_enabled() {
+        tracing::info!("📝 Audit log written to {}", audit.path().display());
+    }
+
+    let pki = if config.pki.enabled {
+        tls::ca::validate_config(&config.pki, &config.security.client_certs)?;
+        let ca = CertificateAuthority::load_or_create(&config.pki)?;
+        tracing::info!("🏛️ Internal CA enabled: {}", ca.cert_path().display());
+        tracing::info!(
+            "   Issued certificates valid for up to {}h",
+            config.pki.leaf_validity_hours
+        );
+        Some(Arc::new(ca))
     } else {
-        tracing::info!("🛡️ Network IP restrictions not configured (disabled)");
         None
     };
 
-    let cors = if config.server.enable_cors {
-        if config.server.cors_origins.contains(&"*".to_string()) {
-            CorsLayer::new()
-                .allow_origin(tower_http::cors::Any)
-                .allow_methods(tower_http::cors::Any)
-                .allow_headers(tower_http::cors::Any)
-        } else {
-            let origins: Result<Vec<_>, _> = config
-                .server
-                .cors_origins
-                .iter()
-                .map(|origin| origin.parse::<axum::http::HeaderValue>())
-                .collect();
-
-            CorsLayer::new()
-                .allow_origin(origins?)
-                .allow_methods([
-                    axum::http::Method::GET,
-                    axum::http::Method::POST,
-                    axum::http::Method::PUT,
-                    axum::http::Method::DELETE,
-                ])
-                .allow_headers([
-                    axum::http::header::CONTENT_TYPE,
-                    axum::http::header::AUTHORIZATION,
-                ])
+    let certificate_reloads = Arc::new(CertificateReloadStats::default());
+
+    let live_config = Arc::new(Live::new(config.clone()));
+    let app_state = AppState {
+        registry,
+        health_checker: health_checker.clone(),
+        config: live_config.clone(),
+        cluster: cluster.clone(),
+        webhooks: webhooks.clone(),
+        audit,
+        rate_limiter: rate_limiter.clone(),
+        pki: pki.clone(),
+        certificate_reloads: certificate_reloads.clone(),
+    };
+
+    // Always installed, so a reload can enable the restrictions
+    let network_config = config.network.clone().unwrap_or_default();
+    let ip_restriction = match IpRestrictionMiddleware::new(&network_config) {
+        Ok(middleware) => Arc::new(Live::new(middleware)),
+        Err(e) => {
+            tracing::error!("❌ Failed to initialize IP restriction middleware: {}", e);
+            return Err(e);
         }
-    } else {
-        CorsLayer::new()
     };
+    log_network_config(config.network.as_ref());
+
+    config_reload::validate_live_config(&config)?;
+    let cors = config_reload::cors_layer(live_config.clone());
+
+    let mut api = api_routes();
+    if let Some(node) = &cluster {
+        api = api.layer(axum::middleware::from_fn_with_state(
+            node.clone(),
+            cluster::read_consistency_layer,
+        ));
+    }
+    // Registered after the read consistency layer: any node describes the cluster
+    let api = api.route(
+        "/cluster",
+        get(api::cluster_status).route_layer(axum::middleware::from_fn_with_state(
+            RequiredScope::Read,
+            require_scope,
+        )),
+    );
 
     let mut app = Router::new()
-        .nest("/api", api_routes())
+        .nest("/api", api)
         .route("/health", get(health_endpoint))
         .route("/metrics", get(metrics_endpoint))
         .route("/dashboard", get(dashboard))
         .route("/info", get(info_endpoint))
-        .route("/ws", get(websocket_handler))
-        .layer(TraceLayer::new_for_http());
+        .route(
+            "/ws",
+            get(websocket::websocket_handler).route_layer(axum::middleware::from_fn_with_state(
+                RequiredScope::Read,
+                require_scope,
+            )),
+        );
 
-    // Add IP restriction middleware if configured
-    if let Some(ip_middleware) = ip_restriction_middleware {
-        app = app.layer(axum::middleware::from_fn_with_state(
-            ip_middleware,
-            ip_restriction_layer,
+    if let Some(node) = cluster {
+        // Peers replicate writes, so their key needs the admin scope
+        let rpc = cluster::rpc::router(node).route_layer(axum::middleware::from_fn_with_state(
+            RequiredScope::Admin,
+            require_scope,
         ));
+        app = app.nest_service("/cluster", rpc);
     }
 
-    let app = app.layer(cors).with_state(app_state);
+    // Inside the auth layer, so authenticated clients are limited per key,
+    // while failed authentications are limited outside it per address
+    app = app
+        .layer(axum::middleware::from_fn_with_state(
+            rate_limiter.clone(),
+            rate_limit_layer,
+        ))
+        .layer(axum::middleware::from_fn_with_state(auth, auth_layer))
+        .layer(axum::middleware::from_fn_with_state(
+            rate_limiter,
+            failed_auth_limit_layer,
+        ))
+        .layer(TraceLayer::new_for_http());
+
+    let app = app
+        .layer(axum::middleware::from_fn_with_state(
+            ip_restriction.clone(),
+            ip_restriction_layer,
+        ))
+        .layer(cors)
+        .with_state(app_state);
+
+    Arc::new(ConfigReloader::new(
+        &args,
+        live_config,
+        ip_restriction,
+        health_checker,
+        webhooks,
+        log_filter,
+    ))
+    .start();
 
     // Apply command-line overrides to server config
-    let mut final_config = config.clone();
-    if let Some(port) = args.port {
-        final_config.server.port = port;
-    }
-    if let Some(host) = &args.host {
-        final_config.server.host = host.clone();
-    }
+    let final_config = apply_cli_overrides(&args, config.clone());
 
     // Log server startup information
     let protocol = if final_config
@@ -279,7 +721,7 @@ async fn main() -> anyhow::Result<()> {
     );
 
     // Start the server (HTTP or HTTPS based on configuration)
-    start_server(app, &final_config).await?;
+    start_server(app, &final_config, pki, certificate_reloads).await?;
 
     Ok(())
 }
@@ -315,14 +757,28 @@ fn load_config(args: &Args) -> anyhow::Result<AppConfig> {
     Ok(config)
 }
 
-fn setup_logging(config: &LoggingConfig) -> anyhow::Result<()> {
-    let level = config
-        .level
-        .parse::<tracing::Level>()
-        .unwrap_or(tracing::Level::INFO);
+/// Applies the `--port`, `--host` and `--log-level` overrides
+fn apply_cli_overrides(args: &Args, mut config: AppConfig) -> AppConfig {
+    if let Some(port) = args.port {
+        config.server.port = port;
+    }
+    if let Some(host) = &args.host {
+        config.server.host = host.clone();
+    }
+    if let Some(log_level) = &args.log_level {
+        config.logging.level = log_level.clone();
+    }
+    config
+}
+
+/// Installs the subscriber, returning the handle that swaps the log
+/// filter on configuration reload
+fn setup_logging(config: &LoggingConfig) -> anyhow::Result<LogFilterHandle> {
+    let filter =
+        config_reload::log_filter(&config.level).or_else(|_| config_reload::log_filter("info"))?;
+    let (filter, handle) = tracing_subscriber::reload::Layer::new(filter);
 
-    let registry = tracing_subscriber::registry()
-        .with(EnvFilter::from_default_env().add_directive(level.into()));
+    let registry = tracing_subscriber::registry().with(filter);
 
     match config.format.as_str() {
         "json" => {
@@ -338,57 +794,188 @@ fn setup_logging(config: &LoggingConfig) -> anyhow::Result<()> {
         }
     }
 
-    Ok(())
+    Ok(handle)
+}
+
+/// Logs the network restrictions, at startup and when reloaded
+fn log_network_config(network_config: Option<&NetworkConfig>) {
+    let Some(network_config) = network_config else {
+        tracing::info!("🛡️ Network IP restrictions not configured (disabled)");
+        return;
+    };
+
+    if network_config.enabled {
+        tracing::info!("🛡️ Network IP restrictions enabled");
+        tracing::info!("   Allowed CIDRs: {:?}", network_config.allowed_cidrs);
+        if let Some(denied) = &network_config.denied_cidrs {
+            tracing::info!("   Denied CIDRs: {:?}", denied);
+        }
+        for (name, policy) in &network_config.policies {
+            tracing::info!(
+                "   Policy {}: allowed {:?}, denied {:?}",
+                name,
+                policy.allowed_cidrs,
+                policy.denied_cidrs
+            );
+        }
+        let routes = &network_config.routes;
+        for (group, policy) in [
+            ("reads", &routes.reads),
+            ("writes", &routes.writes),
+            ("heartbeats", &routes.heartbeats),
+            ("cluster", &routes.cluster),
+        ] {
+            if let Some(policy) = policy {
+                tracing::info!("   {} routes: {} policy", group, policy);
+            }
+        }
+        tracing::info!("   Action: {}", network_config.deny_action);
+    } else {
+        tracing::info!("🛡️ Network IP restrictions disabled");
+    }
+    if network_config.trust_proxy_headers {
+        if network_config.trusted_proxies.is_empty() {
+            tracing::warn!(
+                "⚠️ trust_proxy_headers is set without trusted_proxies, forwarded headers are ignored"
+            );
+        } else {
+            tracing::info!("   Trusted proxies: {:?}", network_config.trusted_proxies);
+            tracing::info!("   Proxy header: {}", network_config.proxy_header);
+        }
+    }
 }
 
 fn api_routes() -> Router<AppState> {
+    let read = || axum::middleware::from_fn_with_state(RequiredScope::Read, require_scope);
+    let register = || axum::middleware::from_fn_with_state(RequiredScope::Register, require_scope);
+    let admin = || axum::middleware::from_fn_with_state(RequiredScope::Admin, require_scope);
+
     Router::new()
         .route(
             "/services",
-            get(api::list_services).post(api::register_service),
+            get(api::list_services)
+                .route_layer(read())
+                .merge(post(api::register_service).route_layer(register())),
         )
         .route(
             "/services/{name}",
-            get(api::get_service).delete(api::delete_service),
+            get(api::get_service)
+                .route_layer(read())
+                .merge(delete(api::delete_service).route_layer(admin())),
+        )
+        .route(
+            "/services/{name}/instances",
+            get(api::get_instances).route_layer(read()),
         )
-        .route("/services/{name}/instances", get(api::get_instances))
         .route(
             "/services/{name}/instances/{id}",
-            delete(api::deregister_instance),
+            delete(api::deregister_instance).route_layer(register()),
         )
         .route(
             "/services/{name}/instances/{id}/heartbeat",
-            post(api::heartbeat),
+            post(api::heartbeat).route_layer(register()),
         )
         .route(
             "/services/{name}/instances/{id}/status",
-            put(api::update_status),
+            put(api::update_status).route_layer(admin()),
+        )
+        .route(
+            "/services/{name}/instances/{id}/maintenance",
+            put(api::start_instance_maintenance)
+                .route_layer(admin())
+                .merge(delete(api::end_instance_maintenance).route_layer(admin())),
+        )
+        .route(
+            "/services/{name}/maintenance",
+            put(api::start_service_maintenance)
+                .route_layer(admin())
+                .merge(delete(api::end_service_maintenance).route_layer(admin())),
+        )
+        .route(
+            "/discovery/{name}",
+            get(api::discover_service).route_layer(read()),
         )
-        .route("/discovery/{name}", get(api::discover_service))
         .route(
             "/discovery/{name}/load-balance",
-            get(api::load_balance_service),
+            get(api::load_balance_service).route_layer(read()),
+        )
+        .route(
+            "/services/{name}/tags",
+            get(api::get_service_tags).route_layer(read()),
+        )
+        .route(
+            "/tags/{tag}/services",
+            get(api::get_services_by_tag).route_layer(read()),
+        )
+        .route("/events", get(api::get_events).route_layer(read()))
+        .route(
+            "/services/{name}/watch",
+            get(api::watch_service).route_layer(read()),
+        )
+        .route(
+            "/admin/webhooks",
+            get(api::webhook_deliveries).route_layer(admin()),
+        )
+        .route("/admin/audit", get(api::audit_entries).route_layer(admin()))
+        .route(
+            "/pki/issue",
+            post(api::issue_certificate).route_layer(register()),
         )
-        .route("/services/{name}/tags", get(api::get_service_tags))
-        .route("/tags/{tag}/services", get(api::get_services_by_tag))
-        .route("/events", get(api::get_events))
-        .route("/services/{name}/watch", get(api::watch_service))
 }
 
-async fn health_endpoint(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let stats = state.registry.get_stats().await;
-    Json(serde_json::json!({
-        "status": "UP",
-        "services": stats.total_services,
-        "instances": stats.total_instances,
-        "healthy_instances": stats.healthy_instances,
-        "timestamp": chrono::Utc::now()
-    }))
+async fn health_endpoint(State(state): State<AppState>) -> impl IntoResponse {
+    let stats = match state.registry.get_stats().await {
+        Ok(stats) => stats,
+        Err(e) => {
+            tracing::error!("❌ Registry unavailable: {}", e);
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({
+                    "status": "DOWN",
+                    "timestamp": chrono::Utc::now()
+                })),
+            );
+        }
+    };
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "status": "UP",
+            "services": stats.total_services,
+            "instances": stats.total_instances,
+            "healthy_instances": stats.healthy_instances,
+            "timestamp": chrono::Utc::now()
+        })),
+    )
 }
 
-async fn info_endpoint(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let stats = state.registry.get_stats().await;
-    Json(serde_json::json!({
+async fn info_endpoint(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let stats = state
+        .registry
+        .get_stats()
+        .await
+        .map_err(api::registry_error)?;
+    let cluster = state.cluster.as_ref().map(|node| {
+        let status = node.status();
+        serde_json::json!({
+            "node_id": status.node_id,
+            "role": status.role,
+            "term": status.term,
+            "leader_id": status.leader_id,
+            "members": status.members.len(),
+            "read_consistency": status.read_consistency
+        })
+    });
+    let config = state.config.load();
+    let tls = config
+        .tls
+        .as_ref()
+        .filter(|tls| tls.enabled)
+        .and_then(|tls| TlsPolicy::from_config(tls).ok())
+        .map(|policy| policy.describe());
+    Ok(Json(serde_json::json!({
         "name": "SquoutQuest Server",
         "version": env!("CARGO_PKG_VERSION"),
         "description": "Universal Service Discovery for microservices",
@@ -396,36 +983,48 @@ async fn info_endpoint(State(state): State<AppState>) -> Json<serde_json::Value>
         "services": stats.total_services,
         "instances": stats.total_instances,
         "healthy_instances": stats.healthy_instances,
+        "cluster": cluster,
         "config": {
             "server": {
-                "host": state.config.server.host,
-                "port": state.config.server.port,
-                "cors_enabled": state.config.server.enable_cors
+                "host": config.server.host,
+                "port": config.server.port,
+                "cors_enabled": config.server.enable_cors
             },
             "health_check": {
-                "interval_seconds": state.config.health_check.interval_seconds,
-                "timeout_seconds": state.config.health_check.timeout_seconds,
-                "max_failures": state.config.health_check.max_failures
-            }
+                "interval_seconds": config.health_check.interval_seconds,
+                "timeout_seconds": config.health_check.timeout_seconds,
+                "max_failures": config.health_check.max_failures
+            },
+            "tls": tls
         }
-    }))
+    })))
 }
 
-async fn metrics_endpoint(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let stats = state.registry.get_stats().await;
-    Json(serde_json::json!({
+async fn metrics_endpoint(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let stats = state
+        .registry
+        .get_stats()
+        .await
+        .map_err(api::registry_error)?;
+    Ok(Json(serde_json::json!({
         "registry": {
             "services": stats.total_services,
             "instances": stats.total_instances,
             "healthy": stats.healthy_instances,
             "unhealthy": stats.total_instances - stats.healthy_instances
         },
+        "rate_limit": state.rate_limiter.metrics(),
+        "tls": {
+            "certificate_reloads": state.certificate_reloads.metrics()
+        },
         "system": {
             "uptime_seconds": chrono::Utc::now().timestamp() - stats.start_time,
             "memory_usage": "TODO",
             "cpu_usage": "TODO"
         }
-    }))
+    })))
 }
 
 async fn dashboard() -> axum::response::Html<&'static str> {
@@ -480,6 +1079,7 @@ async fn dashboard() -> axum::response::Html<&'static str> {
         .btn:hover { background: #5a6fd8; transform: translateY(-1px); }
 
         .service-grid { display: grid; grid-template-columns: repeat(auto-fill, minmax(350px, 1fr)); gap: 20px; }
+        .maintenance { background: #fff4e5; border-left: 4px solid #f0a020; padding: 8px 12px; margin: 8px 0; border-radius: 4px; font-size: 0.9em; }
         .loading { text-align: center; padding: 40px; color: #666; animation: pulse 2s infinite; }
 
         @keyframes pulse {
@@ -521,6 +1121,20 @@ async fn dashboard() -> axum::response::Html<&'static str> {
     </div>
 
     <script>
+        function escapeHtml(text) {
+            const div = document.createElement('div');
+            div.textContent = text;
+            return div.innerHTML;
+        }
+
+        function maintenanceHtml(label, maintenance) {
+            const expiry = maintenance.expires_at
+                ? `until ${new Date(maintenance.expires_at).toLocaleString()}`
+                : 'until ended';
+            return `<div class="maintenance">🔧 ${escapeHtml(label)}: ${escapeHtml(maintenance.reason)}
+                (${escapeHtml(maintenance.author)}, ${expiry})</div>`;
+        }
+
         async function loadData() {
             try {
                 const healthResponse = await fetch('/health');
@@ -545,7 +1159,7 @@ async fn dashboard() -> axum::response::Html<&'static str> {
                     </div>
                 `;
 
-                const servicesResponse = await fetch('/api/v1/services');
+                const servicesResponse = await fetch('/api/services');
                 const services = await servicesResponse.json();
 
                 if (services.length === 0) {
@@ -554,9 +1168,14 @@ async fn dashboard() -> axum::response::Html<&'static str> {
                 } else {
                     const servicesHtml = services.map(service => `
                         <div class="card">
-                            <h3>${service.name}</h3>
+                            <h3>${escapeHtml(service.name)}</h3>
+                            ${service.maintenance ? maintenanceHtml('Service in maintenance', service.maintenance) : ''}
                             <p>Instances: ${service.instances.length}</p>
-                            <p>Tags: ${service.tags.join(', ')}</p>
+                            <p>Tags: ${escapeHtml(service.tags.join(', '))}</p>
+                            ${service.instances
+                                .filter(instance => instance.maintenance && !instance.maintenance.service_wide)
+                                .map(instance => maintenanceHtml(`${instance.host}:${instance.port}`, instance.maintenance))
+                                .join('')}
                         </div>
                     `).join('');
 
@@ -591,10 +1210,3 @@ async fn dashboard() -> axum::response::Html<&'static str> {
     "#,
     )
 }
-
-async fn websocket_handler() -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "message": "WebSocket endpoint for real-time updates",
-        "status": "coming_soon"
-    }))
-}
@@ -0,0 +1,518 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::jwt::JwtValidator;
+use super::scopes::Scope;
+use crate::tls::ClientIdentity;
+use crate::SecurityConfig;
+
+/// Header accepted as an alternative to `Authorization: Bearer`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// A named API key, read from exactly one of `key`, `key_file` or `key_env`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ApiKeyConfig {
+    /// Name reported in logs and the audit trail, never the key itself
+    pub id: String,
+    pub key: Option<String>,
+    /// File holding the key, surrounding whitespace is ignored
+    pub key_file: Option<String>,
+    /// Environment variable holding the key
+    pub key_env: Option<String>,
+    /// Permissions of the key, `admin` when empty
+    pub scopes: Vec<Scope>,
+}
+
+/// Client certificate identity granted scopes, matched against the URI
+/// subject alternative names of the verified certificate only
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClientCertConfig {
+    /// URI SAN of the certificate, such as a SPIFFE ID. Common names and
+    /// DNS names never match.
+    pub name: String,
+    /// Permissions of the certificate, `admin` when empty
+    pub scopes: Vec<Scope>,
+}
+
+/// API key a request was authenticated with, available to handlers as a
+/// request extension
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey {
+    pub id: String,
+    pub scopes: Vec<Scope>,
+}
+
+struct ApiKey {
+    id: String,
+    secret: String,
+    scopes: Vec<Scope>,
+}
+
+pub struct ApiKeyAuth {
+    enabled: bool,
+    keys: Vec<ApiKey>,
+    /// Accepts bearer JWTs when `[security.jwt]` is configured
+    jwt: Option<Arc<JwtValidator>>,
+    client_certs: Vec<ClientCertConfig>,
+    exempt_paths: Vec<String>,
+}
+
+impl ApiKeyConfig {
+    fn load(&self) -> anyhow::Result<ApiKey> {
+        if self.id.is_empty() {
+            return Err(anyhow::anyhow!("API key id cannot be empty"));
+        }
+
+        let secret = match (&self.key, &self.key_file, &self.key_env) {
+            (Some(key), None, None) => key.clone(),
+            (None, Some(path), None) => std::fs::read_to_string(path)
+                .map_err(|e| {
+                    anyhow::anyhow!("Failed to read API key {} from {}: {}", self.id, path, e)
+                })?
+                .trim()
+                .to_string(),
+            (None, None, Some(variable)) => std::env::var(variable).map_err(|_| {
+                anyhow::anyhow!(
+                    "Environment variable {} for API key {} is not set",
+                    variable,
+                    self.id
+                )
+            })?,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "API key {} needs exactly one of key, key_file or key_env",
+                    self.id
+                ))
+            }
+        };
+
+        if secret.is_empty() {
+            return Err(anyhow::anyhow!("API key {} is empty", self.id));
+        }
+
+        let scopes = if self.scopes.is_empty() {
+            vec![Scope::Admin]
+        } else {
+            self.scopes.clone()
+        };
+
+        Ok(ApiKey {
+            id: self.id.clone(),
+            secret,
+            scopes,
+        })
+    }
+}
+
+impl ApiKeyAuth {
+    pub fn new(config: &SecurityConfig) -> anyhow::Result<Self> {
+        let mut keys = config
+            .api_keys
+            .iter()
+            .map(ApiKeyConfig::load)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // Single key from the original configuration format
+        if let Some(secret) = config.api_key.as_ref().filter(|key| !key.is_empty()) {
+            keys.push(ApiKey {
+                id: "default".to_string(),
+                secret: secret.clone(),
+                scopes: vec![Scope::Admin],
+            });
+        }
+
+        for (index, key) in keys.iter().enumerate() {
+            if keys[..index].iter().any(|other| other.id == key.id) {
+                return Err(anyhow::anyhow!("Duplicate API key id: {}", key.id));
+            }
+        }
+
+        let jwt = match &config.jwt {
+            Some(jwt) => Some(Arc::new(JwtValidator::new(jwt)?)),
+            None => None,
+        };
+
+        if let Some(cert) = config.client_certs.iter().find(|cert| cert.name.is_empty()) {
+            return Err(anyhow::anyhow!(
+                "Client certificate name cannot be empty (scopes: {:?})",
+                cert.scopes
+            ));
+        }
+
+        if config.enable_auth && keys.is_empty() && jwt.is_none() && config.client_certs.is_empty()
+        {
+            return Err(anyhow::anyhow!(
+                "Authentication is enabled but no API key, JWT issuer or client certificate is configured"
+            ));
+        }
+
+        Ok(Self {
+            enabled: config.enable_auth,
+            keys,
+            jwt,
+            client_certs: config.client_certs.clone(),
+            exempt_paths: config.exempt_paths.clone(),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Key ids with their scopes, for the startup log
+    pub fn describe_keys(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .map(|key| {
+                let scopes: Vec<String> = key.scopes.iter().map(Scope::to_string).collect();
+                format!("{} ({})", key.id, scopes.join(", "))
+            })
+            .collect()
+    }
+
+    pub fn accepts_jwt(&self) -> bool {
+        self.jwt.is_some()
+    }
+
+    /// Picks up JWKS key rotations
+    pub fn start_key_reload(&self) {
+        if let Some(jwt) = &self.jwt {
+            jwt.start_watching();
+        }
+    }
+
+    /// Secret of the key named `id`, used by this server to call its peers
+    pub fn secret(&self, id: &str) -> Option<String> {
+        self.keys
+            .iter()
+            .find(|key| key.id == id)
+            .map(|key| key.secret.clone())
+    }
+
+    /// Exempt paths match exactly, or by prefix when they end with `*`
+    fn is_exempt(&self, path: &str) -> bool {
+        self.exempt_paths
+            .iter()
+            .any(|exempt| match exempt.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == exempt,
+            })
+    }
+
+    /// Client certificate identities with their scopes, for the startup log
+    pub fn describe_client_certs(&self) -> Vec<String> {
+        self.client_certs
+            .iter()
+            .map(|cert| {
+                let scopes: Vec<String> = cert_scopes(cert).iter().map(Scope::to_string).collect();
+                format!("{} ({})", cert.name, scopes.join(", "))
+            })
+            .collect()
+    }
+
+    fn authenticate(
+        &self,
+        headers: &HeaderMap,
+        identity: Option<&ClientIdentity>,
+    ) -> Result<AuthenticatedKey, String> {
+        let bearer = bearer_token(headers);
+
+        // JWTs have three dot-separated segments; an API key may look the
+        // same, so keys are still tried when the token is not a valid JWT
+        let jwt_error = match (&self.jwt, bearer) {
+            (Some(jwt), Some(token)) if token.split('.').count() == 3 => {
+                match jwt.validate(token.trim()) {
+                    Ok(key) => return Ok(key),
+                    Err(e) => Some(e),
+                }
+            }
+            _ => None,
+        };
+
+        let presented = bearer.or_else(|| {
+            headers
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+        });
+
+        // A verified client certificate stands in for a missing key
+        let Some(presented) = presented else {
+            return identity
+                .and_then(|identity| self.client_cert(identity))
+                .ok_or_else(|| "Missing API key".to_string());
+        };
+
+        self.keys
+            .iter()
+            .find(|key| constant_time_eq(key.secret.as_bytes(), presented.trim().as_bytes()))
+            .map(|key| AuthenticatedKey {
+                id: key.id.clone(),
+                scopes: key.scopes.clone(),
+            })
+            .ok_or_else(|| jwt_error.unwrap_or_else(|| "Invalid API key".to_string()))
+    }
+
+    fn client_cert(&self, identity: &ClientIdentity) -> Option<AuthenticatedKey> {
+        let cert = self
+            .client_certs
+            .iter()
+            .find(|cert| identity.uris.contains(&cert.name))?;
+
+        Some(AuthenticatedKey {
+            id: format!("cert:{}", cert.name),
+            scopes: cert_scopes(cert),
+        })
+    }
+}
+
+fn cert_scopes(cert: &ClientCertConfig) -> Vec<Scope> {
+    if cert.scopes.is_empty() {
+        vec![Scope::Admin]
+    } else {
+        cert.scopes.clone()
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then_some(token)
+}
+
+/// Compares without short-circuiting, so response times don't leak how much
+/// of a key was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+pub async fn auth_layer(
+    State(auth): State<Arc<ApiKeyAuth>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if !auth.enabled || auth.is_exempt(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    match auth.authenticate(req.headers(), req.extensions().get::<ClientIdentity>()) {
+        Ok(key) => {
+            tracing::debug!("Request authenticated as {}", key.id);
+            req.extensions_mut().insert(key);
+            next.run(req).await
+        }
+        Err(reason) => {
+            tracing::warn!(
+                "Rejected unauthenticated request to {}: {}",
+                req.uri().path(),
+                reason
+            );
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                reason,
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use tempfile::TempDir;
+
+    fn security_config(api_keys: Vec<ApiKeyConfig>) -> SecurityConfig {
+        SecurityConfig {
+            enable_auth: true,
+            api_key: None,
+            api_keys,
+            jwt: None,
+            client_certs: vec![],
+            exempt_paths: vec!["/health".to_string(), "/metrics".to_string()],
+            rate_limit_per_minute: 1000,
+            rate_limits: Default::default(),
+        }
+    }
+
+    fn inline_key(id: &str, key: &str) -> ApiKeyConfig {
+        ApiKeyConfig {
+            id: id.to_string(),
+            key: Some(key.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_bearer_and_api_key_headers() {
+        let auth = ApiKeyAuth::new(&security_config(vec![
+            inline_key("deployer", "deploy-secret"),
+            inline_key("ops", "ops-secret"),
+        ]))
+        .unwrap();
+
+        let key = auth
+            .authenticate(&headers(header::AUTHORIZATION, "Bearer ops-secret"), None)
+            .unwrap();
+        assert_eq!(key.id, "ops");
+
+        let api_key = header::HeaderName::from_static(API_KEY_HEADER);
+        let key = auth
+            .authenticate(&headers(api_key.clone(), "deploy-secret"), None)
+            .unwrap();
+        assert_eq!(key.id, "deployer");
+
+        assert_eq!(
+            auth.authenticate(&headers(api_key, "nope"), None)
+                .err()
+                .as_deref(),
+            Some("Invalid API key")
+        );
+        assert_eq!(
+            auth.authenticate(&headers(header::AUTHORIZATION, "Basic ops-secret"), None)
+                .err()
+                .as_deref(),
+            Some("Missing API key")
+        );
+    }
+
+    #[test]
+    fn test_dotted_api_key_accepted_with_jwt() {
+        let dir = TempDir::new().unwrap();
+        let jwks_path = dir.path().join("jwks.json");
+        std::fs::write(&jwks_path, r#"{"keys": []}"#).unwrap();
+
+        let mut config = security_config(vec![inline_key("ops", "sq.ops.secret")]);
+        config.jwt = Some(crate::middleware::jwt::JwtConfig {
+            jwks_path: Some(jwks_path.to_string_lossy().to_string()),
+            ..Default::default()
+        });
+        let auth = ApiKeyAuth::new(&config).unwrap();
+
+        let key = auth
+            .authenticate(
+                &headers(header::AUTHORIZATION, "Bearer sq.ops.secret"),
+                None,
+            )
+            .unwrap();
+        assert_eq!(key.id, "ops");
+
+        // Neither a key nor a valid JWT: the JWT error is reported
+        let error = auth
+            .authenticate(&headers(header::AUTHORIZATION, "Bearer a.b.c"), None)
+            .unwrap_err();
+        assert_ne!(error, "Invalid API key");
+    }
+
+    #[test]
+    fn test_keys_loaded_from_file_and_environment() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("ci.key");
+        std::fs::write(&path, "file-secret\n").unwrap();
+        std::env::set_var("SCOUTQUEST_TEST_AUTH_KEY", "env-secret");
+
+        let auth = ApiKeyAuth::new(&security_config(vec![
+            ApiKeyConfig {
+                id: "ci".to_string(),
+                key_file: Some(path.to_string_lossy().to_string()),
+                ..Default::default()
+            },
+            ApiKeyConfig {
+                id: "ops".to_string(),
+                key_env: Some("SCOUTQUEST_TEST_AUTH_KEY".to_string()),
+                ..Default::default()
+            },
+        ]))
+        .unwrap();
+
+        assert_eq!(auth.secret("ci").as_deref(), Some("file-secret"));
+        assert_eq!(auth.secret("ops").as_deref(), Some("env-secret"));
+    }
+
+    #[test]
+    fn test_invalid_key_configurations() {
+        assert!(ApiKeyAuth::new(&security_config(vec![])).is_err());
+        assert!(ApiKeyAuth::new(&security_config(vec![
+            inline_key("ops", "a"),
+            inline_key("ops", "b")
+        ]))
+        .is_err());
+        assert!(ApiKeyAuth::new(&security_config(vec![ApiKeyConfig {
+            id: "ops".to_string(),
+            key: Some("a".to_string()),
+            key_env: Some("PATH".to_string()),
+            ..Default::default()
+        }]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_client_certificate_identities() {
+        let orders = "spiffe://scoutquest.internal/service/orders";
+        let mut config = security_config(vec![inline_key("ops", "secret")]);
+        config.client_certs = vec![ClientCertConfig {
+            name: orders.to_string(),
+            scopes: vec!["register:orders".parse().unwrap()],
+        }];
+        let auth = ApiKeyAuth::new(&config).unwrap();
+
+        let identity = ClientIdentity {
+            common_name: Some("orders".to_string()),
+            sans: vec!["orders.scoutquest.internal".to_string(), orders.to_string()],
+            uris: vec![orders.to_string()],
+        };
+        let key = auth
+            .authenticate(&HeaderMap::new(), Some(&identity))
+            .unwrap();
+        assert_eq!(key.id, format!("cert:{}", orders));
+        assert!(!key.has_scope(&Scope::Read));
+
+        // An explicit key takes precedence over the certificate
+        let key = auth
+            .authenticate(
+                &headers(header::AUTHORIZATION, "Bearer secret"),
+                Some(&identity),
+            )
+            .unwrap();
+        assert_eq!(key.id, "ops");
+
+        // Only URI SANs are matched, not the common name or DNS names
+        let lookalike = ClientIdentity {
+            common_name: Some(orders.to_string()),
+            sans: vec![orders.to_string()],
+            uris: vec![],
+        };
+        assert!(auth
+            .authenticate(&HeaderMap::new(), Some(&lookalike))
+            .is_err());
+    }
+
+    #[test]
+    fn test_exempt_paths() {
+        let mut config = security_config(vec![inline_key("ops", "secret")]);
+        config.exempt_paths.push("/public/*".to_string());
+        let auth = ApiKeyAuth::new(&config).unwrap();
+
+        assert!(auth.is_exempt("/health"));
+        assert!(auth.is_exempt("/public/docs"));
+        assert!(!auth.is_exempt("/health/details"));
+        assert!(!auth.is_exempt("/api/services"));
+    }
+}
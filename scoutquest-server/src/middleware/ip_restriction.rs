@@ -1,27 +1,153 @@
 use axum::{
     extract::{ConnectInfo, Request, State},
-    http::{HeaderMap, StatusCode},
+    http::{HeaderMap, Method, StatusCode},
     middleware::Next,
     response::Response,
 };
 use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     net::{IpAddr, SocketAddr},
     str::FromStr,
     sync::Arc,
 };
 
+use super::rate_limit::RouteClass;
+use crate::config_reload::Live;
 use crate::NetworkConfig;
 
+/// Named set of allowed and denied CIDR ranges
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct NetworkPolicyConfig {
+    pub allowed_cidrs: Vec<String>,
+    pub denied_cidrs: Vec<String>,
+}
+
+/// Policy names bound to route groups. Unbound groups use the top-level
+/// `allowed_cidrs` and `denied_cidrs`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct NetworkRoutesConfig {
+    /// Service listings, discovery, event streams and status pages
+    pub reads: Option<String>,
+    /// Registrations, deregistrations, deletions and status changes
+    pub writes: Option<String>,
+    pub heartbeats: Option<String>,
+    /// Replication between cluster nodes
+    pub cluster: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RouteGroup {
+    Reads,
+    Writes,
+    Heartbeats,
+    Cluster,
+}
+
+impl RouteGroup {
+    fn of(method: &Method, path: &str) -> Self {
+        match RouteClass::of(method, path) {
+            None => RouteGroup::Cluster,
+            Some(RouteClass::Discovery) => RouteGroup::Reads,
+            Some(RouteClass::Writes) => RouteGroup::Writes,
+            Some(RouteClass::Heartbeats) => RouteGroup::Heartbeats,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct IpRestrictionMiddleware {
-    enabled: bool,
+struct CidrPolicy {
+    name: String,
     allowed_cidrs: Vec<IpNet>,
     denied_cidrs: Vec<IpNet>,
+}
+
+impl CidrPolicy {
+    fn parse(name: &str, allowed: &[String], denied: &[String]) -> anyhow::Result<Self> {
+        let allowed_cidrs = parse_cidrs(allowed)
+            .map_err(|e| anyhow::anyhow!("Invalid CIDR in {} allowed_cidrs: {}", name, e))?;
+        let denied_cidrs = parse_cidrs(denied)
+            .map_err(|e| anyhow::anyhow!("Invalid CIDR in {} denied_cidrs: {}", name, e))?;
+
+        if allowed_cidrs.is_empty() {
+            return Err(anyhow::anyhow!(
+                "allowed_cidrs of {} cannot be empty when network restrictions are enabled",
+                name
+            ));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            allowed_cidrs,
+            denied_cidrs,
+        })
+    }
+
+    fn allows(&self, ip: IpAddr) -> bool {
+        // First check denied CIDRs (blacklist has priority)
+        for denied_cidr in &self.denied_cidrs {
+            if denied_cidr.contains(&ip) {
+                tracing::warn!(
+                    "IP {} is explicitly denied by CIDR {} of {}",
+                    ip,
+                    denied_cidr,
+                    self.name
+                );
+                return false;
+            }
+        }
+
+        // Then check allowed CIDRs (whitelist)
+        for allowed_cidr in &self.allowed_cidrs {
+            if allowed_cidr.contains(&ip) {
+                tracing::debug!(
+                    "IP {} is allowed by CIDR {} of {}",
+                    ip,
+                    allowed_cidr,
+                    self.name
+                );
+                return true;
+            }
+        }
+
+        tracing::warn!(
+            "IP {} is not in any allowed CIDR range of {}",
+            ip,
+            self.name
+        );
+        false
+    }
+}
+
+fn parse_cidrs(cidrs: &[String]) -> Result<Vec<IpNet>, ipnet::AddrParseError> {
+    cidrs.iter().map(|s| s.parse::<IpNet>()).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct IpRestrictionMiddleware {
+    enabled: bool,
+    /// Policy of each route group, indexed by [`RouteGroup`]
+    route_policies: [Arc<CidrPolicy>; 4],
     deny_action: DenyAction,
     trust_proxy_headers: bool,
+    /// Peers whose forwarded headers are believed
+    trusted_proxies: Vec<IpNet>,
+    /// The only forwarded header read from trusted proxies
+    proxy_header: ProxyHeader,
 }
 
+/// Client address resolved by the middleware (proxy headers included),
+/// available to handlers as a request extension
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
 #[derive(Debug, Clone)]
 pub enum DenyAction {
     Reject,
@@ -40,137 +166,215 @@ impl FromStr for DenyAction {
     }
 }
 
+/// Forwarded header set by the trusted proxies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyHeader {
+    /// RFC 7239 `Forwarded`
+    Forwarded,
+    XForwardedFor,
+    XRealIp,
+}
+
+impl FromStr for ProxyHeader {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "forwarded" => Ok(ProxyHeader::Forwarded),
+            "x-forwarded-for" => Ok(ProxyHeader::XForwardedFor),
+            "x-real-ip" => Ok(ProxyHeader::XRealIp),
+            _ => Err(anyhow::anyhow!("Invalid proxy_header: {}", s)),
+        }
+    }
+}
+
 impl IpRestrictionMiddleware {
     pub fn new(config: &NetworkConfig) -> anyhow::Result<Self> {
+        let trusted_proxies = parse_cidrs(&config.trusted_proxies)
+            .map_err(|e| anyhow::anyhow!("Invalid CIDR in trusted_proxies: {}", e))?;
+        let proxy_header = config.proxy_header.parse()?;
+
         if !config.enabled {
-            return Ok(Self {
-                enabled: false,
+            let default_policy = Arc::new(CidrPolicy {
+                name: "default".to_string(),
                 allowed_cidrs: vec![],
                 denied_cidrs: vec![],
+            });
+            return Ok(Self {
+                enabled: false,
+                route_policies: std::array::from_fn(|_| default_policy.clone()),
                 deny_action: DenyAction::Reject,
                 trust_proxy_headers: config.trust_proxy_headers,
+                trusted_proxies,
+                proxy_header,
             });
         }
 
-        let allowed_cidrs: Result<Vec<_>, _> = config
-            .allowed_cidrs
+        let default_policy = Arc::new(CidrPolicy::parse(
+            "default",
+            &config.allowed_cidrs,
+            config.denied_cidrs.as_deref().unwrap_or_default(),
+        )?);
+
+        let policies = config
+            .policies
             .iter()
-            .map(|s| s.parse::<IpNet>())
-            .collect();
-
-        let allowed_cidrs =
-            allowed_cidrs.map_err(|e| anyhow::anyhow!("Invalid CIDR in allowed_cidrs: {}", e))?;
-
-        let denied_cidrs = if let Some(denied) = &config.denied_cidrs {
-            let denied_cidrs: Result<Vec<_>, _> =
-                denied.iter().map(|s| s.parse::<IpNet>()).collect();
-            denied_cidrs.map_err(|e| anyhow::anyhow!("Invalid CIDR in denied_cidrs: {}", e))?
-        } else {
-            vec![]
-        };
+            .map(|(name, policy)| {
+                CidrPolicy::parse(name, &policy.allowed_cidrs, &policy.denied_cidrs)
+                    .map(|policy| (name.as_str(), Arc::new(policy)))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+
+        let routes = &config.routes;
+        let bindings = [
+            &routes.reads,
+            &routes.writes,
+            &routes.heartbeats,
+            &routes.cluster,
+        ];
+        let mut route_policies: [Arc<CidrPolicy>; 4] =
+            std::array::from_fn(|_| default_policy.clone());
+        for (policy, name) in route_policies.iter_mut().zip(bindings) {
+            if let Some(name) = name {
+                *policy = policies
+                    .get(name.as_str())
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Unknown network policy: {}", name))?;
+            }
+        }
 
         let deny_action = config.deny_action.parse()?;
 
-        if allowed_cidrs.is_empty() {
-            return Err(anyhow::anyhow!(
-                "allowed_cidrs cannot be empty when network restrictions are enabled"
-            ));
-        }
-
         Ok(Self {
             enabled: true,
-            allowed_cidrs,
-            denied_cidrs,
+            route_policies,
             deny_action,
             trust_proxy_headers: config.trust_proxy_headers,
+            trusted_proxies,
+            proxy_header,
         })
     }
 
-    fn extract_client_ip(
-        &self,
-        headers: &HeaderMap,
-        connect_info: &ConnectInfo<SocketAddr>,
-    ) -> IpAddr {
-        if self.trust_proxy_headers {
-            // Try X-Forwarded-For first
-            if let Some(xff) = headers.get("x-forwarded-for") {
-                if let Ok(xff_str) = xff.to_str() {
-                    // Take the first IP in the chain (original client)
-                    if let Some(first_ip) = xff_str.split(',').next() {
-                        if let Ok(ip) = first_ip.trim().parse::<IpAddr>() {
-                            tracing::debug!("Using X-Forwarded-For IP: {}", ip);
-                            return ip;
-                        }
-                    }
-                }
-            }
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|cidr| cidr.contains(&ip))
+    }
+
+    /// Resolves the client address. Forwarded headers are only believed
+    /// when sent by a trusted proxy, and the chain is walked from the
+    /// nearest hop until an address that is not a trusted proxy, so
+    /// clients can't prepend addresses of their choice.
+    fn extract_client_ip(&self, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+        if !self.trust_proxy_headers || !self.is_trusted_proxy(peer) {
+            tracing::debug!("Using connection IP: {}", peer);
+            return peer;
+        }
 
-            // Try X-Real-IP
-            if let Some(real_ip) = headers.get("x-real-ip") {
-                if let Ok(real_ip_str) = real_ip.to_str() {
-                    if let Ok(ip) = real_ip_str.parse::<IpAddr>() {
-                        tracing::debug!("Using X-Real-IP: {}", ip);
-                        return ip;
+        let mut client = peer;
+        for hop in forwarded_chain(headers, self.proxy_header)
+            .into_iter()
+            .rev()
+        {
+            match hop {
+                Some(ip) => {
+                    client = ip;
+                    if !self.is_trusted_proxy(ip) {
+                        break;
                     }
                 }
+                // Unknown or obfuscated hop, the last known address is used
+                None => break,
             }
         }
 
-        // Fallback to connection info
-        let ip = connect_info.0.ip();
-        tracing::debug!("Using connection IP: {}", ip);
-        ip
+        tracing::debug!("Using forwarded client IP: {} (proxy {})", client, peer);
+        client
     }
 
-    fn is_ip_allowed(&self, ip: IpAddr) -> bool {
-        // First check denied CIDRs (blacklist has priority)
-        for denied_cidr in &self.denied_cidrs {
-            if denied_cidr.contains(&ip) {
-                tracing::warn!("IP {} is explicitly denied by CIDR {}", ip, denied_cidr);
-                return false;
-            }
-        }
+    fn policy_for(&self, method: &Method, path: &str) -> &CidrPolicy {
+        &self.route_policies[RouteGroup::of(method, path).index()]
+    }
+}
 
-        // Then check allowed CIDRs (whitelist)
-        for allowed_cidr in &self.allowed_cidrs {
-            if allowed_cidr.contains(&ip) {
-                tracing::debug!("IP {} is allowed by CIDR {}", ip, allowed_cidr);
-                return true;
-            }
-        }
+/// Client addresses of the proxy header, the original client first. Only
+/// the configured header is read: the others may come straight from the
+/// client, passed through untouched by the proxy.
+fn forwarded_chain(headers: &HeaderMap, header: ProxyHeader) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| -> Vec<String> {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(str::to_string)
+            .collect()
+    };
 
-        tracing::warn!("IP {} is not in any allowed CIDR range", ip);
-        false
+    match header {
+        ProxyHeader::Forwarded => values("forwarded")
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect(),
+        ProxyHeader::XForwardedFor => values("x-forwarded-for")
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(parse_node)
+            .collect(),
+        ProxyHeader::XRealIp => values("x-real-ip")
+            .iter()
+            .map(|ip| parse_node(ip))
+            .collect(),
+    }
+}
+
+/// Address of a forwarded node: a bare IP, a quoted IPv6 in brackets, or
+/// either with a port. `unknown` and obfuscated identifiers yield `None`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
     }
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
 }
 
 pub async fn ip_restriction_layer(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    State(restriction): State<Arc<IpRestrictionMiddleware>>,
-    req: Request,
+    State(restriction): State<Arc<Live<IpRestrictionMiddleware>>>,
+    mut req: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
+    let restriction = restriction.load();
+    let client_ip = restriction.extract_client_ip(req.headers(), addr.ip());
+    req.extensions_mut().insert(ClientIp(client_ip));
+
     // Skip if middleware is disabled
     if !restriction.enabled {
         return Ok(next.run(req).await);
     }
 
-    let headers = req.headers();
-    let client_ip = restriction.extract_client_ip(headers, &ConnectInfo(addr));
-
-    if !restriction.is_ip_allowed(client_ip) {
+    let policy = restriction.policy_for(req.method(), req.uri().path());
+    if !policy.allows(client_ip) {
         match restriction.deny_action {
             DenyAction::Reject => {
                 tracing::warn!(
-                    "Access denied for IP {} - returning 403 Forbidden",
-                    client_ip
+                    "Access denied for IP {} by {} policy - returning 403 Forbidden",
+                    client_ip,
+                    policy.name
                 );
                 return Err(StatusCode::FORBIDDEN);
             }
             DenyAction::LogOnly => {
                 tracing::warn!(
-                    "Access would be denied for IP {} (log_only mode - allowing request)",
-                    client_ip
+                    "Access would be denied for IP {} by {} policy (log_only mode - allowing request)",
+                    client_ip,
+                    policy.name
                 );
                 // Continue with the request in log-only mode
             }
@@ -186,6 +390,13 @@ pub async fn ip_restriction_layer(
 mod tests {
     use super::*;
 
+    impl IpRestrictionMiddleware {
+        /// Checks `ip` against the policy of the routes without a binding
+        fn is_ip_allowed(&self, ip: IpAddr) -> bool {
+            self.policy_for(&Method::GET, "/health").allows(ip)
+        }
+    }
+
     #[test]
     fn test_ipv4_cidr_matching() {
         let config = NetworkConfig {
@@ -194,6 +405,10 @@ mod tests {
             denied_cidrs: None,
             deny_action: "reject".to_string(),
             trust_proxy_headers: true,
+            trusted_proxies: vec![],
+            proxy_header: "x-forwarded-for".to_string(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
         };
 
         let middleware = IpRestrictionMiddleware::new(&config).unwrap();
@@ -217,6 +432,10 @@ mod tests {
             denied_cidrs: None,
             deny_action: "reject".to_string(),
             trust_proxy_headers: true,
+            trusted_proxies: vec![],
+            proxy_header: "x-forwarded-for".to_string(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
         };
 
         let middleware = IpRestrictionMiddleware::new(&config).unwrap();
@@ -238,6 +457,10 @@ mod tests {
             denied_cidrs: Some(vec!["10.42.0.0/16".to_string()]),
             deny_action: "reject".to_string(),
             trust_proxy_headers: true,
+            trusted_proxies: vec![],
+            proxy_header: "x-forwarded-for".to_string(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
         };
 
         let middleware = IpRestrictionMiddleware::new(&config).unwrap();
@@ -259,12 +482,16 @@ mod tests {
             denied_cidrs: None,
             deny_action: "log_only".to_string(),
             trust_proxy_headers: true,
+            trusted_proxies: vec![],
+            proxy_header: "x-forwarded-for".to_string(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
         };
 
         let middleware = IpRestrictionMiddleware::new(&config).unwrap();
 
         match middleware.deny_action {
-            DenyAction::LogOnly => assert!(true),
+            DenyAction::LogOnly => {}
             DenyAction::Reject => panic!("Expected LogOnly mode"),
         }
     }
@@ -277,6 +504,10 @@ mod tests {
             denied_cidrs: None,
             deny_action: "reject".to_string(),
             trust_proxy_headers: true,
+            trusted_proxies: vec![],
+            proxy_header: "x-forwarded-for".to_string(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
         };
 
         let middleware = IpRestrictionMiddleware::new(&config).unwrap();
@@ -291,6 +522,10 @@ mod tests {
             denied_cidrs: None,
             deny_action: "reject".to_string(),
             trust_proxy_headers: true,
+            trusted_proxies: vec![],
+            proxy_header: "x-forwarded-for".to_string(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
         };
 
         assert!(IpRestrictionMiddleware::new(&config).is_err());
@@ -304,8 +539,158 @@ mod tests {
             denied_cidrs: None,
             deny_action: "reject".to_string(),
             trust_proxy_headers: true,
+            trusted_proxies: vec![],
+            proxy_header: "x-forwarded-for".to_string(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
         };
 
         assert!(IpRestrictionMiddleware::new(&config).is_err());
     }
+
+    fn network_config(allowed_cidrs: &[&str]) -> NetworkConfig {
+        NetworkConfig {
+            enabled: true,
+            allowed_cidrs: allowed_cidrs.iter().map(|c| c.to_string()).collect(),
+            denied_cidrs: None,
+            deny_action: "reject".to_string(),
+            trust_proxy_headers: true,
+            trusted_proxies: vec!["10.0.0.0/24".to_string()],
+            proxy_header: "x-forwarded-for".to_string(),
+            policies: HashMap::new(),
+            routes: NetworkRoutesConfig::default(),
+        }
+    }
+
+    #[test]
+    fn test_route_policies() {
+        let mut config = network_config(&["10.0.0.0/8"]);
+        config.policies.insert(
+            "nodes".to_string(),
+            NetworkPolicyConfig {
+                allowed_cidrs: vec!["10.1.0.0/16".to_string()],
+                denied_cidrs: vec![],
+            },
+        );
+        config.routes.writes = Some("nodes".to_string());
+        config.routes.heartbeats = Some("nodes".to_string());
+        let middleware = IpRestrictionMiddleware::new(&config).unwrap();
+
+        let pod: IpAddr = "10.2.3.4".parse().unwrap();
+        let node: IpAddr = "10.1.0.9".parse().unwrap();
+        let read = middleware.policy_for(&Method::GET, "/api/discovery/users");
+        let write = middleware.policy_for(&Method::POST, "/api/services");
+        assert!(read.allows(pod) && read.allows(node));
+        assert!(!write.allows(pod) && write.allows(node));
+        assert_eq!(
+            middleware
+                .policy_for(&Method::POST, "/api/services/users/instances/1/heartbeat")
+                .name,
+            "nodes"
+        );
+        assert_eq!(
+            middleware.policy_for(&Method::POST, "/cluster/append").name,
+            "default"
+        );
+
+        config.routes.reads = Some("missing".to_string());
+        assert!(IpRestrictionMiddleware::new(&config).is_err());
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_forwarded_headers_from_trusted_proxies_only() {
+        let middleware = IpRestrictionMiddleware::new(&network_config(&["0.0.0.0/0"])).unwrap();
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let stranger: IpAddr = "203.0.113.9".parse().unwrap();
+        let spoofed = headers(&[("x-forwarded-for", "10.1.2.3")]);
+
+        // Headers of untrusted peers are ignored
+        assert_eq!(middleware.extract_client_ip(&spoofed, stranger), stranger);
+        assert_eq!(
+            middleware.extract_client_ip(&spoofed, proxy),
+            "10.1.2.3".parse::<IpAddr>().unwrap()
+        );
+
+        // Addresses prepended by the client are not believed
+        let chain = headers(&[("x-forwarded-for", "10.1.2.3, 198.51.100.7, 10.0.0.3")]);
+        assert_eq!(
+            middleware.extract_client_ip(&chain, proxy),
+            "198.51.100.7".parse::<IpAddr>().unwrap()
+        );
+
+        // Without trusted proxies, nobody's headers are believed
+        let mut config = network_config(&["0.0.0.0/0"]);
+        config.trusted_proxies.clear();
+        let middleware = IpRestrictionMiddleware::new(&config).unwrap();
+        assert_eq!(middleware.extract_client_ip(&spoofed, proxy), proxy);
+    }
+
+    #[test]
+    fn test_rfc7239_forwarded_header() {
+        let mut config = network_config(&["0.0.0.0/0"]);
+        config.proxy_header = "forwarded".to_string();
+        let middleware = IpRestrictionMiddleware::new(&config).unwrap();
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let forwarded = headers(&[
+            ("forwarded", r#"for="[2001:db8:cafe::17]:4711";proto=https"#),
+            ("forwarded", "for=10.0.0.3;by=10.0.0.2"),
+            // Not the header the proxy sets
+            ("x-forwarded-for", "192.0.2.1"),
+        ]);
+        assert_eq!(
+            middleware.extract_client_ip(&forwarded, proxy),
+            "2001:db8:cafe::17".parse::<IpAddr>().unwrap()
+        );
+
+        let with_port = headers(&[("forwarded", "For=\"192.0.2.60:8080\", for=10.0.0.3")]);
+        assert_eq!(
+            middleware.extract_client_ip(&with_port, proxy),
+            "192.0.2.60".parse::<IpAddr>().unwrap()
+        );
+
+        // An unknown hop stops at the last known address
+        let unknown = headers(&[("forwarded", "for=unknown, for=10.0.0.3")]);
+        assert_eq!(
+            middleware.extract_client_ip(&unknown, proxy),
+            "10.0.0.3".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_only_configured_proxy_header_read() {
+        let middleware = IpRestrictionMiddleware::new(&network_config(&["0.0.0.0/0"])).unwrap();
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+
+        // The proxy appends to X-Forwarded-For and passes the client's
+        // own Forwarded and X-Real-IP headers through untouched
+        let passed_through = headers(&[
+            ("forwarded", "for=10.1.2.3"),
+            ("x-real-ip", "10.1.2.4"),
+            ("x-forwarded-for", "198.51.100.7"),
+        ]);
+        assert_eq!(
+            middleware.extract_client_ip(&passed_through, proxy),
+            "198.51.100.7".parse::<IpAddr>().unwrap()
+        );
+
+        let mut config = network_config(&["0.0.0.0/0"]);
+        config.proxy_header = "X-Real-IP".to_string();
+        let middleware = IpRestrictionMiddleware::new(&config).unwrap();
+        assert_eq!(
+            middleware.extract_client_ip(&passed_through, proxy),
+            "10.1.2.4".parse::<IpAddr>().unwrap()
+        );
+
+        config.proxy_header = "x-client-ip".to_string();
+        assert!(IpRestrictionMiddleware::new(&config).is_err());
+    }
 }
@@ -0,0 +1,439 @@
+use jsonwebtoken::{jwk::JwkSet, Algorithm, AlgorithmFamily, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use super::auth::AuthenticatedKey;
+use super::scopes::Scope;
+
+/// Bearer JWTs accepted alongside API keys
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JwtConfig {
+    /// Required `iss` claim
+    pub issuer: Option<String>,
+    /// Required `aud` claim
+    pub audience: Option<String>,
+    /// JWKS document holding the verification keys, reloaded when it changes
+    pub jwks_path: Option<String>,
+    /// PEM public keys, for issuers that don't publish a JWKS
+    pub public_keys: Vec<JwtPublicKeyConfig>,
+    /// Claim listing the services the token may register (dotted path for nested claims)
+    pub services_claim: String,
+    /// Claim listing the roles of the token (dotted path for nested claims)
+    pub roles_claim: String,
+    /// Scopes granted by each role, roles named after a scope grant it directly
+    pub role_scopes: BTreeMap<String, Vec<Scope>>,
+    /// Clock skew tolerated on `exp` and `nbf`
+    pub leeway_seconds: u64,
+    /// How often the JWKS file is checked for changes
+    pub reload_interval_seconds: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JwtPublicKeyConfig {
+    /// Matched against the token `kid` header when set
+    pub kid: Option<String>,
+    pub algorithm: Algorithm,
+    pub pem_file: String,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            issuer: None,
+            audience: None,
+            jwks_path: None,
+            public_keys: Vec::new(),
+            services_claim: "services".to_string(),
+            roles_claim: "roles".to_string(),
+            role_scopes: BTreeMap::new(),
+            leeway_seconds: 60,
+            reload_interval_seconds: 10,
+        }
+    }
+}
+
+struct VerificationKey {
+    kid: Option<String>,
+    /// Algorithm the key is restricted to, when the JWKS says so
+    algorithm: Option<Algorithm>,
+    key: DecodingKey,
+}
+
+struct KeySet {
+    /// Keys from `jwks_path`
+    jwks: Vec<VerificationKey>,
+    jwks_modified: Option<SystemTime>,
+    /// Keys from `public_keys`
+    static_keys: Vec<VerificationKey>,
+}
+
+pub struct JwtValidator {
+    config: JwtConfig,
+    keys: RwLock<KeySet>,
+}
+
+impl JwtValidator {
+    pub fn new(config: &JwtConfig) -> anyhow::Result<Self> {
+        if config.jwks_path.is_none() && config.public_keys.is_empty() {
+            return Err(anyhow::anyhow!(
+                "security.jwt needs a jwks_path or public_keys"
+            ));
+        }
+
+        let static_keys = config
+            .public_keys
+            .iter()
+            .map(load_pem_key)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let (jwks, jwks_modified) = match &config.jwks_path {
+            Some(path) => load_jwks(path)?,
+            None => (Vec::new(), None),
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            keys: RwLock::new(KeySet {
+                jwks,
+                jwks_modified,
+                static_keys,
+            }),
+        })
+    }
+
+    /// Reloads the JWKS file when its modification time changed. A file that
+    /// can't be read keeps the current keys in use.
+    pub fn reload_if_changed(&self) -> anyhow::Result<bool> {
+        let Some(path) = &self.config.jwks_path else {
+            return Ok(false);
+        };
+
+        let modified = std::fs::metadata(path)?.modified()?;
+        if self.read_keys().jwks_modified == Some(modified) {
+            return Ok(false);
+        }
+
+        let (jwks, jwks_modified) = load_jwks(path)?;
+        let mut keys = self.keys.write().unwrap_or_else(|e| e.into_inner());
+        keys.jwks = jwks;
+        keys.jwks_modified = jwks_modified;
+        Ok(true)
+    }
+
+    /// Watches the JWKS file for key rotations
+    pub fn start_watching(self: &Arc<Self>) {
+        let Some(path) = self.config.jwks_path.clone() else {
+            return;
+        };
+
+        let validator = self.clone();
+        let interval = Duration::from_secs(self.config.reload_interval_seconds.max(1));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match validator.reload_if_changed() {
+                    Ok(true) => tracing::info!("🔑 JWKS reloaded from {}", path),
+                    Ok(false) => {}
+                    Err(e) => tracing::error!("❌ Failed to reload JWKS from {}: {}", path, e),
+                }
+            }
+        });
+    }
+
+    /// Verifies `token` and maps its claims to scopes
+    pub fn validate(&self, token: &str) -> Result<AuthenticatedKey, String> {
+        let header =
+            jsonwebtoken::decode_header(token).map_err(|e| format!("Invalid token: {}", e))?;
+        if header.alg.family() == AlgorithmFamily::Hmac {
+            return Err("Invalid token: shared secret algorithms are not accepted".to_string());
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.config.leeway_seconds;
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let keys = self.read_keys();
+        let mut candidates = keys
+            .jwks
+            .iter()
+            .chain(&keys.static_keys)
+            .filter(|key| header.kid.is_none() || key.kid == header.kid)
+            .filter(|key| {
+                key.algorithm
+                    .is_none_or(|algorithm| algorithm == header.alg)
+            })
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err("Invalid token: unknown signing key".to_string());
+        }
+
+        let mut error = None;
+        for key in candidates {
+            match jsonwebtoken::decode::<serde_json::Value>(token, &key.key, &validation) {
+                Ok(data) => return Ok(self.identity(&data.claims)),
+                Err(e) => error = Some(e),
+            }
+        }
+
+        Err(format!(
+            "Invalid token: {}",
+            error.map(|e| e.to_string()).unwrap_or_default()
+        ))
+    }
+
+    fn identity(&self, claims: &serde_json::Value) -> AuthenticatedKey {
+        let mut scopes: Vec<Scope> = claim_values(claims, &self.config.services_claim)
+            .into_iter()
+            .map(Scope::Register)
+            .collect();
+
+        for role in claim_values(claims, &self.config.roles_claim) {
+            match self.config.role_scopes.get(&role) {
+                Some(role_scopes) => scopes.extend(role_scopes.iter().cloned()),
+                None => scopes.extend(role.parse::<Scope>().ok()),
+            }
+        }
+
+        let subject = claims
+            .get("sub")
+            .and_then(|sub| sub.as_str())
+            .unwrap_or("unknown");
+
+        AuthenticatedKey {
+            id: format!("jwt:{}", subject),
+            scopes,
+        }
+    }
+
+    fn read_keys(&self) -> std::sync::RwLockReadGuard<'_, KeySet> {
+        self.keys.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Strings found at `path` in the claims, as a list or a single value
+fn claim_values(claims: &serde_json::Value, path: &str) -> Vec<String> {
+    let value = path
+        .split('.')
+        .try_fold(claims, |value, segment| value.get(segment));
+
+    match value {
+        Some(serde_json::Value::String(value)) => vec![value.clone()],
+        Some(serde_json::Value::Array(values)) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn load_jwks(path: &str) -> anyhow::Result<(Vec<VerificationKey>, Option<SystemTime>)> {
+    let modified = std::fs::metadata(path)?.modified().ok();
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read JWKS {}: {}", path, e))?;
+    let jwks: JwkSet = serde_json::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("Invalid JWKS {}: {}", path, e))?;
+
+    let keys = jwks
+        .keys
+        .iter()
+        .map(|jwk| {
+            let algorithm = match jwk.common.key_algorithm {
+                Some(key_algorithm) => Some(key_algorithm.to_string().parse::<Algorithm>()?),
+                None => None,
+            };
+            Ok(VerificationKey {
+                kid: jwk.common.key_id.clone(),
+                algorithm,
+                key: DecodingKey::from_jwk(jwk)?,
+            })
+        })
+        .collect::<Result<Vec<_>, jsonwebtoken::errors::Error>>()
+        .map_err(|e| anyhow::anyhow!("Invalid key in JWKS {}: {}", path, e))?;
+
+    Ok((keys, modified))
+}
+
+fn load_pem_key(config: &JwtPublicKeyConfig) -> anyhow::Result<VerificationKey> {
+    let pem = std::fs::read(&config.pem_file)
+        .map_err(|e| anyhow::anyhow!("Failed to read JWT key {}: {}", config.pem_file, e))?;
+
+    let key = match config.algorithm.family() {
+        AlgorithmFamily::Rsa => DecodingKey::from_rsa_pem(&pem),
+        AlgorithmFamily::Ec => DecodingKey::from_ec_pem(&pem),
+        AlgorithmFamily::Ed => DecodingKey::from_ed_pem(&pem),
+        AlgorithmFamily::Hmac => {
+            return Err(anyhow::anyhow!(
+                "JWT key {} must be a public key, {:?} is not supported",
+                config.pem_file,
+                config.algorithm
+            ))
+        }
+    }
+    .map_err(|e| anyhow::anyhow!("Invalid JWT key {}: {}", config.pem_file, e))?;
+
+    Ok(VerificationKey {
+        kid: config.kid.clone(),
+        algorithm: Some(config.algorithm),
+        key,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{jwk::Jwk, EncodingKey, Header};
+    use tempfile::TempDir;
+
+    struct Issuer {
+        kid: String,
+        key: EncodingKey,
+        jwk: Jwk,
+    }
+
+    impl Issuer {
+        fn generate(kid: &str) -> Self {
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            let key = EncodingKey::from_ec_pem(key_pair.serialize_pem().as_bytes()).unwrap();
+            let mut jwk = Jwk::from_encoding_key(&key, Algorithm::ES256).unwrap();
+            jwk.common.key_id = Some(kid.to_string());
+            Self {
+                kid: kid.to_string(),
+                key,
+                jwk,
+            }
+        }
+
+        fn token(&self, claims: serde_json::Value) -> String {
+            let mut header = Header::new(Algorithm::ES256);
+            header.kid = Some(self.kid.clone());
+            jsonwebtoken::encode(&header, &claims, &self.key).unwrap()
+        }
+    }
+
+    fn write_jwks(path: &std::path::Path, issuers: &[&Issuer]) {
+        let jwks = JwkSet {
+            keys: issuers.iter().map(|issuer| issuer.jwk.clone()).collect(),
+        };
+        std::fs::write(path, serde_json::to_string(&jwks).unwrap()).unwrap();
+    }
+
+    fn claims(extra: serde_json::Value) -> serde_json::Value {
+        let mut claims = serde_json::json!({
+            "sub": "orders-api",
+            "iss": "https://auth.example.com",
+            "aud": "scoutquest",
+            "exp": chrono::Utc::now().timestamp() + 300,
+        });
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        claims
+    }
+
+    fn validator(jwks_path: &std::path::Path) -> JwtValidator {
+        JwtValidator::new(&JwtConfig {
+            issuer: Some("https://auth.example.com".to_string()),
+            audience: Some("scoutquest".to_string()),
+            jwks_path: Some(jwks_path.to_string_lossy().to_string()),
+            role_scopes: BTreeMap::from([("operator".to_string(), vec![Scope::Admin])]),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_claims_mapped_to_scopes() {
+        let dir = TempDir::new().unwrap();
+        let jwks_path = dir.path().join("jwks.json");
+        let issuer = Issuer::generate("key-1");
+        write_jwks(&jwks_path, &[&issuer]);
+        let validator = validator(&jwks_path);
+
+        let identity = validator
+            .validate(&issuer.token(claims(serde_json::json!({
+                "services": ["orders-*"],
+                "roles": ["read", "unknown-role"]
+            }))))
+            .unwrap();
+        assert_eq!(identity.id, "jwt:orders-api");
+        assert_eq!(
+            identity.scopes,
+            vec![Scope::Register("orders-*".to_string()), Scope::Read]
+        );
+
+        let identity = validator
+            .validate(&issuer.token(claims(serde_json::json!({ "roles": "operator" }))))
+            .unwrap();
+        assert_eq!(identity.scopes, vec![Scope::Admin]);
+    }
+
+    #[test]
+    fn test_invalid_tokens_rejected() {
+        let dir = TempDir::new().unwrap();
+        let jwks_path = dir.path().join("jwks.json");
+        let issuer = Issuer::generate("key-1");
+        write_jwks(&jwks_path, &[&issuer]);
+        let validator = validator(&jwks_path);
+
+        let expired = claims(serde_json::json!({
+            "exp": chrono::Utc::now().timestamp() - 3600
+        }));
+        assert!(validator.validate(&issuer.token(expired)).is_err());
+
+        let other_audience = claims(serde_json::json!({ "aud": "billing" }));
+        assert!(validator.validate(&issuer.token(other_audience)).is_err());
+
+        let unknown = Issuer::generate("key-2");
+        let error = validator.validate(&unknown.token(claims(serde_json::json!({}))));
+        assert_eq!(error.unwrap_err(), "Invalid token: unknown signing key");
+
+        // Same kid, different key
+        let forged = Issuer::generate("key-1");
+        assert!(validator
+            .validate(&forged.token(claims(serde_json::json!({}))))
+            .is_err());
+    }
+
+    #[test]
+    fn test_rotated_keys_picked_up() {
+        let dir = TempDir::new().unwrap();
+        let jwks_path = dir.path().join("jwks.json");
+        let old = Issuer::generate("key-1");
+        write_jwks(&jwks_path, &[&old]);
+        let validator = validator(&jwks_path);
+
+        let new = Issuer::generate("key-2");
+        assert!(validator
+            .validate(&new.token(claims(serde_json::json!({}))))
+            .is_err());
+
+        write_jwks(&jwks_path, &[&new]);
+        // Make sure the modification time moves on coarse-grained filesystems
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&jwks_path)
+            .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+
+        assert!(validator.reload_if_changed().unwrap());
+        assert!(validator
+            .validate(&new.token(claims(serde_json::json!({}))))
+            .is_ok());
+        assert!(validator
+            .validate(&old.token(claims(serde_json::json!({}))))
+            .is_err());
+        assert!(!validator.reload_if_changed().unwrap());
+    }
+}
@@ -1 +1,5 @@
+pub mod auth;
 pub mod ip_restriction;
+pub mod jwt;
+pub mod rate_limit;
+pub mod scopes;
@@ -0,0 +1,491 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::auth::AuthenticatedKey;
+use super::ip_restriction::ClientIp;
+use crate::SecurityConfig;
+
+/// Per route class limits, each falling back to `rate_limit_per_minute`.
+/// A limit of 0 disables rate limiting for its class.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Registrations, deregistrations, deletions and status changes
+    pub writes_per_minute: Option<u32>,
+    pub heartbeats_per_minute: Option<u32>,
+    /// Service listings, discovery and event streams
+    pub discovery_per_minute: Option<u32>,
+    /// Failed authentications per client address, cluster routes included
+    pub failed_auth_per_minute: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+    Writes,
+    Heartbeats,
+    Discovery,
+}
+
+impl RouteClass {
+    /// Class of a request, `None` for the cluster RPC between peers
+    pub(crate) fn of(method: &Method, path: &str) -> Option<Self> {
+        if path.starts_with("/cluster/") {
+            return None;
+        }
+
+        match *method {
+            Method::GET | Method::HEAD | Method::OPTIONS => Some(RouteClass::Discovery),
+            Method::POST if path.ends_with("/heartbeat") => Some(RouteClass::Heartbeats),
+            _ => Some(RouteClass::Writes),
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            RouteClass::Writes => "writes",
+            RouteClass::Heartbeats => "heartbeats",
+            RouteClass::Discovery => "discovery",
+        }
+    }
+}
+
+/// What a bucket counts: requests of a route class, or failed
+/// authentications
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Quota {
+    Route(RouteClass),
+    FailedAuth,
+}
+
+impl Quota {
+    const ALL: [Quota; 4] = [
+        Quota::Route(RouteClass::Writes),
+        Quota::Route(RouteClass::Heartbeats),
+        Quota::Route(RouteClass::Discovery),
+        Quota::FailedAuth,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            Quota::Route(class) => class.index(),
+            Quota::FailedAuth => 3,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Quota::Route(class) => class.name(),
+            Quota::FailedAuth => "failed_auth",
+        }
+    }
+}
+
+impl From<RouteClass> for Quota {
+    fn from(class: RouteClass) -> Self {
+        Quota::Route(class)
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket rate limiter, one bucket per client and route class.
+///
+/// Clients are identified by the API key they authenticated with, or by
+/// their address for anonymous requests. Each bucket holds a minute worth
+/// of requests and refills continuously. Failed authentications have their
+/// own buckets, per client address.
+pub struct RateLimiter {
+    /// Requests per minute, indexed by [`Quota`]
+    limits: [u32; 4],
+    buckets: DashMap<(Quota, String), Bucket>,
+    limited: [AtomicU64; 4],
+}
+
+impl RateLimiter {
+    pub fn new(config: &SecurityConfig) -> Self {
+        let default = config.rate_limit_per_minute;
+        let limits = &config.rate_limits;
+
+        Self {
+            limits: [
+                limits.writes_per_minute.unwrap_or(default),
+                limits.heartbeats_per_minute.unwrap_or(default),
+                limits.discovery_per_minute.unwrap_or(default),
+                limits.failed_auth_per_minute.unwrap_or(default),
+            ],
+            buckets: DashMap::new(),
+            limited: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.limits.iter().any(|&limit| limit > 0)
+    }
+
+    /// Limits per route class, for the startup log
+    pub fn describe_limits(&self) -> Vec<String> {
+        Quota::ALL
+            .iter()
+            .map(|quota| match self.limits[quota.index()] {
+                0 => format!("{}: unlimited", quota.name()),
+                limit => format!("{}: {}/min", quota.name(), limit),
+            })
+            .collect()
+    }
+
+    /// Rejected requests per route class, for `/metrics`
+    pub fn metrics(&self) -> serde_json::Value {
+        let limited: serde_json::Map<String, serde_json::Value> = Quota::ALL
+            .iter()
+            .map(|quota| {
+                (
+                    quota.name().to_string(),
+                    self.limited[quota.index()].load(Ordering::Relaxed).into(),
+                )
+            })
+            .collect();
+
+        serde_json::json!({
+            "limited": limited,
+            "tracked_clients": self.buckets.len(),
+        })
+    }
+
+    /// Takes a token from the bucket of `client`, or returns how long to
+    /// wait for the next one
+    fn acquire(&self, quota: impl Into<Quota>, client: &str) -> Result<(), Duration> {
+        self.acquire_at(quota.into(), client, Instant::now())
+    }
+
+    fn acquire_at(
+        &self,
+        quota: impl Into<Quota>,
+        client: &str,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        self.take_at(quota.into(), client, now, true)
+    }
+
+    /// Whether `client` may fail to authenticate again, or how long it has
+    /// to wait. Only failures take tokens.
+    fn check_failed_auth_at(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        self.take_at(Quota::FailedAuth, client, now, false)
+    }
+
+    fn record_failed_auth_at(&self, client: &str, now: Instant) {
+        let _ = self.take_at(Quota::FailedAuth, client, now, true);
+    }
+
+    /// Refills the bucket of `client`, then checks for a token and takes it
+    /// if `take` is set
+    fn take_at(
+        &self,
+        quota: Quota,
+        client: &str,
+        now: Instant,
+        take: bool,
+    ) -> Result<(), Duration> {
+        let limit = self.limits[quota.index()];
+        if limit == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(limit);
+        let per_second = capacity / 60.0;
+
+        let mut bucket = self
+            .buckets
+            .entry((quota, client.to_string()))
+            .or_insert_with(|| Bucket {
+                tokens: capacity,
+                updated: now,
+            });
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            if take {
+                bucket.tokens -= 1.0;
+            }
+            Ok(())
+        } else {
+            self.limited[quota.index()].fetch_add(1, Ordering::Relaxed);
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+
+    /// Drops the buckets that have been idle long enough to be full again,
+    /// so clients that went away don't accumulate
+    pub fn start_cleanup(self: &Arc<Self>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let limiter = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let Some(limiter) = limiter.upgrade() else {
+                    return;
+                };
+                limiter.remove_idle(Instant::now());
+            }
+        });
+    }
+
+    fn remove_idle(&self, now: Instant) {
+        self.buckets.retain(|_, bucket| {
+            now.saturating_duration_since(bucket.updated) < Duration::from_secs(60)
+        });
+    }
+}
+
+pub async fn rate_limit_layer(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(class) = RouteClass::of(req.method(), req.uri().path()) else {
+        return next.run(req).await;
+    };
+
+    let client = match req.extensions().get::<AuthenticatedKey>() {
+        Some(key) => format!("key:{}", key.id),
+        None => match client_address(&req) {
+            Some(address) => address,
+            None => return next.run(req).await,
+        },
+    };
+
+    match limiter.acquire(class, &client) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => too_many_requests(class.into(), &client, &req, retry_after),
+    }
+}
+
+/// Limits failed authentications per client address. Sits outside the auth
+/// layer, so the requests it rejects are counted, and once a client runs out
+/// it is refused before its credentials are even checked.
+pub async fn failed_auth_limit_layer(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(client) = client_address(&req) else {
+        return next.run(req).await;
+    };
+
+    if let Err(retry_after) = limiter.check_failed_auth_at(&client, Instant::now()) {
+        return too_many_requests(Quota::FailedAuth, &client, &req, retry_after);
+    }
+
+    let response = next.run(req).await;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        limiter.record_failed_auth_at(&client, Instant::now());
+    }
+    response
+}
+
+/// Address of the client, resolved from the proxy headers when trusted
+fn client_address(req: &Request) -> Option<String> {
+    let extensions = req.extensions();
+    match extensions.get::<ClientIp>() {
+        Some(ClientIp(ip)) => Some(ip.to_string()),
+        None => extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string()),
+    }
+}
+
+fn too_many_requests(quota: Quota, client: &str, req: &Request, retry_after: Duration) -> Response {
+    let retry_after = (retry_after.as_secs_f64().ceil() as u64).max(1);
+    tracing::warn!(
+        "🚦 Rate limited {} request from {} to {}, retry in {}s",
+        quota.name(),
+        client,
+        req.uri().path(),
+        retry_after
+    );
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        format!("Rate limit exceeded for {}", quota.name()),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::{auth_layer, ApiKeyAuth, ApiKeyConfig};
+    use crate::AppConfig;
+
+    fn limiter(writes: u32, heartbeats: u32) -> RateLimiter {
+        let mut security = AppConfig::default().security;
+        security.rate_limit_per_minute = 0;
+        security.rate_limits = RateLimitConfig {
+            writes_per_minute: Some(writes),
+            heartbeats_per_minute: Some(heartbeats),
+            discovery_per_minute: None,
+            failed_auth_per_minute: None,
+        };
+        RateLimiter::new(&security)
+    }
+
+    #[test]
+    fn test_route_classes() {
+        let heartbeat = "/api/services/users/instances/1/heartbeat";
+        assert_eq!(
+            RouteClass::of(&Method::POST, heartbeat),
+            Some(RouteClass::Heartbeats)
+        );
+        assert_eq!(
+            RouteClass::of(&Method::POST, "/api/services"),
+            Some(RouteClass::Writes)
+        );
+        assert_eq!(
+            RouteClass::of(&Method::DELETE, "/api/services/users"),
+            Some(RouteClass::Writes)
+        );
+        assert_eq!(
+            RouteClass::of(&Method::GET, "/api/discovery/users"),
+            Some(RouteClass::Discovery)
+        );
+        assert_eq!(RouteClass::of(&Method::POST, "/cluster/append"), None);
+    }
+
+    #[test]
+    fn test_bucket_refills_over_time() {
+        let limiter = limiter(2, 60);
+        let start = Instant::now();
+
+        assert!(limiter.acquire_at(RouteClass::Writes, "a", start).is_ok());
+        assert!(limiter.acquire_at(RouteClass::Writes, "a", start).is_ok());
+        let retry_after = limiter
+            .acquire_at(RouteClass::Writes, "a", start)
+            .unwrap_err();
+        assert_eq!(retry_after.as_secs(), 30);
+
+        // Other clients and classes have their own buckets
+        assert!(limiter.acquire_at(RouteClass::Writes, "b", start).is_ok());
+        assert!(limiter
+            .acquire_at(RouteClass::Heartbeats, "a", start)
+            .is_ok());
+        assert!(limiter
+            .acquire_at(RouteClass::Discovery, "a", start)
+            .is_ok());
+
+        let later = start + Duration::from_secs(30);
+        assert!(limiter.acquire_at(RouteClass::Writes, "a", later).is_ok());
+        assert!(limiter.acquire_at(RouteClass::Writes, "a", later).is_err());
+
+        assert_eq!(limiter.metrics()["limited"]["writes"], 2);
+        limiter.remove_idle(start + Duration::from_secs(120));
+        assert_eq!(limiter.metrics()["tracked_clients"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_limited_requests_get_retry_after() {
+        let limiter = Arc::new(limiter(1, 1));
+        let app = axum::Router::new()
+            .route("/api/services", axum::routing::post(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                limiter,
+                rate_limit_layer,
+            ));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await;
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/api/services", address);
+        let response = client.post(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client.post(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "60");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failed_auth_limited() {
+        let mut security = AppConfig::default().security;
+        security.enable_auth = true;
+        security.api_keys = vec![ApiKeyConfig {
+            id: "ops".to_string(),
+            key: Some("right".to_string()),
+            ..Default::default()
+        }];
+        security.rate_limit_per_minute = 0;
+        security.rate_limits.failed_auth_per_minute = Some(3);
+        let limiter = Arc::new(RateLimiter::new(&security));
+        let auth = Arc::new(ApiKeyAuth::new(&security).unwrap());
+
+        let app = axum::Router::new()
+            .route("/api/services", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(auth, auth_layer))
+            .layer(axum::middleware::from_fn_with_state(
+                limiter,
+                failed_auth_limit_layer,
+            ));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await;
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/api/services", address);
+        let request = |key: &str| client.get(&url).bearer_auth(key).send();
+
+        // Successes don't count
+        for _ in 0..5 {
+            assert_eq!(request("right").await.unwrap().status(), StatusCode::OK);
+        }
+        for _ in 0..3 {
+            assert_eq!(
+                request("guess").await.unwrap().status(),
+                StatusCode::UNAUTHORIZED
+            );
+        }
+
+        let response = request("guess").await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "20");
+
+        // Even the right key waits, the client is refused before auth
+        let response = request("right").await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,358 @@
+use axum::{
+    extract::{RawPathParams, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+use super::auth::AuthenticatedKey;
+
+/// Permission granted to an API key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// Discovery, service listings and event streams
+    Read,
+    /// Register, heartbeat and deregister instances of the services
+    /// matching the glob
+    Register(String),
+    /// Everything, including deleting services and forcing statuses
+    Admin,
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "read" => Ok(Scope::Read),
+            None if s == "admin" => Ok(Scope::Admin),
+            Some(("register", glob)) if !glob.is_empty() => Ok(Scope::Register(glob.to_string())),
+            _ => Err(anyhow::anyhow!(
+                "Invalid scope '{}': expected read, register:<service-glob> or admin",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Register(glob) => write!(f, "register:{}", glob),
+            Scope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl Serialize for Scope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Scope {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Scope a route requires, checked by [`require_scope`]
+#[derive(Debug, Clone, Copy)]
+pub enum RequiredScope {
+    Read,
+    /// `register:` scope matching the `{name}` path parameter, or any
+    /// `register:` scope on routes without one (the handler then checks the
+    /// service name from the body)
+    Register,
+    Admin,
+}
+
+impl AuthenticatedKey {
+    pub fn has_scope(&self, required: &Scope) -> bool {
+        self.scopes.iter().any(|scope| match (scope, required) {
+            (Scope::Admin, _) => true,
+            (Scope::Read, Scope::Read) => true,
+            (Scope::Register(glob), Scope::Register(service)) => glob_matches(glob, service),
+            _ => false,
+        })
+    }
+
+    fn has_any_register_scope(&self) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| matches!(scope, Scope::Admin | Scope::Register(_)))
+    }
+}
+
+/// Checks that the request key grants `required`. Requests without a key
+/// are let through: authentication is disabled or the path is exempt.
+pub fn authorize(
+    key: Option<&AuthenticatedKey>,
+    required: &Scope,
+) -> Result<(), (StatusCode, String)> {
+    match key {
+        Some(key) if !key.has_scope(required) => Err(forbidden(key, &required.to_string())),
+        _ => Ok(()),
+    }
+}
+
+fn forbidden(key: &AuthenticatedKey, missing: &str) -> (StatusCode, String) {
+    tracing::warn!("API key {} denied: missing scope {}", key.id, missing);
+    (
+        StatusCode::FORBIDDEN,
+        format!(
+            "API key {} is missing the {} scope (or admin)",
+            key.id, missing
+        ),
+    )
+}
+
+pub async fn require_scope(
+    State(required): State<RequiredScope>,
+    params: RawPathParams,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = req.extensions().get::<AuthenticatedKey>() else {
+        return next.run(req).await;
+    };
+
+    let service = params
+        .iter()
+        .find(|(name, _)| *name == "name")
+        .map(|(_, value)| value.to_string());
+
+    let result = match (required, service) {
+        (RequiredScope::Read, _) => authorize(Some(key), &Scope::Read),
+        (RequiredScope::Register, Some(service)) => authorize(Some(key), &Scope::Register(service)),
+        (RequiredScope::Register, None) if key.has_any_register_scope() => Ok(()),
+        (RequiredScope::Register, None) => Err(forbidden(key, "register:<service>")),
+        (RequiredScope::Admin, _) => authorize(Some(key), &Scope::Admin),
+    };
+
+    match result {
+        Ok(()) => next.run(req).await,
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Matches `value` against a glob where `*` stands for any sequence of
+/// characters and `?` for a single one
+fn glob_matches(glob: &str, value: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut g, mut v) = (0, 0);
+    // Position after the last `*` and the value position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g + 1, v));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                g += 1;
+                v += 1;
+            }
+            _ => match backtrack {
+                Some((glob_position, value_position)) => {
+                    g = glob_position;
+                    v = value_position + 1;
+                    backtrack = Some((glob_position, value_position + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::{auth_layer, ApiKeyAuth, ApiKeyConfig};
+    use crate::registry::ServiceRegistry;
+    use crate::{AppConfig, AppState};
+    use std::sync::Arc;
+
+    async fn start_server() -> String {
+        let mut security = AppConfig::default().security;
+        security.enable_auth = true;
+        security.api_keys = [
+            ("sidecar", "read"),
+            ("orders", "register:orders*"),
+            ("operator", "admin"),
+        ]
+        .into_iter()
+        .map(|(id, scope)| ApiKeyConfig {
+            id: id.to_string(),
+            key: Some(format!("{}-secret", id)),
+            scopes: vec![scope.parse().unwrap()],
+            ..Default::default()
+        })
+        .collect();
+        let auth = Arc::new(ApiKeyAuth::new(&security).unwrap());
+
+        let app = axum::Router::new()
+            .nest("/api", crate::api_routes())
+            .layer(axum::middleware::from_fn_with_state(auth, auth_layer))
+            .with_state(AppState::for_tests(Arc::new(ServiceRegistry::new())));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        format!("http://{}/api", address)
+    }
+
+    fn registration(service_name: &str) -> serde_json::Value {
+        serde_json::json!({"service_name": service_name, "host": "localhost", "port": 3000})
+    }
+
+    #[tokio::test]
+    async fn test_routes_enforce_scopes() {
+        let url = start_server().await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/services", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .get(format!("{}/services", url))
+            .bearer_auth("sidecar-secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .post(format!("{}/services", url))
+            .bearer_auth("sidecar-secret")
+            .json(&registration("orders"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains("register:<service>"));
+
+        let response = client
+            .post(format!("{}/services", url))
+            .bearer_auth("orders-secret")
+            .json(&registration("users"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.text().await.unwrap().contains("register:users"));
+
+        let instance: serde_json::Value = client
+            .post(format!("{}/services", url))
+            .bearer_auth("orders-secret")
+            .json(&registration("orders-api"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let instance_url = format!(
+            "{}/services/orders-api/instances/{}",
+            url,
+            instance["id"].as_str().unwrap()
+        );
+
+        let response = client
+            .post(format!("{}/heartbeat", instance_url))
+            .bearer_auth("orders-secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .put(format!("{}/status", instance_url))
+            .bearer_auth("orders-secret")
+            .json(&serde_json::json!({"status": "Down"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.text().await.unwrap().contains("admin"));
+
+        let response = client
+            .delete(format!("{}/services/orders-api", url))
+            .bearer_auth("operator-secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    fn key(scopes: &[&str]) -> AuthenticatedKey {
+        AuthenticatedKey {
+            id: "test".to_string(),
+            scopes: scopes.iter().map(|scope| scope.parse().unwrap()).collect(),
+        }
+    }
+
+    fn register(service: &str) -> Scope {
+        Scope::Register(service.to_string())
+    }
+
+    #[test]
+    fn test_scope_parsing() {
+        assert_eq!("read".parse::<Scope>().unwrap(), Scope::Read);
+        assert_eq!(
+            "register:orders-*".parse::<Scope>().unwrap(),
+            register("orders-*")
+        );
+        assert!("register:".parse::<Scope>().is_err());
+        assert!("write".parse::<Scope>().is_err());
+        assert_eq!(register("orders-*").to_string(), "register:orders-*");
+    }
+
+    #[test]
+    fn test_scope_checks() {
+        let instance = key(&["register:orders-*"]);
+        assert!(instance.has_scope(&register("orders-api")));
+        assert!(!instance.has_scope(&register("users")));
+        assert!(!instance.has_scope(&Scope::Read));
+
+        let sidecar = key(&["read"]);
+        assert!(sidecar.has_scope(&Scope::Read));
+        assert!(!sidecar.has_scope(&register("orders")));
+        assert!(!sidecar.has_scope(&Scope::Admin));
+
+        let operator = key(&["admin"]);
+        assert!(operator.has_scope(&register("users")));
+        assert!(operator.has_scope(&Scope::Read));
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        assert!(glob_matches("*", "anything"));
+        assert!(glob_matches("orders", "orders"));
+        assert!(!glob_matches("orders", "orders-api"));
+        assert!(glob_matches("*-api", "orders-api"));
+        assert!(glob_matches("a*b*c", "a-b-b-c"));
+        assert!(glob_matches("user?", "users"));
+        assert!(!glob_matches("a*c", "abd"));
+    }
+}
@@ -16,6 +16,9 @@ pub struct ServiceInstance {
     pub registered_at: DateTime<Utc>,
     pub last_heartbeat: DateTime<Utc>,
     pub last_status_change: DateTime<Utc>,
+    /// Active maintenance window, own or inherited from the service
+    #[serde(default)]
+    pub maintenance: Option<Maintenance>,
 }
 
 /// TLS configuration for ScoutQuest server
@@ -27,20 +30,50 @@ pub struct ScoutQuestTlsConfig {
     pub cert_dir: String,
     /// Auto-generate self-signed certificates if none exist
     pub auto_generate: bool,
-    /// Verify peer certificates (for client authentication)
+    /// Require client certificates chained to `client_ca_path` (mutual TLS)
     pub verify_peer: bool,
+    /// PEM bundle of the CAs trusted to issue client certificates
+    pub client_ca_path: Option<String>,
     /// Optional: Custom certificate path (overrides auto-generation)
     pub cert_path: Option<String>,
     /// Optional: Custom private key path (overrides auto-generation)
     pub key_path: Option<String>,
+    /// DNS names and IP addresses added to the generated certificates, on
+    /// top of localhost, 127.0.0.1, scoutquest and scoutquest-server
+    #[serde(default)]
+    pub subject_alt_names: Vec<String>,
+    /// Validity of the generated certificates (default 365 days)
+    pub validity_days: Option<u32>,
+    /// Key algorithm of the generated certificates: ecdsa-p256 (default),
+    /// ecdsa-p384 or ed25519
+    pub key_algorithm: Option<String>,
+    /// Regenerate auto-managed certificates this long before they expire
+    /// (default 30 days)
+    pub renew_before_days: Option<u32>,
     /// TLS minimum version (1.2, 1.3)
     pub min_version: Option<String>,
     /// TLS maximum version (1.2, 1.3)
     pub max_version: Option<String>,
+    /// Allowed cipher suites by IANA name, e.g. TLS13_AES_256_GCM_SHA384
+    /// (default: the rustls defaults)
+    pub cipher_suites: Option<Vec<String>>,
+    /// Protocols offered through ALPN, among h2 and http/1.1
+    pub alpn_protocols: Option<Vec<String>>,
     /// HTTPS redirect (redirect HTTP to HTTPS)
     pub redirect_http: Option<bool>,
     /// Port for HTTP redirect server
     pub http_port: Option<u16>,
+    /// Host redirected to instead of the Host header of the request
+    pub canonical_host: Option<String>,
+    /// max-age of the Strict-Transport-Security header sent over HTTPS,
+    /// unset or 0 to not send it
+    pub hsts_max_age_seconds: Option<u64>,
+    /// Extend HSTS to the subdomains
+    #[serde(default)]
+    pub hsts_include_subdomains: bool,
+    /// Interval between checks of the certificate files for changes, 0 to
+    /// disable live reload
+    pub reload_interval_seconds: Option<u64>,
 }
 
 impl Default for ScoutQuestTlsConfig {
@@ -49,18 +82,29 @@ impl Default for ScoutQuestTlsConfig {
             enabled: false,
             cert_dir: "/etc/certs".to_string(),
             auto_generate: true,
-            verify_peer: true,
+            verify_peer: false,
+            client_ca_path: None,
             cert_path: None,
             key_path: None,
+            subject_alt_names: Vec::new(),
+            validity_days: Some(365),
+            key_algorithm: Some("ecdsa-p256".to_string()),
+            renew_before_days: Some(30),
             min_version: Some("1.2".to_string()),
             max_version: Some("1.3".to_string()),
+            cipher_suites: None,
+            alpn_protocols: Some(vec!["h2".to_string(), "http/1.1".to_string()]),
             redirect_http: Some(false),
             http_port: Some(3001),
+            canonical_host: None,
+            hsts_max_age_seconds: None,
+            hsts_include_subdomains: false,
+            reload_interval_seconds: Some(30),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InstanceStatus {
     Up,
     Down,
@@ -70,6 +114,33 @@ pub enum InstanceStatus {
     Unknown,
 }
 
+/// Maintenance window of an instance or a whole service.
+///
+/// An instance in maintenance is `OutOfService`. Heartbeats and health checks
+/// only update the status it returns to when the window ends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Maintenance {
+    pub reason: String,
+    /// Given by the operator, or the API key, client certificate or address
+    /// of the caller
+    pub author: String,
+    pub started_at: DateTime<Utc>,
+    /// The window ends by itself at this time
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Set on instances when the window is their service's
+    #[serde(default)]
+    pub service_wide: bool,
+    /// Status the instance returns to when the window ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_status: Option<InstanceStatus>,
+}
+
+impl Maintenance {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthCheck {
     pub url: String,
@@ -80,6 +151,8 @@ pub struct HealthCheck {
     pub headers: Option<HashMap<String, String>>,
 }
 
+/// Service as returned by the API, assembled from the current state of its
+/// instances
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Service {
     pub name: String,
@@ -87,6 +160,22 @@ pub struct Service {
     pub tags: Vec<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Active maintenance window of the whole service
+    pub maintenance: Option<Maintenance>,
+}
+
+/// Stored form of a service.
+///
+/// Instances are referenced by id so their status, heartbeat and metadata
+/// live in exactly one place.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceRecord {
+    pub name: String,
+    pub instance_ids: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub maintenance: Option<Maintenance>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,6 +198,19 @@ pub struct RegisterServiceRequest {
     pub health_check: Option<HealthCheck>,
 }
 
+/// Body of `POST /api/pki/issue`
+#[derive(Debug, Deserialize)]
+pub struct IssueCertificateRequest {
+    pub service_name: String,
+    /// Registered instance of `service_name` the certificate is for
+    pub instance_id: String,
+    /// Capped at `pki.leaf_validity_hours`, which is also the default
+    pub validity_hours: Option<u32>,
+    /// PEM certificate signing request, to keep the private key on the
+    /// instance. A key pair is generated and returned otherwise.
+    pub csr: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DiscoveryQuery {
     pub healthy_only: Option<bool>,
@@ -117,6 +219,43 @@ pub struct DiscoveryQuery {
     pub strategy: Option<LoadBalancingStrategy>,
 }
 
+/// Blocking query parameters: hold the request until the registry index
+/// moves past `index`, or `wait` expires
+#[derive(Debug, Deserialize)]
+pub struct BlockingQuery {
+    pub index: Option<u64>,
+    /// Duration such as `30s`, `5m` or `500ms`; plain numbers are seconds
+    pub wait: Option<String>,
+}
+
+/// Filters accepted by the event endpoints
+#[derive(Debug, Deserialize)]
+pub struct EventQuery {
+    /// Return the events recorded after this sequence number
+    pub since: Option<u64>,
+    pub service: Option<String>,
+    #[serde(rename = "type")]
+    pub event_type: Option<EventType>,
+    pub tag: Option<String>,
+}
+
+/// Filter of the webhook delivery report
+#[derive(Debug, Deserialize)]
+pub struct WebhookQuery {
+    pub webhook: Option<String>,
+}
+
+/// Body of the maintenance endpoints
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub reason: String,
+    /// Defaults to the caller's identity
+    pub author: Option<String>,
+    /// End of the window; `duration_seconds` is the relative alternative
+    pub expires_at: Option<DateTime<Utc>>,
+    pub duration_seconds: Option<u64>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateStatusRequest {
     pub status: InstanceStatus,
@@ -130,16 +269,22 @@ pub struct RegistryStats {
     pub start_time: i64,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceEvent {
     pub event_type: EventType,
     pub service_name: String,
     pub instance_id: Option<String>,
+    /// Position of the event in the registry's event stream, starting at 1
+    #[serde(default)]
+    pub sequence: u64,
+    /// Tags of the instance the event is about
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub timestamp: DateTime<Utc>,
     pub details: serde_json::Value,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EventType {
     ServiceRegistered,
     ServiceDeregistered,
@@ -148,4 +293,79 @@ pub enum EventType {
     InstanceStatusChanged,
     HealthCheckFailed,
     HealthCheckRecovered,
+    /// The service has no healthy instance left
+    ServiceUnavailable,
+    /// The service has a healthy instance again
+    ServiceAvailable,
+    /// An instance, or the whole service when `instance_id` is empty, went
+    /// into maintenance
+    MaintenanceStarted,
+    /// A maintenance window ended, by request or on expiry
+    MaintenanceEnded,
+    /// The instance's status changed too often within the flap window
+    InstanceFlapping,
+}
+
+/// A single state change applied to the registry.
+///
+/// Every write goes through one of these so it can be recorded in the
+/// write-ahead log and replayed on startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RegistryMutation {
+    Register {
+        instance: Box<ServiceInstance>,
+    },
+    Deregister {
+        instance_id: String,
+        timestamp: DateTime<Utc>,
+    },
+    Heartbeat {
+        instance_id: String,
+        timestamp: DateTime<Utc>,
+    },
+    StatusChange {
+        instance_id: String,
+        status: InstanceStatus,
+        timestamp: DateTime<Utc>,
+    },
+    /// Starts a window on one instance, or on the whole service when
+    /// `instance_id` is `None`
+    MaintenanceStart {
+        service_name: String,
+        instance_id: Option<String>,
+        maintenance: Maintenance,
+    },
+    MaintenanceEnd {
+        service_name: String,
+        instance_id: Option<String>,
+        /// Ended because `expires_at` passed
+        expired: bool,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+impl RegistryMutation {
+    /// Instance targeted, `None` for a whole service
+    pub fn instance_id(&self) -> Option<&str> {
+        match self {
+            RegistryMutation::Register { instance } => Some(&instance.id),
+            RegistryMutation::Deregister { instance_id, .. }
+            | RegistryMutation::Heartbeat { instance_id, .. }
+            | RegistryMutation::StatusChange { instance_id, .. } => Some(instance_id),
+            RegistryMutation::MaintenanceStart { instance_id, .. }
+            | RegistryMutation::MaintenanceEnd { instance_id, .. } => instance_id.as_deref(),
+        }
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            RegistryMutation::Register { instance } => instance.registered_at,
+            RegistryMutation::Deregister { timestamp, .. }
+            | RegistryMutation::Heartbeat { timestamp, .. }
+            | RegistryMutation::StatusChange { timestamp, .. }
+            | RegistryMutation::MaintenanceEnd { timestamp, .. } => *timestamp,
+            RegistryMutation::MaintenanceStart { maintenance, .. } => maintenance.started_at,
+        }
+    }
 }
@@ -0,0 +1,546 @@
+//! Durable registry persistence
+//!
+//! Every registry mutation is appended to a write-ahead log (one JSON document
+//! per line) under the configured data directory. The log is periodically
+//! compacted into a snapshot of the whole registry, and on startup the
+//! registry is rebuilt from the latest snapshot followed by the log entries
+//! written after it.
+//!
+//! On disk the data directory looks like this:
+//!
+//! ```text
+//! data/
+//! ├── snapshot.json                  # Latest compacted registry state
+//! ├── wal-00000000000000000001.log   # Log segment starting at sequence 1
+//! └── wal-00000000000000000042.log   # Current segment
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::models::{RegistryMutation, ServiceInstance, ServiceRecord};
+use crate::registry::ServiceRegistry;
+use crate::StorageConfig;
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SEGMENT_PREFIX: &str = "wal-";
+const SEGMENT_SUFFIX: &str = ".log";
+
+/// Compacted registry state written to `snapshot.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    /// Sequence number of the last log entry included in this snapshot
+    pub sequence: u64,
+    /// Sequence number of the last event recorded before this snapshot, so
+    /// event numbering carries on after a restart
+    #[serde(default)]
+    pub event_sequence: u64,
+    pub taken_at: DateTime<Utc>,
+    pub services: Vec<ServiceRecord>,
+    pub instances: Vec<ServiceInstance>,
+}
+
+/// State read back from disk when the registry starts
+#[derive(Debug, Default)]
+pub struct RecoveredState {
+    pub snapshot: Option<RegistrySnapshot>,
+    /// Log entries written after the snapshot, in order
+    pub mutations: Vec<RegistryMutation>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LogEntry {
+    sequence: u64,
+    mutation: RegistryMutation,
+}
+
+struct LogSegment {
+    file: File,
+    start_sequence: u64,
+    next_sequence: u64,
+}
+
+/// Write-ahead log and snapshot store for the service registry
+pub struct RegistryPersistence {
+    data_dir: PathBuf,
+    sync_writes: bool,
+    segment: Mutex<LogSegment>,
+}
+
+impl RegistryPersistence {
+    /// Opens the data directory, reading back any existing snapshot and log.
+    ///
+    /// A fresh log segment is always started so that a torn write at the end
+    /// of the previous segment can never be followed by new entries.
+    pub fn open(config: &StorageConfig) -> anyhow::Result<(Self, RecoveredState)> {
+        let data_dir = PathBuf::from(&config.data_dir);
+        fs::create_dir_all(&data_dir).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to create data directory {}: {}",
+                data_dir.display(),
+                e
+            )
+        })?;
+
+        let snapshot = read_snapshot(&data_dir)?;
+        let snapshot_sequence = snapshot.as_ref().map(|s| s.sequence).unwrap_or(0);
+
+        let mut last_sequence = snapshot_sequence;
+        let mut mutations = Vec::new();
+
+        for (_, path) in list_segments(&data_dir)? {
+            for entry in read_segment::<LogEntry>(&path)? {
+                if entry.sequence <= snapshot_sequence {
+                    continue;
+                }
+                last_sequence = last_sequence.max(entry.sequence);
+                mutations.push(entry.mutation);
+            }
+        }
+
+        let next_sequence = last_sequence + 1;
+        let segment = LogSegment {
+            file: File::create(segment_path(&data_dir, next_sequence))?,
+            start_sequence: next_sequence,
+            next_sequence,
+        };
+
+        let persistence = Self {
+            data_dir,
+            sync_writes: config.sync_writes,
+            segment: Mutex::new(segment),
+        };
+
+        Ok((
+            persistence,
+            RecoveredState {
+                snapshot,
+                mutations,
+            },
+        ))
+    }
+
+    /// Appends a mutation to the current log segment, synced to disk when
+    /// `sync_writes` is set
+    pub fn append(&self, mutation: &RegistryMutation) -> anyhow::Result<()> {
+        let mut segment = self.lock_segment();
+
+        let entry = LogEntry {
+            sequence: segment.next_sequence,
+            mutation: mutation.clone(),
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let position = segment.file.stream_position()?;
+        let written = segment.file.write_all(line.as_bytes()).and_then(|_| {
+            if self.sync_writes {
+                segment.file.sync_data()
+            } else {
+                Ok(())
+            }
+        });
+
+        if let Err(e) = written {
+            // Drop the partial entry so later entries never follow it, or
+            // give up on the segment and its sequence number
+            let truncated = segment
+                .file
+                .set_len(position)
+                .and_then(|_| segment.file.seek(SeekFrom::Start(position)));
+            if truncated.is_err() {
+                segment.next_sequence += 1;
+                let path = segment_path(&self.data_dir, segment.next_sequence);
+                match File::create(&path) {
+                    Ok(file) => {
+                        segment.file = file;
+                        segment.start_sequence = segment.next_sequence;
+                    }
+                    Err(e) => {
+                        tracing::error!("❌ Failed to create log segment {}: {}", path.display(), e)
+                    }
+                }
+            }
+            return Err(e.into());
+        }
+
+        segment.next_sequence += 1;
+        Ok(())
+    }
+
+    /// Closes the current log segment and starts a new one.
+    ///
+    /// Returns the sequence number of the last entry written before the
+    /// rotation; a snapshot taken at the same instant covers exactly the
+    /// entries up to and including it.
+    pub fn rotate(&self) -> anyhow::Result<u64> {
+        let mut segment = self.lock_segment();
+        let last_sequence = segment.next_sequence - 1;
+
+        // Nothing was written since the last rotation, keep the current segment
+        if segment.start_sequence == segment.next_sequence {
+            return Ok(last_sequence);
+        }
+
+        segment.file.sync_data()?;
+        segment.file = File::create(segment_path(&self.data_dir, segment.next_sequence))?;
+        segment.start_sequence = segment.next_sequence;
+
+        Ok(last_sequence)
+    }
+
+    /// Atomically writes a snapshot and removes the log segments it covers
+    pub fn write_snapshot(&self, snapshot: &RegistrySnapshot) -> anyhow::Result<()> {
+        let path = self.data_dir.join(SNAPSHOT_FILE);
+        let tmp_path = self.data_dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+
+        {
+            let mut file = File::create(&tmp_path)?;
+            serde_json::to_writer(&mut file, snapshot)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)?;
+
+        let current_start = self.lock_segment().start_sequence;
+        for (start_sequence, segment_path) in list_segments(&self.data_dir)? {
+            if start_sequence <= snapshot.sequence && start_sequence < current_start {
+                fs::remove_file(&segment_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    fn lock_segment(&self) -> std::sync::MutexGuard<'_, LogSegment> {
+        self.segment.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Periodically compacts the registry log into a snapshot
+pub fn start_snapshot_task(registry: Arc<ServiceRegistry>, interval_seconds: u64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds.max(1)));
+        // The first tick completes immediately, the registry was just restored
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = registry.snapshot() {
+                tracing::error!("❌ Failed to write registry snapshot: {}", e);
+            }
+        }
+    });
+
+    tracing::info!(
+        "💾 Registry snapshots scheduled (interval: {}s)",
+        interval_seconds
+    );
+}
+
+fn segment_path(data_dir: &Path, start_sequence: u64) -> PathBuf {
+    data_dir.join(format!(
+        "{}{:020}{}",
+        SEGMENT_PREFIX, start_sequence, SEGMENT_SUFFIX
+    ))
+}
+
+/// Lists log segments sorted by their starting sequence number
+fn list_segments(data_dir: &Path) -> anyhow::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+
+    for entry in fs::read_dir(data_dir)? {
+        let path = entry?.path();
+        let start_sequence = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(SEGMENT_PREFIX))
+            .and_then(|name| name.strip_suffix(SEGMENT_SUFFIX))
+            .and_then(|sequence| sequence.parse::<u64>().ok());
+
+        if let Some(start_sequence) = start_sequence {
+            segments.push((start_sequence, path));
+        }
+    }
+
+    segments.sort_by_key(|(start_sequence, _)| *start_sequence);
+    Ok(segments)
+}
+
+fn read_snapshot(data_dir: &Path) -> anyhow::Result<Option<RegistrySnapshot>> {
+    let path = data_dir.join(SNAPSHOT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = File::open(&path)?;
+    let snapshot = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| anyhow::anyhow!("Corrupted snapshot {}: {}", path.display(), e))?;
+    Ok(Some(snapshot))
+}
+
+/// Reads the entries of a segment. An unreadable last line is the torn
+/// write of a crash and is skipped; anywhere else the log is corrupted.
+pub(crate) fn read_segment<T: DeserializeOwned>(path: &Path) -> anyhow::Result<Vec<T>> {
+    let file = OpenOptions::new().read(true).open(path)?;
+    let lines = BufReader::new(file)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()?;
+    let last_line = lines.iter().rposition(|line| !line.trim().is_empty());
+    let mut entries = Vec::new();
+
+    for (line_number, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<T>(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) if Some(line_number) == last_line => {
+                tracing::warn!(
+                    "Ignoring torn log entry at {}:{}: {}",
+                    path.display(),
+                    line_number + 1,
+                    e
+                );
+            }
+            Err(e) => {
+                return Err(anyhow::anyhow!(
+                    "Corrupted log entry at {}:{}: {}",
+                    path.display(),
+                    line_number + 1,
+                    e
+                ))
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventReplay;
+    use crate::models::{InstanceStatus, RegisterServiceRequest};
+    use crate::store::StorageBackend;
+    use tempfile::TempDir;
+
+    fn storage_config(dir: &TempDir) -> StorageConfig {
+        StorageConfig {
+            enabled: true,
+            backend: StorageBackend::Memory,
+            data_dir: dir.path().to_string_lossy().to_string(),
+            snapshot_interval_seconds: 300,
+            sync_writes: false,
+        }
+    }
+
+    fn open_registry(config: &StorageConfig) -> ServiceRegistry {
+        let (persistence, recovered) = RegistryPersistence::open(config).unwrap();
+        ServiceRegistry::with_persistence(persistence, recovered).unwrap()
+    }
+
+    fn register_request(service_name: &str, port: u16) -> RegisterServiceRequest {
+        RegisterServiceRequest {
+            service_name: service_name.to_string(),
+            host: "localhost".to_string(),
+            port,
+            secure: None,
+            metadata: None,
+            tags: Some(vec!["api".to_string()]),
+            health_check: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_recovers_from_log() {
+        let dir = TempDir::new().unwrap();
+        let config = storage_config(&dir);
+
+        let (kept_id, removed_id) = {
+            let registry = open_registry(&config);
+            let kept = registry
+                .register_instance(register_request("users", 3000))
+                .await
+                .unwrap();
+            let removed = registry
+                .register_instance(register_request("users", 3001))
+                .await
+                .unwrap();
+            registry
+                .update_instance_status(&kept.id, InstanceStatus::OutOfService)
+                .await
+                .unwrap();
+            registry.deregister_instance(&removed.id).await.unwrap();
+            (kept.id, removed.id)
+        };
+
+        let registry = open_registry(&config);
+        let instances = registry.get_all_instances().unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].id, kept_id);
+        assert!(matches!(instances[0].status, InstanceStatus::OutOfService));
+        assert!(!instances.iter().any(|i| i.id == removed_id));
+        assert_eq!(registry.get_all_services().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_compacts_log() {
+        let dir = TempDir::new().unwrap();
+        let config = storage_config(&dir);
+
+        {
+            let registry = open_registry(&config);
+            registry
+                .register_instance(register_request("users", 3000))
+                .await
+                .unwrap();
+            registry.snapshot().unwrap();
+            registry
+                .register_instance(register_request("orders", 4000))
+                .await
+                .unwrap();
+        }
+
+        // Only the segment written after the snapshot remains
+        let segments = list_segments(dir.path()).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].0, 2);
+
+        let registry = open_registry(&config);
+        let mut names: Vec<_> = registry
+            .get_all_services()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["orders", "users"]);
+    }
+
+    #[tokio::test]
+    async fn test_event_sequence_survives_restart() {
+        let dir = TempDir::new().unwrap();
+        let config = storage_config(&dir);
+
+        let last_sequence = {
+            let registry = open_registry(&config);
+            let instance = registry
+                .register_instance(register_request("users", 3000))
+                .await
+                .unwrap();
+            registry.snapshot().unwrap();
+            registry
+                .update_instance_status(&instance.id, InstanceStatus::OutOfService)
+                .await
+                .unwrap();
+            registry
+                .register_instance(register_request("orders", 4000))
+                .await
+                .unwrap();
+            registry.index(None)
+        };
+        assert!(last_sequence > 2);
+
+        // Numbering may resume past the last event, never before it
+        let registry = open_registry(&config);
+        let resumed = registry.index(None);
+        assert!(resumed >= last_sequence);
+
+        // Older cursors must resync, up-to-date ones never get stale events
+        assert!(matches!(
+            registry.events_since(2),
+            EventReplay::TooOld { .. }
+        ));
+        assert!(!matches!(
+            registry.events_since(last_sequence),
+            EventReplay::Events { ref events, .. } if !events.is_empty()
+        ));
+
+        registry
+            .register_instance(register_request("billing", 5000))
+            .await
+            .unwrap();
+        let EventReplay::Events { events, .. } = registry.events_since(resumed) else {
+            panic!("events after the restart should be replayed");
+        };
+        assert_eq!(events[0].sequence, resumed + 1);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_mutations_not_logged() {
+        let dir = TempDir::new().unwrap();
+        let config = storage_config(&dir);
+
+        let registry = open_registry(&config);
+        registry
+            .register_instance(register_request("users", 3000))
+            .await
+            .unwrap();
+        let (_, path) = list_segments(dir.path()).unwrap().remove(0);
+        let logged = fs::read_to_string(&path).unwrap();
+
+        // The service exists but is not in maintenance
+        assert!(!registry
+            .end_maintenance("users", None, false)
+            .await
+            .unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), logged);
+    }
+
+    #[tokio::test]
+    async fn test_torn_log_entry_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        let config = storage_config(&dir);
+
+        {
+            let registry = open_registry(&config);
+            registry
+                .register_instance(register_request("users", 3000))
+                .await
+                .unwrap();
+        }
+
+        let (_, path) = list_segments(dir.path()).unwrap().remove(0);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"sequence\":2,\"mutation\":{\"ty")
+            .unwrap();
+
+        let registry = open_registry(&config);
+        assert_eq!(registry.get_all_instances().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_log_entry_fails_startup() {
+        let dir = TempDir::new().unwrap();
+        let config = storage_config(&dir);
+
+        {
+            let registry = open_registry(&config);
+            for port in [3000, 3001] {
+                registry
+                    .register_instance(register_request("users", port))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let (_, path) = list_segments(dir.path()).unwrap().remove(0);
+        let content = fs::read_to_string(&path).unwrap();
+        let (first, rest) = content.split_once('\n').unwrap();
+        fs::write(&path, format!("{}\n{}", &first[..first.len() / 2], rest)).unwrap();
+
+        let error = RegistryPersistence::open(&config).err().unwrap();
+        assert!(error.to_string().contains("Corrupted log entry"));
+    }
+}
@@ -1,18 +1,38 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use rand::prelude::IndexedRandom;
-use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
-use tokio::sync::broadcast;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
 use uuid::Uuid;
 
+use crate::cluster::ClusterNode;
+use crate::events::{EventHistory, EventReplay, FlapDetector};
 use crate::models::*;
+use crate::persistence::{RecoveredState, RegistryPersistence, RegistrySnapshot};
+use crate::store::{MemoryStore, RegistryStore, StoreWrite};
+
+/// Default number of recent events kept for clients resuming a stream
+const DEFAULT_EVENT_HISTORY_SIZE: usize = 1000;
 
 pub struct ServiceRegistry {
-    services: DashMap<String, Service>,
-    instances: DashMap<String, ServiceInstance>,
+    store: Box<dyn RegistryStore>,
     start_time: AtomicI64,
-    round_robin_counters: DashMap<String, AtomicUsize>,
     event_sender: broadcast::Sender<ServiceEvent>,
+    /// Also serializes publishing, so subscribers see events in sequence order
+    event_history: Mutex<EventHistory>,
+    /// Sequence of the last event, the registry-wide index
+    index: watch::Sender<u64>,
+    /// Sequence of the last event for each service
+    service_indexes: DashMap<String, u64>,
+    flaps: Mutex<FlapDetector>,
+    persistence: Option<RegistryPersistence>,
+    /// Serializes mutations so the log order always matches the applied order
+    commit_lock: Mutex<()>,
+    /// Set when running as a cluster member; writes then go through the
+    /// replicated log instead of being applied directly
+    cluster: OnceLock<Weak<ClusterNode>>,
 }
 
 impl Default for ServiceRegistry {
@@ -23,15 +43,99 @@ impl Default for ServiceRegistry {
 
 impl ServiceRegistry {
     pub fn new() -> Self {
+        Self::with_store(Box::new(MemoryStore::new()))
+    }
+
+    /// Creates a registry on top of the given storage backend
+    pub fn with_store(store: Box<dyn RegistryStore>) -> Self {
         let (event_sender, _) = broadcast::channel(1000);
 
         Self {
-            services: DashMap::new(),
-            instances: DashMap::new(),
+            store,
             start_time: AtomicI64::new(Utc::now().timestamp()),
-            round_robin_counters: DashMap::new(),
             event_sender,
+            event_history: Mutex::new(EventHistory::new(DEFAULT_EVENT_HISTORY_SIZE)),
+            index: watch::channel(0).0,
+            service_indexes: DashMap::new(),
+            flaps: Mutex::new(FlapDetector::new(0, Duration::ZERO)),
+            persistence: None,
+            commit_lock: Mutex::new(()),
+            cluster: OnceLock::new(),
+        }
+    }
+
+    /// Creates an in-memory registry backed by a write-ahead log, restoring
+    /// the state recovered from disk.
+    pub fn with_persistence(
+        persistence: RegistryPersistence,
+        recovered: RecoveredState,
+    ) -> anyhow::Result<Self> {
+        let mut registry = Self::new();
+
+        if let Some(snapshot) = &recovered.snapshot {
+            registry.store.write(&snapshot_writes(snapshot))?;
+        }
+
+        let replayed = recovered.mutations.len();
+        let mut event_sequence = recovered
+            .snapshot
+            .as_ref()
+            .map(|snapshot| snapshot.event_sequence)
+            .unwrap_or(0);
+        for mutation in &recovered.mutations {
+            let Some(events) = registry.apply(mutation)? else {
+                continue;
+            };
+            // Flapping isn't detected on replay, but every status change may
+            // have been followed by a flapping event. Numbering resumes past
+            // them, so resumed cursors never skip events.
+            let flappable = events
+                .iter()
+                .filter(|event| {
+                    matches!(
+                        event.event_type,
+                        EventType::InstanceStatusChanged | EventType::HealthCheckRecovered
+                    )
+                })
+                .count();
+            event_sequence += (events.len() + flappable) as u64;
         }
+        registry.lock_history().resume_after(event_sequence);
+        registry.index.send_replace(event_sequence);
+
+        tracing::info!(
+            "💾 Registry restored from {}: {} services, {} instances ({} log entries replayed)",
+            persistence.data_dir().display(),
+            registry.store.service_count()?,
+            registry.store.instance_count()?,
+            replayed
+        );
+
+        registry.persistence = Some(persistence);
+        Ok(registry)
+    }
+
+    /// Keeps the last `capacity` events for replay instead of the default
+    pub fn with_event_history(self, capacity: usize) -> Self {
+        let mut history = self.lock_history();
+        let last_sequence = history.last_sequence();
+        *history = EventHistory::new(capacity);
+        history.resume_after(last_sequence);
+        drop(history);
+        self
+    }
+
+    /// Reports an instance as flapping once its status changed `threshold`
+    /// times within `window`
+    pub fn with_flap_detection(self, threshold: usize, window: Duration) -> Self {
+        *self.flaps.lock().unwrap_or_else(|e| e.into_inner()) =
+            FlapDetector::new(threshold, window);
+        self
+    }
+
+    /// Whether the registry writes a log that needs periodic snapshots
+    pub fn is_persistent(&self) -> bool {
+        self.persistence.is_some()
     }
 
     pub async fn register_instance(
@@ -54,130 +158,51 @@ impl ServiceRegistry {
             registered_at: now,
             last_heartbeat: now,
             last_status_change: now,
+            maintenance: None,
         };
 
-        self.instances.insert(instance_id.clone(), instance.clone());
-
-        let service_existed = self.services.contains_key(&request.service_name);
-
-        self.services
-            .entry(request.service_name.clone())
-            .and_modify(|service| {
-                service.instances.push(instance.clone());
-                service.updated_at = now;
-            })
-            .or_insert_with(|| Service {
-                name: request.service_name.clone(),
-                instances: vec![instance.clone()],
-                tags: instance.tags.clone(),
-                created_at: now,
-                updated_at: now,
-            });
-
-        let event = ServiceEvent {
-            event_type: if service_existed {
-                EventType::InstanceRegistered
-            } else {
-                EventType::ServiceRegistered
-            },
-            service_name: request.service_name.clone(),
-            instance_id: Some(instance_id.clone()),
-            timestamp: now,
-            details: serde_json::json!({
-                "host": instance.host,
-                "port": instance.port,
-                "tags": instance.tags
-            }),
-        };
-
-        let _ = self.event_sender.send(event);
+        self.submit(RegistryMutation::Register {
+            instance: Box::new(instance.clone()),
+        })
+        .await?;
 
         tracing::info!(
             "Instance registered: {} for service {}",
             instance_id,
             request.service_name
         );
-        Ok(instance)
+        // Joining a service in maintenance puts the instance in maintenance too
+        Ok(self.get_instance(&instance_id)?.unwrap_or(instance))
     }
 
-    pub async fn deregister_instance(&self, instance_id: &str) -> bool {
-        if let Some((_, instance)) = self.instances.remove(instance_id) {
-            let mut service_removed = false;
-
-            if let Some(mut service) = self.services.get_mut(&instance.service_name) {
-                service.instances.retain(|i| i.id != instance_id);
-                service.updated_at = Utc::now();
-
-                if service.instances.is_empty() {
-                    drop(service);
-                    self.services.remove(&instance.service_name);
-                    service_removed = true;
-                }
-            }
-
-            let event = ServiceEvent {
-                event_type: if service_removed {
-                    EventType::ServiceDeregistered
-                } else {
-                    EventType::InstanceDeregistered
-                },
-                service_name: instance.service_name.clone(),
-                instance_id: Some(instance_id.to_string()),
+    pub async fn deregister_instance(&self, instance_id: &str) -> anyhow::Result<bool> {
+        let deregistered = self
+            .submit(RegistryMutation::Deregister {
+                instance_id: instance_id.to_string(),
                 timestamp: Utc::now(),
-                details: serde_json::json!({
-                    "host": instance.host,
-                    "port": instance.port
-                }),
-            };
-
-            let _ = self.event_sender.send(event);
+            })
+            .await?;
 
+        if deregistered {
             tracing::info!("Instance deregistered: {}", instance_id);
-            true
-        } else {
-            false
         }
+        Ok(deregistered)
     }
 
-    pub async fn update_heartbeat(&self, instance_id: &str) -> bool {
-        if let Some(mut instance) = self.instances.get_mut(instance_id) {
-            let previous_status = instance.status.clone();
-            instance.last_heartbeat = Utc::now();
-
-            if !matches!(instance.status, InstanceStatus::Up) {
-                instance.status = InstanceStatus::Up;
-                instance.last_status_change = Utc::now();
-
-                let event = ServiceEvent {
-                    event_type: EventType::HealthCheckRecovered,
-                    service_name: instance.service_name.clone(),
-                    instance_id: Some(instance_id.to_string()),
-                    timestamp: Utc::now(),
-                    details: serde_json::json!({
-                        "previous_status": format!("{:?}", previous_status),
-                        "new_status": "Up"
-                    }),
-                };
-
-                let _ = self.event_sender.send(event);
-            }
-
-            true
-        } else {
-            false
-        }
+    pub async fn update_heartbeat(&self, instance_id: &str) -> anyhow::Result<bool> {
+        self.submit(RegistryMutation::Heartbeat {
+            instance_id: instance_id.to_string(),
+            timestamp: Utc::now(),
+        })
+        .await
     }
 
     pub async fn get_service_instances(
         &self,
         service_name: &str,
         query: &DiscoveryQuery,
-    ) -> Vec<ServiceInstance> {
-        let mut instances = self
-            .services
-            .get(service_name)
-            .map(|service| service.instances.clone())
-            .unwrap_or_default();
+    ) -> anyhow::Result<Vec<ServiceInstance>> {
+        let mut instances = self.service_instances(service_name)?;
 
         if query.healthy_only.unwrap_or(true) {
             instances.retain(|i| matches!(i.status, InstanceStatus::Up));
@@ -192,14 +217,14 @@ impl ServiceRegistry {
             instances.truncate(limit);
         }
 
-        instances
+        Ok(instances)
     }
 
     pub async fn load_balance_service(
         &self,
         service_name: &str,
         strategy: LoadBalancingStrategy,
-    ) -> Option<ServiceInstance> {
+    ) -> anyhow::Result<Option<ServiceInstance>> {
         let query = DiscoveryQuery {
             healthy_only: Some(true),
             tags: None,
@@ -207,25 +232,20 @@ impl ServiceRegistry {
             strategy: Some(strategy.clone()),
         };
 
-        let instances = self.get_service_instances(service_name, &query).await;
+        let instances = self.get_service_instances(service_name, &query).await?;
 
         if instances.is_empty() {
-            return None;
+            return Ok(None);
         }
 
-        match strategy {
+        Ok(match strategy {
             LoadBalancingStrategy::Random => {
                 let mut rng = rand::rng();
                 instances.choose(&mut rng).cloned()
             }
             LoadBalancingStrategy::RoundRobin => {
-                let counter = self
-                    .round_robin_counters
-                    .entry(service_name.to_string())
-                    .or_insert_with(|| AtomicUsize::new(0));
-
-                let index = counter.fetch_add(1, Ordering::Relaxed) % instances.len();
-                instances.get(index).cloned()
+                let position = self.store.next_round_robin(service_name)?;
+                instances.get(position % instances.len()).cloned()
             }
             LoadBalancingStrategy::LeastConnections => instances.first().cloned(),
             LoadBalancingStrategy::WeightedRandom => {
@@ -233,75 +253,1363 @@ impl ServiceRegistry {
                 instances.choose(&mut rng).cloned()
             }
             LoadBalancingStrategy::HealthyOnly => instances.first().cloned(),
-        }
+        })
     }
 
-    pub async fn get_all_services(&self) -> Vec<Service> {
-        self.services
-            .iter()
-            .map(|entry| entry.value().clone())
-            .collect()
+    pub async fn get_all_services(&self) -> anyhow::Result<Vec<Service>> {
+        self.list_service_views()
     }
 
-    pub async fn get_services_by_tag(&self, tag: &str) -> Vec<Service> {
-        self.services
-            .iter()
-            .filter(|entry| entry.value().tags.contains(&tag.to_string()))
-            .map(|entry| entry.value().clone())
-            .collect()
+    pub async fn get_service(&self, name: &str) -> anyhow::Result<Option<Service>> {
+        self.store
+            .get_service(name)?
+            .map(|record| self.service_view(record))
+            .transpose()
     }
 
-    pub async fn update_instance_status(&self, instance_id: &str, status: InstanceStatus) -> bool {
-        if let Some(mut instance) = self.instances.get_mut(instance_id) {
-            let previous_status = instance.status.clone();
-            instance.status = status.clone();
-            instance.last_status_change = Utc::now();
+    pub async fn get_services_by_tag(&self, tag: &str) -> anyhow::Result<Vec<Service>> {
+        Ok(self
+            .list_service_views()?
+            .into_iter()
+            .filter(|service| service.tags.contains(&tag.to_string()))
+            .collect())
+    }
 
-            let event = ServiceEvent {
-                event_type: EventType::InstanceStatusChanged,
-                service_name: instance.service_name.clone(),
-                instance_id: Some(instance_id.to_string()),
+    pub async fn update_instance_status(
+        &self,
+        instance_id: &str,
+        status: InstanceStatus,
+    ) -> anyhow::Result<bool> {
+        let updated = self
+            .submit(RegistryMutation::StatusChange {
+                instance_id: instance_id.to_string(),
+                status: status.clone(),
                 timestamp: Utc::now(),
-                details: serde_json::json!({
-                    "previous_status": format!("{:?}", previous_status),
-                    "new_status": format!("{:?}", status)
-                }),
-            };
-
-            let _ = self.event_sender.send(event);
+            })
+            .await?;
 
+        if updated {
             tracing::info!("Status updated for instance {}: {:?}", instance_id, status);
-            true
-        } else {
-            false
         }
+        Ok(updated)
     }
 
-    pub async fn get_stats(&self) -> RegistryStats {
-        let total_services = self.services.len();
-        let total_instances = self.instances.len();
-        let healthy_instances = self
-            .instances
+    /// Starts a maintenance window on an instance, or on every instance of
+    /// the service when `instance_id` is `None`. Returns `false` if the
+    /// target does not exist.
+    pub async fn start_maintenance(
+        &self,
+        service_name: &str,
+        instance_id: Option<&str>,
+        maintenance: Maintenance,
+    ) -> anyhow::Result<bool> {
+        let reason = maintenance.reason.clone();
+        let started = self
+            .submit(RegistryMutation::MaintenanceStart {
+                service_name: service_name.to_string(),
+                instance_id: instance_id.map(str::to_string),
+                maintenance,
+            })
+            .await?;
+
+        if started {
+            tracing::info!(
+                "🔧 Maintenance started on {}: {}",
+                instance_id.unwrap_or(service_name),
+                reason
+            );
+        }
+        Ok(started)
+    }
+
+    /// Ends the maintenance window of an instance or a service. Returns
+    /// `false` if the target does not exist or is not in maintenance.
+    pub async fn end_maintenance(
+        &self,
+        service_name: &str,
+        instance_id: Option<&str>,
+        expired: bool,
+    ) -> anyhow::Result<bool> {
+        let ended = self
+            .submit(RegistryMutation::MaintenanceEnd {
+                service_name: service_name.to_string(),
+                instance_id: instance_id.map(str::to_string),
+                expired,
+                timestamp: Utc::now(),
+            })
+            .await?;
+
+        if ended {
+            tracing::info!(
+                "🔧 Maintenance ended on {}{}",
+                instance_id.unwrap_or(service_name),
+                if expired { " (expired)" } else { "" }
+            );
+        }
+        Ok(ended)
+    }
+
+    /// Ends the maintenance windows whose expiry time has passed
+    pub async fn end_expired_maintenance(&self) -> anyhow::Result<()> {
+        let now = Utc::now();
+
+        let services: Vec<String> = self
+            .store
+            .list_services()?
+            .into_iter()
+            .filter(|record| {
+                record
+                    .maintenance
+                    .as_ref()
+                    .is_some_and(|maintenance| maintenance.is_expired(now))
+            })
+            .map(|record| record.name)
+            .collect();
+        for service_name in services {
+            self.end_maintenance(&service_name, None, true).await?;
+        }
+
+        // Inherited windows end with their service's
+        let instances: Vec<ServiceInstance> = self
+            .get_all_instances()?
+            .into_iter()
+            .filter(|instance| {
+                instance.maintenance.as_ref().is_some_and(|maintenance| {
+                    !maintenance.service_wide && maintenance.is_expired(now)
+                })
+            })
+            .collect();
+        for instance in instances {
+            self.end_maintenance(&instance.service_name, Some(&instance.id), true)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_stats(&self) -> anyhow::Result<RegistryStats> {
+        let instances = self.get_all_instances()?;
+        let total_services = self.store.service_count()?;
+        let total_instances = instances.len();
+        let healthy_instances = instances
             .iter()
-            .filter(|entry| matches!(entry.value().status, InstanceStatus::Up))
+            .filter(|instance| matches!(instance.status, InstanceStatus::Up))
             .count();
 
-        RegistryStats {
+        Ok(RegistryStats {
             total_services,
             total_instances,
             healthy_instances,
             start_time: self.start_time.load(Ordering::Relaxed),
-        }
+        })
     }
 
     pub fn subscribe_events(&self) -> broadcast::Receiver<ServiceEvent> {
         self.event_sender.subscribe()
     }
 
-    pub fn get_all_instances(&self) -> Vec<ServiceInstance> {
-        self.instances
-            .iter()
-            .map(|entry| entry.value().clone())
+    /// Index of the registry, or of one service: the sequence number of the
+    /// last event that changed it. Heartbeats that do not change an
+    /// instance's status do not move it.
+    pub fn index(&self, service_name: Option<&str>) -> u64 {
+        match service_name {
+            Some(name) => self
+                .service_indexes
+                .get(name)
+                .map(|index| *index)
+                .unwrap_or(0),
+            None => *self.index.borrow(),
+        }
+    }
+
+    /// Waits until the index differs from `index`, or `timeout` expires, and
+    /// returns the current index.
+    ///
+    /// An index lower than the client's also returns immediately: the
+    /// registry was restarted and the client has to refresh.
+    pub async fn wait_for_index(
+        &self,
+        service_name: Option<&str>,
+        index: u64,
+        timeout: Duration,
+    ) -> u64 {
+        let mut changes = self.index.subscribe();
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            let current = self.index(service_name);
+            if current != index {
+                return current;
+            }
+            match tokio::time::timeout_at(deadline, changes.changed()).await {
+                Ok(Ok(())) => continue,
+                _ => return current,
+            }
+        }
+    }
+
+    /// Recorded events following `sequence`
+    pub fn events_since(&self, sequence: u64) -> EventReplay {
+        self.lock_history().since(sequence)
+    }
+
+    /// Subscribes to new events and returns the recorded events following
+    /// `sequence`, with no gap or overlap between the two.
+    pub fn subscribe_events_since(
+        &self,
+        sequence: u64,
+    ) -> (EventReplay, broadcast::Receiver<ServiceEvent>) {
+        let history = self.lock_history();
+        (history.since(sequence), self.event_sender.subscribe())
+    }
+
+    pub fn get_all_instances(&self) -> anyhow::Result<Vec<ServiceInstance>> {
+        self.store.list_instances()
+    }
+
+    pub fn get_instance(&self, instance_id: &str) -> anyhow::Result<Option<ServiceInstance>> {
+        self.store.get_instance(instance_id)
+    }
+
+    /// Compacts the write-ahead log into a snapshot of the current state.
+    ///
+    /// Does nothing when the registry is not persistent.
+    pub fn snapshot(&self) -> anyhow::Result<()> {
+        let Some(persistence) = &self.persistence else {
+            return Ok(());
+        };
+
+        let snapshot = {
+            let _guard = self.lock_commits();
+            let sequence = persistence.rotate()?;
+            self.capture(sequence)?
+        };
+
+        persistence.write_snapshot(&snapshot)?;
+
+        tracing::debug!(
+            "💾 Registry snapshot written at sequence {} ({} instances)",
+            snapshot.sequence,
+            snapshot.instances.len()
+        );
+        Ok(())
+    }
+
+    /// Routes writes through the given cluster node from now on
+    pub fn attach_cluster(&self, node: &Arc<ClusterNode>) {
+        let _ = self.cluster.set(Arc::downgrade(node));
+    }
+
+    /// Whether this node should run cluster-wide duties such as health
+    /// checks. Always true for a standalone registry.
+    pub fn is_leader(&self) -> bool {
+        self.cluster().is_none_or(|node| node.is_leader())
+    }
+
+    /// Copies the current state, tagged with `sequence`
+    pub fn capture_snapshot(&self, sequence: u64) -> anyhow::Result<RegistrySnapshot> {
+        let _guard = self.lock_commits();
+        self.capture(sequence)
+    }
+
+    /// Replaces the whole registry state with `snapshot`, as received from
+    /// the cluster leader. No events are published.
+    pub fn restore_snapshot(&self, snapshot: &RegistrySnapshot) -> anyhow::Result<()> {
+        let _guard = self.lock_commits();
+
+        let mut writes: Vec<StoreWrite> = self
+            .store
+            .list_instances()?
+            .into_iter()
+            .map(|instance| StoreWrite::RemoveInstance(instance.id))
+            .chain(
+                self.store
+                    .list_services()?
+                    .into_iter()
+                    .map(|service| StoreWrite::RemoveService(service.name)),
+            )
+            .collect();
+        writes.extend(snapshot_writes(snapshot));
+        self.store.write(&writes)?;
+        self.service_indexes.retain(|name, _| {
+            snapshot
+                .services
+                .iter()
+                .any(|service| &service.name == name)
+        });
+
+        // The local log no longer describes the state, start over from here
+        if let Some(persistence) = &self.persistence {
+            let sequence = persistence.rotate()?;
+            persistence.write_snapshot(&self.capture(sequence)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn cluster(&self) -> Option<Arc<ClusterNode>> {
+        self.cluster.get().and_then(Weak::upgrade)
+    }
+
+    /// Submits a mutation: replicated through the cluster when clustered,
+    /// committed locally otherwise.
+    async fn submit(&self, mutation: RegistryMutation) -> anyhow::Result<bool> {
+        match self.cluster() {
+            Some(node) => node.propose(mutation).await,
+            None => self.commit(mutation),
+        }
+    }
+
+    /// Current instances of a service, in registration order
+    fn service_instances(&self, service_name: &str) -> anyhow::Result<Vec<ServiceInstance>> {
+        let Some(record) = self.store.get_service(service_name)? else {
+            return Ok(vec![]);
+        };
+        self.resolve_instances(&record)
+    }
+
+    fn resolve_instances(&self, record: &ServiceRecord) -> anyhow::Result<Vec<ServiceInstance>> {
+        let mut instances = Vec::with_capacity(record.instance_ids.len());
+        for id in &record.instance_ids {
+            if let Some(instance) = self.store.get_instance(id)? {
+                instances.push(instance);
+            }
+        }
+        Ok(instances)
+    }
+
+    /// Builds the API view of a service from the current state of its instances
+    fn service_view(&self, record: ServiceRecord) -> anyhow::Result<Service> {
+        let instances = self.resolve_instances(&record)?;
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in instances.iter().flat_map(|instance| &instance.tags) {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+
+        Ok(Service {
+            name: record.name,
+            instances,
+            tags,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            maintenance: record.maintenance,
+        })
+    }
+
+    fn list_service_views(&self) -> anyhow::Result<Vec<Service>> {
+        self.store
+            .list_services()?
+            .into_iter()
+            .map(|record| self.service_view(record))
             .collect()
     }
+
+    fn capture(&self, sequence: u64) -> anyhow::Result<RegistrySnapshot> {
+        Ok(RegistrySnapshot {
+            sequence,
+            event_sequence: self.lock_history().last_sequence(),
+            taken_at: Utc::now(),
+            services: self.store.list_services()?,
+            instances: self.store.list_instances()?,
+        })
+    }
+
+    /// Records a mutation in the write-ahead log, applies it and publishes
+    /// the resulting events.
+    ///
+    /// Returns `false` when the mutation does not apply (unknown instance or
+    /// service, maintenance already over...), in which case nothing is
+    /// logged. Fails without applying anything when the log cannot be
+    /// written. The writes are computed before the entry is logged, so a
+    /// logged entry always applies, at restart as it did here.
+    pub(crate) fn commit(&self, mutation: RegistryMutation) -> anyhow::Result<bool> {
+        let _guard = self.lock_commits();
+
+        let Some(prepared) = self.prepare(&mutation)? else {
+            return Ok(false);
+        };
+
+        if let Some(persistence) = &self.persistence {
+            persistence
+                .append(&mutation)
+                .map_err(|e| anyhow::anyhow!("Failed to append to registry log: {}", e))?;
+        }
+
+        let events = self.finish(&mutation, prepared)?;
+
+        // Still under the commit lock, so sequence numbers follow the applied order
+        let mut history = self.lock_history();
+        let mut last_sequence = None;
+        for mut event in events {
+            history.record(&mut event);
+            if event.event_type == EventType::ServiceDeregistered {
+                // Waiters see the index drop to 0, as for a service never registered
+                self.service_indexes.remove(&event.service_name);
+            } else {
+                self.service_indexes
+                    .insert(event.service_name.clone(), event.sequence);
+            }
+            last_sequence = Some(event.sequence);
+            let _ = self.event_sender.send(event);
+        }
+        if let Some(sequence) = last_sequence {
+            self.index.send_replace(sequence);
+        }
+
+        Ok(true)
+    }
+
+    /// Applies a mutation to the store and returns the events it produced,
+    /// or `None` if it does not apply.
+    fn apply(&self, mutation: &RegistryMutation) -> anyhow::Result<Option<Vec<ServiceEvent>>> {
+        match self.prepare(mutation)? {
+            Some(prepared) => self.finish(mutation, prepared).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Computes the writes and events of a mutation without touching the
+    /// store, `None` if it does not apply
+    fn prepare(&self, mutation: &RegistryMutation) -> anyhow::Result<Option<PreparedMutation>> {
+        let Some(service_name) = self.target_service(mutation)? else {
+            return Ok(None);
+        };
+
+        let healthy_before = self.healthy_instance_count(&service_name)?;
+        let mut writes = Vec::new();
+        let Some(events) = self.apply_to_store(mutation, &mut writes)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(PreparedMutation {
+            service_name,
+            healthy_before,
+            writes,
+            events,
+        }))
+    }
+
+    /// Writes a prepared mutation to the store and returns its events.
+    ///
+    /// Also reports an existing service losing its last healthy instance, or
+    /// getting one back.
+    fn finish(
+        &self,
+        mutation: &RegistryMutation,
+        prepared: PreparedMutation,
+    ) -> anyhow::Result<Vec<ServiceEvent>> {
+        let PreparedMutation {
+            service_name,
+            healthy_before,
+            writes,
+            mut events,
+        } = prepared;
+
+        self.store.write(&writes)?;
+        let healthy_after = self.healthy_instance_count(&service_name)?;
+
+        // Services appearing or disappearing already have their own events
+        let transition = match (healthy_before, healthy_after) {
+            (None, _) | (_, None) => false,
+            (Some(before), Some(after)) => (before == 0) != (after == 0),
+        };
+
+        if let (true, Some(healthy_after)) = (transition, healthy_after) {
+            events.push(ServiceEvent {
+                event_type: if healthy_after == 0 {
+                    EventType::ServiceUnavailable
+                } else {
+                    EventType::ServiceAvailable
+                },
+                service_name,
+                instance_id: None,
+                sequence: 0,
+                tags: vec![],
+                timestamp: mutation.timestamp(),
+                details: serde_json::json!({
+                    "healthy_instances": healthy_after
+                }),
+            });
+        }
+
+        let mut flaps = self.flaps.lock().unwrap_or_else(|e| e.into_inner());
+        let mut flapping = Vec::new();
+        for event in &events {
+            let Some(instance_id) = &event.instance_id else {
+                continue;
+            };
+            match event.event_type {
+                EventType::InstanceStatusChanged | EventType::HealthCheckRecovered
+                    if flaps.record(instance_id, event.timestamp) =>
+                {
+                    flapping.push(ServiceEvent {
+                        event_type: EventType::InstanceFlapping,
+                        sequence: 0,
+                        ..event.clone()
+                    });
+                }
+                EventType::InstanceDeregistered | EventType::ServiceDeregistered => {
+                    flaps.forget(instance_id)
+                }
+                _ => {}
+            }
+        }
+        events.extend(flapping);
+
+        Ok(events)
+    }
+
+    /// Service a mutation applies to, `None` if its target does not exist
+    fn target_service(&self, mutation: &RegistryMutation) -> anyhow::Result<Option<String>> {
+        Ok(match mutation {
+            RegistryMutation::Register { instance } => Some(instance.service_name.clone()),
+            RegistryMutation::MaintenanceStart { service_name, .. }
+            | RegistryMutation::MaintenanceEnd { service_name, .. } => self
+                .store
+                .get_service(service_name)?
+                .map(|service| service.name),
+            _ => mutation
+                .instance_id()
+                .map(|id| self.store.get_instance(id))
+                .transpose()?
+                .flatten()
+                .map(|instance| instance.service_name),
+        })
+    }
+
+    /// Number of healthy instances, `None` if the service does not exist
+    fn healthy_instance_count(&self, service_name: &str) -> anyhow::Result<Option<usize>> {
+        let Some(record) = self.store.get_service(service_name)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            self.resolve_instances(&record)?
+                .iter()
+                .filter(|instance| matches!(instance.status, InstanceStatus::Up))
+                .count(),
+        ))
+    }
+
+    /// Computes the writes of a mutation, added to `writes`, and the events
+    /// it produces
+    fn apply_to_store(
+        &self,
+        mutation: &RegistryMutation,
+        writes: &mut Vec<StoreWrite>,
+    ) -> anyhow::Result<Option<Vec<ServiceEvent>>> {
+        match mutation {
+            RegistryMutation::Register { instance } => {
+                let mut instance = instance.as_ref().clone();
+                let existing = self.store.get_service(&instance.service_name)?;
+                let service_existed = existing.is_some();
+
+                if let Some(maintenance) = existing.as_ref().and_then(|s| s.maintenance.as_ref()) {
+                    enter_maintenance(&mut instance, maintenance, true);
+                }
+                writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+
+                let service = match existing {
+                    Some(mut service) => {
+                        if !service.instance_ids.contains(&instance.id) {
+                            service.instance_ids.push(instance.id.clone());
+                        }
+                        service.updated_at = instance.registered_at;
+                        service
+                    }
+                    None => ServiceRecord {
+                        name: instance.service_name.clone(),
+                        instance_ids: vec![instance.id.clone()],
+                        created_at: instance.registered_at,
+                        updated_at: instance.registered_at,
+                        maintenance: None,
+                    },
+                };
+                writes.push(StoreWrite::PutService(service.clone()));
+
+                Ok(Some(vec![ServiceEvent {
+                    event_type: if service_existed {
+                        EventType::InstanceRegistered
+                    } else {
+                        EventType::ServiceRegistered
+                    },
+                    service_name: instance.service_name.clone(),
+                    instance_id: Some(instance.id.clone()),
+                    sequence: 0,
+                    tags: instance.tags.clone(),
+                    timestamp: instance.registered_at,
+                    details: serde_json::json!({
+                        "host": instance.host,
+                        "port": instance.port,
+                        "tags": instance.tags
+                    }),
+                }]))
+            }
+            RegistryMutation::Deregister {
+                instance_id,
+                timestamp,
+            } => {
+                let Some(instance) = self.store.get_instance(instance_id)? else {
+                    return Ok(None);
+                };
+                writes.push(StoreWrite::RemoveInstance(instance_id.clone()));
+                let mut service_removed = false;
+
+                if let Some(mut service) = self.store.get_service(&instance.service_name)? {
+                    service.instance_ids.retain(|id| id != instance_id);
+                    service.updated_at = *timestamp;
+
+                    if service.instance_ids.is_empty() {
+                        writes.push(StoreWrite::RemoveService(instance.service_name.clone()));
+                        service_removed = true;
+                    } else {
+                        writes.push(StoreWrite::PutService(service.clone()));
+                    }
+                }
+
+                Ok(Some(vec![ServiceEvent {
+                    event_type: if service_removed {
+                        EventType::ServiceDeregistered
+                    } else {
+                        EventType::InstanceDeregistered
+                    },
+                    service_name: instance.service_name.clone(),
+                    instance_id: Some(instance_id.clone()),
+                    sequence: 0,
+                    tags: instance.tags.clone(),
+                    timestamp: *timestamp,
+                    details: serde_json::json!({
+                        "host": instance.host,
+                        "port": instance.port
+                    }),
+                }]))
+            }
+            RegistryMutation::Heartbeat {
+                instance_id,
+                timestamp,
+            } => {
+                let Some(mut instance) = self.store.get_instance(instance_id)? else {
+                    return Ok(None);
+                };
+                let previous_status = instance.status.clone();
+                instance.last_heartbeat = *timestamp;
+
+                // The instance is back once the window ends
+                if let Some(maintenance) = &mut instance.maintenance {
+                    maintenance.previous_status = Some(InstanceStatus::Up);
+                    writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+                    return Ok(Some(vec![]));
+                }
+
+                if matches!(instance.status, InstanceStatus::Up) {
+                    writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+                    return Ok(Some(vec![]));
+                }
+
+                instance.status = InstanceStatus::Up;
+                instance.last_status_change = *timestamp;
+                writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+
+                Ok(Some(vec![ServiceEvent {
+                    event_type: EventType::HealthCheckRecovered,
+                    service_name: instance.service_name.clone(),
+                    instance_id: Some(instance_id.clone()),
+                    sequence: 0,
+                    tags: instance.tags.clone(),
+                    timestamp: *timestamp,
+                    details: serde_json::json!({
+                        "previous_status": format!("{:?}", previous_status),
+                        "new_status": "Up"
+                    }),
+                }]))
+            }
+            RegistryMutation::StatusChange {
+                instance_id,
+                status,
+                timestamp,
+            } => {
+                let Some(mut instance) = self.store.get_instance(instance_id)? else {
+                    return Ok(None);
+                };
+
+                // Maintenance wins; the status applies once the window ends
+                if let Some(maintenance) = &mut instance.maintenance {
+                    maintenance.previous_status = Some(status.clone());
+                    writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+                    return Ok(Some(vec![]));
+                }
+
+                let previous_status = instance.status.clone();
+                instance.status = status.clone();
+                instance.last_status_change = *timestamp;
+                writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+
+                Ok(Some(vec![ServiceEvent {
+                    event_type: EventType::InstanceStatusChanged,
+                    service_name: instance.service_name.clone(),
+                    instance_id: Some(instance_id.clone()),
+                    sequence: 0,
+                    tags: instance.tags.clone(),
+                    timestamp: *timestamp,
+                    details: serde_json::json!({
+                        "previous_status": format!("{:?}", previous_status),
+                        "new_status": format!("{:?}", status)
+                    }),
+                }]))
+            }
+            RegistryMutation::MaintenanceStart {
+                service_name,
+                instance_id: Some(instance_id),
+                maintenance,
+            } => {
+                let Some(mut instance) = self
+                    .store
+                    .get_instance(instance_id)?
+                    .filter(|instance| &instance.service_name == service_name)
+                else {
+                    return Ok(None);
+                };
+                enter_maintenance(&mut instance, maintenance, false);
+                writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+
+                Ok(Some(vec![maintenance_event(
+                    EventType::MaintenanceStarted,
+                    service_name,
+                    Some(&instance),
+                    maintenance.started_at,
+                    serde_json::json!({
+                        "reason": maintenance.reason,
+                        "author": maintenance.author,
+                        "expires_at": maintenance.expires_at
+                    }),
+                )]))
+            }
+            RegistryMutation::MaintenanceStart {
+                service_name,
+                instance_id: None,
+                maintenance,
+            } => {
+                let Some(mut service) = self.store.get_service(service_name)? else {
+                    return Ok(None);
+                };
+                service.maintenance = Some(maintenance.clone());
+                writes.push(StoreWrite::PutService(service.clone()));
+
+                let mut instances = 0;
+                for mut instance in self.resolve_instances(&service)? {
+                    // An instance's own window is kept
+                    if instance
+                        .maintenance
+                        .as_ref()
+                        .is_some_and(|m| !m.service_wide)
+                    {
+                        continue;
+                    }
+                    enter_maintenance(&mut instance, maintenance, true);
+                    writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+                    instances += 1;
+                }
+
+                Ok(Some(vec![maintenance_event(
+                    EventType::MaintenanceStarted,
+                    service_name,
+                    None,
+                    maintenance.started_at,
+                    serde_json::json!({
+                        "reason": maintenance.reason,
+                        "author": maintenance.author,
+                        "expires_at": maintenance.expires_at,
+                        "instances": instances
+                    }),
+                )]))
+            }
+            RegistryMutation::MaintenanceEnd {
+                service_name,
+                instance_id: Some(instance_id),
+                expired,
+                timestamp,
+            } => {
+                let Some(mut instance) = self
+                    .store
+                    .get_instance(instance_id)?
+                    .filter(|instance| &instance.service_name == service_name)
+                else {
+                    return Ok(None);
+                };
+                let Some(ended) = instance.maintenance.clone() else {
+                    return Ok(None);
+                };
+                leave_maintenance(&mut instance, *timestamp);
+
+                // Ending its own window puts it back under the service's
+                if !ended.service_wide {
+                    let service = self.store.get_service(service_name)?;
+                    if let Some(maintenance) = service.and_then(|s| s.maintenance) {
+                        enter_maintenance(&mut instance, &maintenance, true);
+                    }
+                }
+                writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+
+                Ok(Some(vec![maintenance_event(
+                    EventType::MaintenanceEnded,
+                    service_name,
+                    Some(&instance),
+                    *timestamp,
+                    serde_json::json!({
+                        "reason": ended.reason,
+                        "author": ended.author,
+                        "expired": expired,
+                        "status": format!("{:?}", instance.status)
+                    }),
+                )]))
+            }
+            RegistryMutation::MaintenanceEnd {
+                service_name,
+                instance_id: None,
+                expired,
+                timestamp,
+            } => {
+                let Some(mut service) = self.store.get_service(service_name)? else {
+                    return Ok(None);
+                };
+                let Some(ended) = service.maintenance.take() else {
+                    return Ok(None);
+                };
+                writes.push(StoreWrite::PutService(service.clone()));
+
+                let mut instances = 0;
+                for mut instance in self.resolve_instances(&service)? {
+                    if instance
+                        .maintenance
+                        .as_ref()
+                        .is_some_and(|m| m.service_wide)
+                    {
+                        leave_maintenance(&mut instance, *timestamp);
+                        writes.push(StoreWrite::PutInstance(Box::new(instance.clone())));
+                        instances += 1;
+                    }
+                }
+
+                Ok(Some(vec![maintenance_event(
+                    EventType::MaintenanceEnded,
+                    service_name,
+                    None,
+                    *timestamp,
+                    serde_json::json!({
+                        "reason": ended.reason,
+                        "author": ended.author,
+                        "expired": expired,
+                        "instances": instances
+                    }),
+                )]))
+            }
+        }
+    }
+
+    fn lock_history(&self) -> std::sync::MutexGuard<'_, EventHistory> {
+        self.event_history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_commits(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.commit_lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Mutation whose writes and events are computed but not yet stored
+struct PreparedMutation {
+    service_name: String,
+    /// Healthy instances of the service before the mutation
+    healthy_before: Option<usize>,
+    writes: Vec<StoreWrite>,
+    events: Vec<ServiceEvent>,
+}
+
+/// Writes storing the services and instances of a snapshot
+fn snapshot_writes(snapshot: &RegistrySnapshot) -> Vec<StoreWrite> {
+    snapshot
+        .services
+        .iter()
+        .cloned()
+        .map(StoreWrite::PutService)
+        .chain(
+            snapshot
+                .instances
+                .iter()
+                .map(|instance| StoreWrite::PutInstance(Box::new(instance.clone()))),
+        )
+        .collect()
+}
+
+/// Puts an instance in `maintenance`, keeping the status it returns to
+fn enter_maintenance(
+    instance: &mut ServiceInstance,
+    maintenance: &Maintenance,
+    service_wide: bool,
+) {
+    let previous_status = match &instance.maintenance {
+        Some(current) => current.previous_status.clone(),
+        None => Some(instance.status.clone()),
+    };
+    instance.maintenance = Some(Maintenance {
+        service_wide,
+        previous_status,
+        ..maintenance.clone()
+    });
+
+    if !matches!(instance.status, InstanceStatus::OutOfService) {
+        instance.status = InstanceStatus::OutOfService;
+        instance.last_status_change = maintenance.started_at;
+    }
+}
+
+/// Ends the maintenance of an instance, restoring its status
+fn leave_maintenance(instance: &mut ServiceInstance, timestamp: DateTime<Utc>) {
+    let Some(maintenance) = instance.maintenance.take() else {
+        return;
+    };
+    let status = maintenance.previous_status.unwrap_or(InstanceStatus::Up);
+    if status != instance.status {
+        instance.status = status;
+        instance.last_status_change = timestamp;
+    }
+}
+
+fn maintenance_event(
+    event_type: EventType,
+    service_name: &str,
+    instance: Option<&ServiceInstance>,
+    timestamp: DateTime<Utc>,
+    details: serde_json::Value,
+) -> ServiceEvent {
+    ServiceEvent {
+        event_type,
+        service_name: service_name.to_string(),
+        instance_id: instance.map(|instance| instance.id.clone()),
+        sequence: 0,
+        tags: instance
+            .map(|instance| instance.tags.clone())
+            .unwrap_or_default(),
+        timestamp,
+        details,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register_request(service_name: &str, port: u16, tags: &[&str]) -> RegisterServiceRequest {
+        RegisterServiceRequest {
+            service_name: service_name.to_string(),
+            host: "localhost".to_string(),
+            port,
+            secure: None,
+            metadata: None,
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            health_check: None,
+        }
+    }
+
+    fn healthy_only() -> DiscoveryQuery {
+        DiscoveryQuery {
+            healthy_only: Some(true),
+            tags: None,
+            limit: None,
+            strategy: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_change_visible_everywhere() {
+        let registry = ServiceRegistry::new();
+        let down = registry
+            .register_instance(register_request("users", 3000, &["api"]))
+            .await
+            .unwrap();
+        let up = registry
+            .register_instance(register_request("users", 3001, &["api"]))
+            .await
+            .unwrap();
+
+        registry
+            .update_instance_status(&down.id, InstanceStatus::Down)
+            .await
+            .unwrap();
+
+        let discovered = registry
+            .get_service_instances("users", &healthy_only())
+            .await
+            .unwrap();
+        assert_eq!(discovered.len(), 1);
+        assert_eq!(discovered[0].id, up.id);
+
+        let service = registry.get_service("users").await.unwrap().unwrap();
+        let listed = service.instances.iter().find(|i| i.id == down.id).unwrap();
+        assert!(matches!(listed.status, InstanceStatus::Down));
+
+        let listed = &registry.get_all_services().await.unwrap()[0];
+        assert!(listed
+            .instances
+            .iter()
+            .any(|i| i.id == down.id && matches!(i.status, InstanceStatus::Down)));
+
+        let stats = registry.get_stats().await.unwrap();
+        assert_eq!(stats.total_instances, 2);
+        assert_eq!(stats.healthy_instances, 1);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_updates_service_view() {
+        let registry = ServiceRegistry::new();
+        let instance = registry
+            .register_instance(register_request("users", 3000, &["api"]))
+            .await
+            .unwrap();
+        registry
+            .update_instance_status(&instance.id, InstanceStatus::Down)
+            .await
+            .unwrap();
+
+        let mut events = registry.subscribe_events();
+        assert!(registry.update_heartbeat(&instance.id).await.unwrap());
+
+        let event = events.try_recv().unwrap();
+        assert!(matches!(event.event_type, EventType::HealthCheckRecovered));
+
+        let service = registry.get_service("users").await.unwrap().unwrap();
+        assert!(matches!(service.instances[0].status, InstanceStatus::Up));
+        assert!(service.instances[0].last_heartbeat > instance.last_heartbeat);
+        assert_eq!(
+            registry
+                .get_service_instances("users", &healthy_only())
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_service_tags_follow_instances() {
+        let registry = ServiceRegistry::new();
+        let first = registry
+            .register_instance(register_request("users", 3000, &["api"]))
+            .await
+            .unwrap();
+        registry
+            .register_instance(register_request("users", 3001, &["api", "v2"]))
+            .await
+            .unwrap();
+
+        assert_eq!(registry.get_services_by_tag("v2").await.unwrap().len(), 1);
+
+        registry.deregister_instance(&first.id).await.unwrap();
+        let service = registry.get_service("users").await.unwrap().unwrap();
+        assert_eq!(service.instances.len(), 1);
+        assert_eq!(service.tags, vec!["api", "v2"]);
+
+        assert!(registry.get_service("orders").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_query_wakes_on_service_change() {
+        let registry = Arc::new(ServiceRegistry::new());
+        let instance = registry
+            .register_instance(register_request("users", 3000, &[]))
+            .await
+            .unwrap();
+        registry
+            .register_instance(register_request("orders", 4000, &[]))
+            .await
+            .unwrap();
+
+        let index = registry.index(Some("users"));
+        assert_eq!(index, 1);
+        assert_eq!(registry.index(None), 2);
+
+        // Nothing changes for users: the wait expires with the same index
+        registry
+            .register_instance(register_request("orders", 4001, &[]))
+            .await
+            .unwrap();
+        let unchanged = registry
+            .wait_for_index(Some("users"), index, Duration::from_millis(50))
+            .await;
+        assert_eq!(unchanged, index);
+
+        let waiter = {
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                registry
+                    .wait_for_index(Some("users"), index, Duration::from_secs(5))
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        registry
+            .update_instance_status(&instance.id, InstanceStatus::Down)
+            .await
+            .unwrap();
+
+        // Status change, then the service losing its last healthy instance
+        assert_eq!(waiter.await.unwrap(), 5);
+        assert_eq!(registry.index(Some("users")), 5);
+
+        // A client ahead of the registry (e.g. after a restart) is not held
+        let reset = registry
+            .wait_for_index(Some("users"), 99, Duration::from_secs(5))
+            .await;
+        assert_eq!(reset, 5);
+
+        // A deleted service leaves the index
+        registry.deregister_instance(&instance.id).await.unwrap();
+        assert_eq!(registry.index(Some("users")), 0);
+        assert!(!registry.service_indexes.contains_key("users"));
+    }
+
+    #[tokio::test]
+    async fn test_service_availability_events() {
+        let registry = ServiceRegistry::new();
+        let first = registry
+            .register_instance(register_request("users", 3000, &[]))
+            .await
+            .unwrap();
+        let second = registry
+            .register_instance(register_request("users", 3001, &[]))
+            .await
+            .unwrap();
+
+        let mut events = registry.subscribe_events();
+        registry
+            .update_instance_status(&first.id, InstanceStatus::Down)
+            .await
+            .unwrap();
+        registry
+            .update_instance_status(&second.id, InstanceStatus::Down)
+            .await
+            .unwrap();
+        registry.update_heartbeat(&first.id).await.unwrap();
+
+        let types: Vec<EventType> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| event.event_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                EventType::InstanceStatusChanged,
+                EventType::InstanceStatusChanged,
+                EventType::ServiceUnavailable,
+                EventType::HealthCheckRecovered,
+                EventType::ServiceAvailable,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flapping_instance_reported() {
+        let registry = ServiceRegistry::new().with_flap_detection(3, Duration::from_secs(60));
+        let instance = registry
+            .register_instance(register_request("users", 3000, &[]))
+            .await
+            .unwrap();
+
+        let mut events = registry.subscribe_events();
+        registry
+            .update_instance_status(&instance.id, InstanceStatus::Down)
+            .await
+            .unwrap();
+        registry.update_heartbeat(&instance.id).await.unwrap();
+        registry
+            .update_instance_status(&instance.id, InstanceStatus::Down)
+            .await
+            .unwrap();
+
+        let events: Vec<ServiceEvent> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        let flapping: Vec<&ServiceEvent> = events
+            .iter()
+            .filter(|event| event.event_type == EventType::InstanceFlapping)
+            .collect();
+        assert_eq!(flapping.len(), 1);
+        assert_eq!(
+            flapping[0].instance_id.as_deref(),
+            Some(instance.id.as_str())
+        );
+        assert_eq!(flapping[0].details["new_status"], "Down");
+        assert_eq!(
+            events.last().unwrap().event_type,
+            EventType::InstanceFlapping
+        );
+    }
+
+    fn maintenance(reason: &str, expires_at: Option<DateTime<Utc>>) -> Maintenance {
+        Maintenance {
+            reason: reason.to_string(),
+            author: "ops".to_string(),
+            started_at: Utc::now(),
+            expires_at,
+            service_wide: false,
+            previous_status: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_instance_maintenance_survives_heartbeats() {
+        let registry = ServiceRegistry::new();
+        let instance = registry
+            .register_instance(register_request("users", 3000, &[]))
+            .await
+            .unwrap();
+
+        let mut events = registry.subscribe_events();
+        assert!(registry
+            .start_maintenance("users", Some(&instance.id), maintenance("upgrade", None))
+            .await
+            .unwrap());
+        assert!(registry
+            .get_service_instances("users", &healthy_only())
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Neither heartbeats nor status updates end the window
+        registry.update_heartbeat(&instance.id).await.unwrap();
+        registry
+            .update_instance_status(&instance.id, InstanceStatus::Down)
+            .await
+            .unwrap();
+        let current = registry.get_instance(&instance.id).unwrap().unwrap();
+        assert_eq!(current.status, InstanceStatus::OutOfService);
+        let window = current.maintenance.unwrap();
+        assert_eq!(window.reason, "upgrade");
+        assert_eq!(window.previous_status, Some(InstanceStatus::Down));
+
+        // The last status reported applies once the window ends
+        assert!(registry
+            .end_maintenance("users", Some(&instance.id), false)
+            .await
+            .unwrap());
+        assert!(!registry
+            .end_maintenance("users", Some(&instance.id), false)
+            .await
+            .unwrap());
+        let current = registry.get_instance(&instance.id).unwrap().unwrap();
+        assert_eq!(current.status, InstanceStatus::Down);
+        assert!(current.maintenance.is_none());
+
+        let types: Vec<EventType> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| event.event_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                EventType::MaintenanceStarted,
+                EventType::ServiceUnavailable,
+                EventType::MaintenanceEnded,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_service_maintenance() {
+        let registry = ServiceRegistry::new();
+        let first = registry
+            .register_instance(register_request("users", 3000, &[]))
+            .await
+            .unwrap();
+        let second = registry
+            .register_instance(register_request("users", 3001, &[]))
+            .await
+            .unwrap();
+
+        registry
+            .start_maintenance("users", Some(&first.id), maintenance("disk swap", None))
+            .await
+            .unwrap();
+        assert!(registry
+            .start_maintenance("users", None, maintenance("migration", None))
+            .await
+            .unwrap());
+        assert!(!registry
+            .start_maintenance("orders", None, maintenance("migration", None))
+            .await
+            .unwrap());
+
+        let service = registry.get_service("users").await.unwrap().unwrap();
+        assert_eq!(service.maintenance.unwrap().reason, "migration");
+        assert!(service
+            .instances
+            .iter()
+            .all(|instance| instance.status == InstanceStatus::OutOfService));
+
+        // New instances join the service's window
+        let third = registry
+            .register_instance(register_request("users", 3002, &[]))
+            .await
+            .unwrap();
+        assert_eq!(third.status, InstanceStatus::OutOfService);
+        assert!(third.maintenance.unwrap().service_wide);
+
+        // Instances with their own window keep it
+        assert!(registry
+            .end_maintenance("users", None, false)
+            .await
+            .unwrap());
+        let first = registry.get_instance(&first.id).unwrap().unwrap();
+        assert_eq!(first.maintenance.unwrap().reason, "disk swap");
+        assert_eq!(
+            registry.get_instance(&second.id).unwrap().unwrap().status,
+            InstanceStatus::Up
+        );
+        assert_eq!(
+            registry.get_instance(&third.id).unwrap().unwrap().status,
+            InstanceStatus::Up
+        );
+        assert!(registry
+            .get_service("users")
+            .await
+            .unwrap()
+            .unwrap()
+            .maintenance
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expired_maintenance_ends() {
+        let registry = ServiceRegistry::new();
+        let instance = registry
+            .register_instance(register_request("users", 3000, &[]))
+            .await
+            .unwrap();
+        let past = Utc::now() - chrono::Duration::seconds(1);
+        let future = Utc::now() + chrono::Duration::hours(1);
+
+        registry
+            .start_maintenance(
+                "users",
+                Some(&instance.id),
+                maintenance("reboot", Some(past)),
+            )
+            .await
+            .unwrap();
+        registry
+            .start_maintenance("users", None, maintenance("migration", Some(future)))
+            .await
+            .unwrap();
+
+        let mut events = registry.subscribe_events();
+        registry.end_expired_maintenance().await.unwrap();
+
+        // The instance falls back to the service's window, still running
+        let current = registry.get_instance(&instance.id).unwrap().unwrap();
+        assert_eq!(current.status, InstanceStatus::OutOfService);
+        assert_eq!(current.maintenance.unwrap().reason, "migration");
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.event_type, EventType::MaintenanceEnded);
+        assert_eq!(event.instance_id.as_deref(), Some(instance.id.as_str()));
+        assert_eq!(event.details["expired"], true);
+        assert!(events.try_recv().is_err());
+    }
 }
@@ -0,0 +1,256 @@
+//! Server-Sent Events stream of registry events
+//!
+//! Used by `/api/events` and `/api/services/{name}/watch` for consumers that
+//! cannot use WebSockets. Every event is sent with its sequence number as
+//! SSE id, so a reconnecting client sending `Last-Event-ID` resumes right
+//! after the last event it saw. Two control events can appear in the stream:
+//!
+//! - `resync`: the requested events are no longer in the history, re-list
+//!   the registry before relying on the stream
+//! - `events_dropped`: the client was too slow and missed events
+
+use axum::{
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::stream::{self, Stream, StreamExt};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::events::{EventFilter, EventReplay};
+use crate::models::ServiceEvent;
+use crate::registry::ServiceRegistry;
+
+/// Header sent by `EventSource` clients when reconnecting
+pub const LAST_EVENT_ID: &str = "last-event-id";
+
+/// Reads the resume cursor from the `Last-Event-ID` header
+pub fn last_event_id(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Streams the registry events matching `filter`, starting after `since`
+/// when given, otherwise with the next event.
+pub fn event_stream(
+    registry: &Arc<ServiceRegistry>,
+    since: Option<u64>,
+    filter: EventFilter,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (replay, receiver) = match since {
+        Some(sequence) => {
+            let (replay, receiver) = registry.subscribe_events_since(sequence);
+            (Some(replay), receiver)
+        }
+        None => (None, registry.subscribe_events()),
+    };
+
+    let mut last_sequence = since.unwrap_or(0);
+    let backlog: Vec<Event> = match replay {
+        Some(EventReplay::Events {
+            events,
+            last_sequence: latest,
+        }) => {
+            last_sequence = latest;
+            events
+                .iter()
+                .filter(|event| filter.matches(event))
+                .map(to_sse_event)
+                .collect()
+        }
+        Some(EventReplay::TooOld {
+            oldest_sequence,
+            last_sequence: latest,
+        }) => {
+            last_sequence = latest;
+            vec![Event::default()
+                .event("resync")
+                .id(latest.to_string())
+                .json_data(serde_json::json!({
+                    "oldest_sequence": oldest_sequence,
+                    "last_sequence": latest
+                }))
+                .unwrap_or_default()]
+        }
+        None => vec![],
+    };
+
+    let live = stream::unfold(
+        (receiver, last_sequence, filter),
+        |(mut receiver, last_sequence, filter)| async move {
+            loop {
+                match receiver.recv().await {
+                    // Already sent from the history
+                    Ok(event) if event.sequence <= last_sequence => continue,
+                    Ok(event) if !filter.matches(&event) => {
+                        return Some((None, (receiver, event.sequence, filter)))
+                    }
+                    Ok(event) => {
+                        let sequence = event.sequence;
+                        return Some((Some(to_sse_event(&event)), (receiver, sequence, filter)));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        tracing::warn!("📡 SSE client lagging, {} events dropped", count);
+                        let notice = Event::default()
+                            .event("events_dropped")
+                            .json_data(serde_json::json!({ "count": count }))
+                            .unwrap_or_default();
+                        return Some((Some(notice), (receiver, last_sequence, filter)));
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        },
+    )
+    .filter_map(|event| async move { event });
+
+    let events = stream::iter(backlog).chain(live).map(Ok);
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+fn to_sse_event(event: &ServiceEvent) -> Event {
+    Event::default()
+        .id(event.sequence.to_string())
+        .json_data(event)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::RegisterServiceRequest;
+    use crate::registry::ServiceRegistry;
+    use crate::{api, AppState};
+    use axum::{routing::get, Router};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn start_server(registry: Arc<ServiceRegistry>) -> String {
+        let state = AppState::for_tests(registry);
+        let app = Router::new()
+            .route("/api/events", get(api::get_events))
+            .route("/api/services/{name}/watch", get(api::watch_service))
+            .with_state(state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        format!("http://{}", address)
+    }
+
+    /// Reads the stream until `expected` shows up, returning everything read
+    async fn read_until(mut response: reqwest::Response, expected: &str) -> String {
+        let mut body = String::new();
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !body.contains(expected) {
+                let chunk = response.chunk().await.unwrap().expect("stream ended");
+                body.push_str(&String::from_utf8_lossy(&chunk));
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("{:?} not received, got {:?}", expected, body));
+        body
+    }
+
+    async fn register(registry: &ServiceRegistry, service_name: &str) {
+        registry
+            .register_instance(RegisterServiceRequest {
+                service_name: service_name.to_string(),
+                host: "localhost".to_string(),
+                port: 3000,
+                secure: None,
+                metadata: None,
+                tags: None,
+                health_check: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_last_event_id() {
+        let registry = Arc::new(ServiceRegistry::new());
+        let url = start_server(registry.clone()).await;
+        register(&registry, "users").await;
+        register(&registry, "orders").await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/api/events", url))
+            .header("Last-Event-ID", "1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        let body = read_until(response, "id: 2").await;
+        assert!(body.contains("\"service_name\":\"orders\""));
+        assert!(!body.contains("id: 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_watch_streams_live_service_events() {
+        let registry = Arc::new(ServiceRegistry::new());
+        let url = start_server(registry.clone()).await;
+
+        let response = reqwest::get(format!("{}/api/services/orders/watch", url))
+            .await
+            .unwrap();
+        register(&registry, "users").await;
+        register(&registry, "orders").await;
+
+        let body = read_until(response, "id: 2").await;
+        assert!(!body.contains("id: 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_cursor_asks_for_resync() {
+        let registry = Arc::new(ServiceRegistry::new());
+        let url = start_server(registry.clone()).await;
+        register(&registry, "users").await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/api/events", url))
+            .header("Last-Event-ID", "42")
+            .send()
+            .await
+            .unwrap();
+
+        let body = read_until(response, "event: resync").await;
+        assert!(body.contains("\"last_sequence\":1"));
+    }
+
+    #[tokio::test]
+    async fn test_json_replay_since_cursor() {
+        let registry = Arc::new(ServiceRegistry::new().with_event_history(2));
+        let url = start_server(registry.clone()).await;
+        for service_name in ["users", "orders", "users"] {
+            register(&registry, service_name).await;
+        }
+
+        let page: serde_json::Value =
+            reqwest::get(format!("{}/api/events?since=1&service=users", url))
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+        assert_eq!(page["resync"], false);
+        assert_eq!(page["last_sequence"], 3);
+        assert_eq!(page["events"].as_array().unwrap().len(), 1);
+        assert_eq!(page["events"][0]["sequence"], 3);
+        assert_eq!(page["events"][0]["event_type"], "InstanceRegistered");
+
+        let page: serde_json::Value = reqwest::get(format!("{}/api/events?since=0", url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(page["resync"], true);
+        assert_eq!(page["oldest_sequence"], 2);
+    }
+}
@@ -0,0 +1,86 @@
+//! In-memory registry store
+
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{RegistryStore, StoreWrite};
+use crate::models::{ServiceInstance, ServiceRecord};
+
+/// `DashMap`-backed store, the default backend
+#[derive(Default)]
+pub struct MemoryStore {
+    services: DashMap<String, ServiceRecord>,
+    instances: DashMap<String, ServiceInstance>,
+    round_robin_counters: DashMap<String, AtomicUsize>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RegistryStore for MemoryStore {
+    fn get_service(&self, name: &str) -> anyhow::Result<Option<ServiceRecord>> {
+        Ok(self.services.get(name).map(|entry| entry.value().clone()))
+    }
+
+    fn list_services(&self) -> anyhow::Result<Vec<ServiceRecord>> {
+        Ok(self
+            .services
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect())
+    }
+
+    fn service_count(&self) -> anyhow::Result<usize> {
+        Ok(self.services.len())
+    }
+
+    fn get_instance(&self, id: &str) -> anyhow::Result<Option<ServiceInstance>> {
+        Ok(self.instances.get(id).map(|entry| entry.value().clone()))
+    }
+
+    fn list_instances(&self) -> anyhow::Result<Vec<ServiceInstance>> {
+        Ok(self
+            .instances
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect())
+    }
+
+    fn instance_count(&self) -> anyhow::Result<usize> {
+        Ok(self.instances.len())
+    }
+
+    fn write(&self, writes: &[StoreWrite]) -> anyhow::Result<()> {
+        for write in writes {
+            match write {
+                StoreWrite::PutService(service) => {
+                    self.services.insert(service.name.clone(), service.clone());
+                }
+                StoreWrite::RemoveService(name) => {
+                    self.services.remove(name);
+                    self.round_robin_counters.remove(name);
+                }
+                StoreWrite::PutInstance(instance) => {
+                    self.instances
+                        .insert(instance.id.clone(), instance.as_ref().clone());
+                }
+                StoreWrite::RemoveInstance(id) => {
+                    self.instances.remove(id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn next_round_robin(&self, service_name: &str) -> anyhow::Result<usize> {
+        let counter = self
+            .round_robin_counters
+            .entry(service_name.to_string())
+            .or_insert_with(|| AtomicUsize::new(0));
+
+        Ok(counter.fetch_add(1, Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,79 @@
+//! Storage backends for the service registry
+//!
+//! The registry keeps its events, filtering and load-balancing logic to
+//! itself and delegates raw storage of services, instances and round-robin
+//! counters to a [`RegistryStore`]. Two backends are available:
+//!
+//! - `memory`: `DashMap`-based, fastest, optionally made durable by the
+//!   write-ahead log from the `persistence` module
+//! - `sqlite`: embedded SQLite database in the data directory, durable on its own
+
+pub mod memory;
+pub mod sqlite;
+
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::models::{ServiceInstance, ServiceRecord};
+use crate::StorageConfig;
+
+/// Storage backend selected in the `[storage]` section
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Memory,
+    Sqlite,
+}
+
+/// One write of a [`RegistryStore::write`] batch
+#[derive(Debug, Clone)]
+pub enum StoreWrite {
+    PutService(ServiceRecord),
+    RemoveService(String),
+    PutInstance(Box<ServiceInstance>),
+    RemoveInstance(String),
+}
+
+/// Raw storage for registry state.
+///
+/// Implementations only store and return records; all writes are serialized
+/// by the registry, so read-modify-write sequences need no extra locking.
+pub trait RegistryStore: Send + Sync {
+    fn get_service(&self, name: &str) -> anyhow::Result<Option<ServiceRecord>>;
+    fn list_services(&self) -> anyhow::Result<Vec<ServiceRecord>>;
+    fn service_count(&self) -> anyhow::Result<usize>;
+
+    fn get_instance(&self, id: &str) -> anyhow::Result<Option<ServiceInstance>>;
+    fn list_instances(&self) -> anyhow::Result<Vec<ServiceInstance>>;
+    fn instance_count(&self) -> anyhow::Result<usize>;
+
+    /// Applies the writes of one mutation in order, all or none of them
+    fn write(&self, writes: &[StoreWrite]) -> anyhow::Result<()>;
+
+    /// Returns the current round-robin position for a service and advances it
+    fn next_round_robin(&self, service_name: &str) -> anyhow::Result<usize>;
+}
+
+/// Rejects settings the selected backend would ignore
+pub fn validate_config(config: &StorageConfig) -> anyhow::Result<()> {
+    if config.backend == StorageBackend::Sqlite && config.enabled {
+        anyhow::bail!(
+            "storage.enabled only applies to the memory backend, the sqlite backend is always persistent"
+        );
+    }
+    Ok(())
+}
+
+/// Creates the store selected by the storage configuration
+pub fn open_store(config: &StorageConfig) -> anyhow::Result<Box<dyn RegistryStore>> {
+    match config.backend {
+        StorageBackend::Memory => Ok(Box::new(MemoryStore::new())),
+        StorageBackend::Sqlite => {
+            let path = Path::new(&config.data_dir).join(sqlite::DATABASE_FILE);
+            Ok(Box::new(SqliteStore::open(&path)?))
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Embedded SQLite registry store
+
+use dashmap::DashMap;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use super::{RegistryStore, StoreWrite};
+use crate::models::{ServiceInstance, ServiceRecord};
+
+/// Database file created in the storage data directory
+pub const DATABASE_FILE: &str = "registry.db";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS services (
+        name TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS instances (
+        id TEXT PRIMARY KEY,
+        service_name TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS instances_service_name ON instances (service_name);
+";
+
+/// SQLite-backed store.
+///
+/// Records are stored as JSON documents keyed by service name and instance
+/// id. Round-robin counters are load-balancing state rather than registry
+/// data, so they are kept in memory.
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+    round_robin_counters: DashMap<String, AtomicUsize>,
+}
+
+impl SqliteStore {
+    /// Opens (or creates) the database at `path`
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let connection = Connection::open(path).map_err(|e| {
+            anyhow::anyhow!("Failed to open SQLite store {}: {}", path.display(), e)
+        })?;
+        Self::init(connection)
+    }
+
+    /// Creates a store backed by a private in-memory database
+    #[cfg(test)]
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> anyhow::Result<Self> {
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            round_robin_counters: DashMap::new(),
+        })
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn query_one<T: serde::de::DeserializeOwned>(
+        &self,
+        sql: &str,
+        key: &str,
+    ) -> anyhow::Result<Option<T>> {
+        let data: Option<String> = self
+            .connection()
+            .query_row(sql, params![key], |row| row.get(0))
+            .optional()?;
+
+        data.map(|data| serde_json::from_str(&data).map_err(Into::into))
+            .transpose()
+    }
+
+    fn query_all<T: serde::de::DeserializeOwned>(&self, sql: &str) -> anyhow::Result<Vec<T>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(sql)?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut records = Vec::new();
+        for data in rows {
+            records.push(serde_json::from_str(&data?)?);
+        }
+        Ok(records)
+    }
+
+    fn count(&self, sql: &str) -> anyhow::Result<usize> {
+        let count: i64 = self.connection().query_row(sql, [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+}
+
+impl RegistryStore for SqliteStore {
+    fn get_service(&self, name: &str) -> anyhow::Result<Option<ServiceRecord>> {
+        self.query_one("SELECT data FROM services WHERE name = ?1", name)
+    }
+
+    fn list_services(&self) -> anyhow::Result<Vec<ServiceRecord>> {
+        self.query_all("SELECT data FROM services ORDER BY name")
+    }
+
+    fn service_count(&self) -> anyhow::Result<usize> {
+        self.count("SELECT COUNT(*) FROM services")
+    }
+
+    fn get_instance(&self, id: &str) -> anyhow::Result<Option<ServiceInstance>> {
+        self.query_one("SELECT data FROM instances WHERE id = ?1", id)
+    }
+
+    fn list_instances(&self) -> anyhow::Result<Vec<ServiceInstance>> {
+        self.query_all("SELECT data FROM instances ORDER BY service_name, id")
+    }
+
+    fn instance_count(&self) -> anyhow::Result<usize> {
+        self.count("SELECT COUNT(*) FROM instances")
+    }
+
+    fn write(&self, writes: &[StoreWrite]) -> anyhow::Result<()> {
+        let mut connection = self.connection();
+        // Dropped without commit on error, which rolls the batch back
+        let transaction = connection.transaction()?;
+
+        for write in writes {
+            match write {
+                StoreWrite::PutService(service) => {
+                    transaction.execute(
+                        "INSERT INTO services (name, data) VALUES (?1, ?2)
+                         ON CONFLICT(name) DO UPDATE SET data = excluded.data",
+                        params![service.name, serde_json::to_string(service)?],
+                    )?;
+                }
+                StoreWrite::RemoveService(name) => {
+                    transaction.execute("DELETE FROM services WHERE name = ?1", params![name])?;
+                }
+                StoreWrite::PutInstance(instance) => {
+                    transaction.execute(
+                        "INSERT INTO instances (id, service_name, data) VALUES (?1, ?2, ?3)
+                         ON CONFLICT(id) DO UPDATE SET
+                            service_name = excluded.service_name,
+                            data = excluded.data",
+                        params![
+                            instance.id,
+                            instance.service_name,
+                            serde_json::to_string(instance)?
+                        ],
+                    )?;
+                }
+                StoreWrite::RemoveInstance(id) => {
+                    transaction.execute("DELETE FROM instances WHERE id = ?1", params![id])?;
+                }
+            }
+        }
+        transaction.commit()?;
+
+        for write in writes {
+            if let StoreWrite::RemoveService(name) = write {
+                self.round_robin_counters.remove(name);
+            }
+        }
+        Ok(())
+    }
+
+    fn next_round_robin(&self, service_name: &str) -> anyhow::Result<usize> {
+        let counter = self
+            .round_robin_counters
+            .entry(service_name.to_string())
+            .or_insert_with(|| AtomicUsize::new(0));
+
+        Ok(counter.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::InstanceStatus;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn instance(id: &str, service_name: &str) -> ServiceInstance {
+        let now = Utc::now();
+        ServiceInstance {
+            id: id.to_string(),
+            service_name: service_name.to_string(),
+            host: "localhost".to_string(),
+            port: 3000,
+            secure: false,
+            status: InstanceStatus::Up,
+            metadata: Default::default(),
+            tags: vec!["api".to_string()],
+            health_check: None,
+            registered_at: now,
+            last_heartbeat: now,
+            last_status_change: now,
+            maintenance: None,
+        }
+    }
+
+    #[test]
+    fn test_instance_round_trip() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut record = instance("a", "users");
+
+        store
+            .write(&[StoreWrite::PutInstance(Box::new(record.clone()))])
+            .unwrap();
+        record.status = InstanceStatus::Down;
+        store
+            .write(&[StoreWrite::PutInstance(Box::new(record))])
+            .unwrap();
+
+        let stored = store.get_instance("a").unwrap().unwrap();
+        assert!(matches!(stored.status, InstanceStatus::Down));
+        assert_eq!(store.instance_count().unwrap(), 1);
+
+        store
+            .write(&[StoreWrite::RemoveInstance("a".to_string())])
+            .unwrap();
+        assert!(store.get_instance("a").unwrap().is_none());
+        assert_eq!(store.instance_count().unwrap(), 0);
+    }
+
+    fn service(record: &ServiceInstance) -> ServiceRecord {
+        ServiceRecord {
+            name: record.service_name.clone(),
+            instance_ids: vec![record.id.clone()],
+            created_at: record.registered_at,
+            updated_at: record.registered_at,
+            maintenance: None,
+        }
+    }
+
+    #[test]
+    fn test_failed_batch_is_rolled_back() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let record = instance("a", "users");
+        store
+            .connection()
+            .execute_batch("DROP TABLE instances")
+            .unwrap();
+
+        let result = store.write(&[
+            StoreWrite::PutService(service(&record)),
+            StoreWrite::PutInstance(Box::new(record)),
+        ]);
+        assert!(result.is_err());
+        assert!(store.get_service("users").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_data_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(DATABASE_FILE);
+
+        {
+            let store = SqliteStore::open(&path).unwrap();
+            let record = instance("a", "users");
+            store
+                .write(&[
+                    StoreWrite::PutService(service(&record)),
+                    StoreWrite::PutInstance(Box::new(record)),
+                ])
+                .unwrap();
+        }
+
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.list_services().unwrap()[0].name, "users");
+        assert_eq!(store.list_instances().unwrap()[0].id, "a");
+    }
+
+    #[test]
+    fn test_round_robin_counter_advances() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        assert_eq!(store.next_round_robin("users").unwrap(), 0);
+        assert_eq!(store.next_round_robin("users").unwrap(), 1);
+        assert_eq!(store.next_round_robin("orders").unwrap(), 0);
+    }
+}
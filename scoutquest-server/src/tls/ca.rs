@@ -0,0 +1,453 @@
+//! Built-in certificate authority
+//!
+//! When `[pki]` is enabled, ScoutQuest keeps a root key and certificate in
+//! `ca_dir`, signs its own server certificate with it and issues
+//! short-lived leaf certificates to registered instances, so services can
+//! authenticate each other with mutual TLS without a separate PKI.
+//!
+//! Issued certificates only carry names the CA controls: the DNS name
+//! `<service>.<domain>` and the URI `spiffe://<domain>/service/<service>`,
+//! which `[[security.client_certs]]` entries match.
+
+use super::utils::write_private_key;
+use super::{ServerCertificateSpec, TlsError};
+use crate::middleware::auth::ClientCertConfig;
+use crate::middleware::scopes::Scope;
+use crate::PkiConfig;
+use chrono::{DateTime, Utc};
+use rcgen::{
+    BasicConstraints, CertificateParams, CertificateSigningRequestParams, DistinguishedName,
+    DnType, ExtendedKeyUsagePurpose, IsCa, Issuer, KeyPair, KeyUsagePurpose, SanType, SerialNumber,
+    SignatureAlgorithm,
+};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Backdating of issued certificates, to tolerate clock skew between hosts
+const CLOCK_SKEW: time::Duration = time::Duration::minutes(5);
+
+/// Certificate issued by the CA, with its private key unless the caller
+/// sent a CSR
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedCertificate {
+    pub certificate: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    /// Root to trust for the certificates of the other services
+    pub ca_certificate: String,
+    pub serial_number: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+}
+
+/// Where the public key of an issued certificate comes from
+enum LeafKey<'a> {
+    /// PEM certificate signing request of the caller
+    Csr(&'a str),
+    /// Key pair generated here and returned with the certificate
+    Generate(&'static SignatureAlgorithm),
+}
+
+pub struct CertificateAuthority {
+    cert_path: PathBuf,
+    cert_pem: String,
+    /// DER of the root, to check the signature of existing certificates
+    cert_der: Vec<u8>,
+    issuer: Issuer<'static, KeyPair>,
+    leaf_validity: time::Duration,
+    domain: String,
+}
+
+impl CertificateAuthority {
+    /// Loads the root from `ca_dir`, generating and persisting it on first use
+    pub fn load_or_create(config: &PkiConfig) -> Result<Self, TlsError> {
+        let ca_dir = Path::new(&config.ca_dir);
+        let cert_path = ca_dir.join("ca.crt");
+        let key_path = ca_dir.join("ca.key");
+
+        if !cert_path.exists() || !key_path.exists() {
+            generate_root(config, &cert_path, &key_path)?;
+        }
+
+        let cert_pem = fs::read_to_string(&cert_path)?;
+        let key = KeyPair::from_pem(&fs::read_to_string(&key_path)?).map_err(|e| {
+            TlsError::CertificateLoad(format!("Invalid CA key {}: {}", key_path.display(), e))
+        })?;
+        let cert_der = pem_to_der(&cert_pem)?;
+        let issuer = Issuer::from_ca_cert_pem(&cert_pem, key).map_err(|e| {
+            TlsError::CertificateLoad(format!(
+                "Invalid CA certificate {}: {}",
+                cert_path.display(),
+                e
+            ))
+        })?;
+
+        Ok(Self {
+            cert_path,
+            cert_pem,
+            cert_der,
+            issuer,
+            leaf_validity: time::Duration::hours(config.leaf_validity_hours as i64),
+            domain: config.domain.clone(),
+        })
+    }
+
+    /// Root certificate, for `client_ca_path` and the clients' trust stores
+    pub fn cert_path(&self) -> &Path {
+        &self.cert_path
+    }
+
+    /// Longest validity of the issued leaf certificates
+    pub fn leaf_validity(&self) -> time::Duration {
+        self.leaf_validity
+    }
+
+    /// Signs a certificate for `service_name`, usable for both server and
+    /// client authentication, valid for at most `leaf_validity_hours`. The
+    /// key pair is generated unless `csr_pem` provides the public key; the
+    /// subject and SANs of the CSR are ignored.
+    pub fn issue(
+        &self,
+        service_name: &str,
+        validity: time::Duration,
+        csr_pem: Option<&str>,
+    ) -> Result<IssuedCertificate, TlsError> {
+        let invalid_name = |e: rcgen::Error| {
+            TlsError::CertificateGeneration(format!("Invalid service name {}: {}", service_name, e))
+        };
+        let mut params = CertificateParams::default();
+        params.subject_alt_names = vec![
+            SanType::DnsName(
+                format!("{}.{}", service_name, self.domain)
+                    .try_into()
+                    .map_err(invalid_name)?,
+            ),
+            SanType::URI(
+                service_identity(&self.domain, service_name)
+                    .try_into()
+                    .map_err(invalid_name)?,
+            ),
+        ];
+
+        let key = match csr_pem {
+            Some(csr_pem) => LeafKey::Csr(csr_pem),
+            None => LeafKey::Generate(&rcgen::PKCS_ECDSA_P256_SHA256),
+        };
+        self.sign(params, service_name, validity.min(self.leaf_validity), key)
+    }
+
+    /// Writes a server certificate described by `spec` signed by the root,
+    /// followed by the root so clients get the whole chain
+    pub fn issue_server_certificate(
+        &self,
+        cert_path: &Path,
+        key_path: &Path,
+        spec: &ServerCertificateSpec,
+    ) -> Result<(), TlsError> {
+        tracing::info!("🏛️ Issuing the server certificate from the internal CA...");
+        let params = CertificateParams::new(spec.sans.clone())
+            .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+        let issued = self.sign(
+            params,
+            "ScoutQuest Server",
+            spec.validity,
+            LeafKey::Generate(spec.algorithm),
+        )?;
+
+        if let Some(parent) = cert_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            cert_path,
+            format!("{}{}", issued.certificate, issued.ca_certificate),
+        )?;
+        write_private_key(key_path, issued.private_key.as_deref().unwrap_or_default())?;
+
+        tracing::info!("✅ Server certificate written to: {}", cert_path.display());
+        Ok(())
+    }
+
+    /// Whether the first certificate of `cert_pem` was issued by this CA
+    pub fn is_issuer_of(&self, cert_pem: &str) -> bool {
+        let Ok(der) = pem_to_der(cert_pem) else {
+            return false;
+        };
+        let (Ok((_, cert)), Ok((_, root))) = (
+            x509_parser::parse_x509_certificate(&der),
+            x509_parser::parse_x509_certificate(&self.cert_der),
+        ) else {
+            return false;
+        };
+
+        cert.issuer() == root.subject() && cert.verify_signature(Some(root.public_key())).is_ok()
+    }
+
+    fn sign(
+        &self,
+        mut params: CertificateParams,
+        common_name: &str,
+        validity: time::Duration,
+        key: LeafKey,
+    ) -> Result<IssuedCertificate, TlsError> {
+        params.distinguished_name = DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        params.extended_key_usages = vec![
+            ExtendedKeyUsagePurpose::ServerAuth,
+            ExtendedKeyUsagePurpose::ClientAuth,
+        ];
+        params.use_authority_key_identifier_extension = true;
+
+        let mut serial = *uuid::Uuid::new_v4().as_bytes();
+        // Keeps the DER integer positive
+        serial[0] &= 0x7f;
+        params.serial_number = Some(SerialNumber::from_slice(&serial));
+
+        let now = time::OffsetDateTime::now_utc();
+        params.not_before = now - CLOCK_SKEW;
+        params.not_after = now + validity;
+
+        let (cert, private_key) = match key {
+            LeafKey::Csr(csr_pem) => {
+                let csr = CertificateSigningRequestParams::from_pem(csr_pem)
+                    .map_err(|e| TlsError::CertificateGeneration(format!("Invalid CSR: {}", e)))?;
+                let cert = params
+                    .signed_by(&csr.public_key, &self.issuer)
+                    .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+                (cert, None)
+            }
+            LeafKey::Generate(algorithm) => {
+                let key = KeyPair::generate_for(algorithm)
+                    .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+                let cert = params
+                    .signed_by(&key, &self.issuer)
+                    .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+                (cert, Some(key.serialize_pem()))
+            }
+        };
+
+        Ok(IssuedCertificate {
+            certificate: cert.pem(),
+            private_key,
+            ca_certificate: self.cert_pem.clone(),
+            serial_number: hex::encode(serial),
+            not_before: to_chrono(params.not_before),
+            not_after: to_chrono(params.not_after),
+        })
+    }
+}
+
+/// URI SAN identifying `service_name` in the certificates the CA issues
+pub fn service_identity(domain: &str, service_name: &str) -> String {
+    format!("spiffe://{}/service/{}", domain, service_name)
+}
+
+/// Checks the `[pki]` settings. Certificates issued to services must never
+/// match a `client_certs` entry granting `admin`, whatever the service name.
+pub fn validate_config(
+    config: &PkiConfig,
+    client_certs: &[ClientCertConfig],
+) -> anyhow::Result<()> {
+    if config.ca_validity_days == 0 || config.leaf_validity_hours == 0 {
+        return Err(anyhow::anyhow!(
+            "pki.ca_validity_days and pki.leaf_validity_hours must be greater than 0"
+        ));
+    }
+
+    let service_prefix = service_identity(&config.domain, "");
+    for cert in client_certs {
+        let admin = cert.scopes.is_empty() || cert.scopes.contains(&Scope::Admin);
+        if admin && cert.name.starts_with(&service_prefix) {
+            return Err(anyhow::anyhow!(
+                "security.client_certs {} grants admin to a certificate the internal CA issues to services",
+                cert.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn generate_root(config: &PkiConfig, cert_path: &Path, key_path: &Path) -> Result<(), TlsError> {
+    tracing::info!("🏛️ Generating the internal CA root in {}", config.ca_dir);
+
+    let mut params = CertificateParams::new(Vec::<String>::new())
+        .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+    params.distinguished_name = DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(DnType::CommonName, config.common_name.as_str());
+    params
+        .distinguished_name
+        .push(DnType::OrganizationName, "ScoutQuest");
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+
+    let now = time::OffsetDateTime::now_utc();
+    params.not_before = now - CLOCK_SKEW;
+    params.not_after = now + time::Duration::days(config.ca_validity_days as i64);
+
+    let key = KeyPair::generate().map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+    let cert = params
+        .self_signed(&key)
+        .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+
+    fs::create_dir_all(&config.ca_dir)?;
+    write_private_key(key_path, &key.serialize_pem())?;
+    fs::write(cert_path, cert.pem())?;
+
+    tracing::info!("✅ CA certificate written to: {}", cert_path.display());
+    Ok(())
+}
+
+/// DER of the first certificate of `cert_pem`
+fn pem_to_der(cert_pem: &str) -> Result<Vec<u8>, TlsError> {
+    x509_parser::pem::parse_x509_pem(cert_pem.as_bytes())
+        .map(|(_, pem)| pem.contents)
+        .map_err(|e| TlsError::CertificateLoad(format!("Invalid PEM certificate: {}", e)))
+}
+
+fn to_chrono(time: time::OffsetDateTime) -> DateTime<Utc> {
+    DateTime::from_timestamp(time.unix_timestamp(), 0).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::ClientIdentity;
+    use rcgen::PublicKeyData;
+    use rustls::pki_types::{pem::PemObject, CertificateDer, UnixTime};
+    use rustls::server::WebPkiClientVerifier;
+    use tempfile::TempDir;
+
+    fn pki_config(dir: &TempDir) -> PkiConfig {
+        PkiConfig {
+            enabled: true,
+            ca_dir: dir.path().join("pki").to_string_lossy().to_string(),
+            leaf_validity_hours: 24,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_root_persisted_across_restarts() {
+        let dir = TempDir::new().unwrap();
+        let ca = CertificateAuthority::load_or_create(&pki_config(&dir)).unwrap();
+        let issued = ca.issue("orders", time::Duration::hours(1), None).unwrap();
+
+        let reloaded = CertificateAuthority::load_or_create(&pki_config(&dir)).unwrap();
+        assert_eq!(reloaded.cert_pem, ca.cert_pem);
+        assert!(reloaded.is_issuer_of(&issued.certificate));
+
+        let other_dir = TempDir::new().unwrap();
+        let other = CertificateAuthority::load_or_create(&pki_config(&other_dir)).unwrap();
+        assert!(!other.is_issuer_of(&issued.certificate));
+    }
+
+    #[test]
+    fn test_issued_certificates_verify_against_root() {
+        let dir = TempDir::new().unwrap();
+        let ca = CertificateAuthority::load_or_create(&pki_config(&dir)).unwrap();
+
+        let issued = ca.issue("orders", time::Duration::days(30), None).unwrap();
+        assert!(issued.private_key.is_some());
+        // Capped at the configured leaf validity
+        assert!(issued.not_after - issued.not_before <= chrono::Duration::hours(25));
+
+        let leaf = CertificateDer::from_pem_slice(issued.certificate.as_bytes()).unwrap();
+        let identity = ClientIdentity::from_der(&leaf).unwrap();
+        assert_eq!(identity.common_name.as_deref(), Some("orders"));
+        assert_eq!(
+            identity.sans,
+            vec![
+                "orders.scoutquest.internal",
+                "spiffe://scoutquest.internal/service/orders"
+            ]
+        );
+        assert_eq!(
+            identity.uris,
+            vec![service_identity("scoutquest.internal", "orders")]
+        );
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_file(ca.cert_path()).unwrap())
+            .unwrap();
+        let verifier = WebPkiClientVerifier::builder(roots.into()).build().unwrap();
+        assert!(verifier
+            .verify_client_cert(&leaf, &[], UnixTime::now())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_service_certificates_never_admin() {
+        let dir = TempDir::new().unwrap();
+        let config = pki_config(&dir);
+        let cert = |name: &str, scopes: &[&str]| ClientCertConfig {
+            name: name.to_string(),
+            scopes: scopes.iter().map(|scope| scope.parse().unwrap()).collect(),
+        };
+
+        assert!(validate_config(
+            &config,
+            &[
+                cert(
+                    "spiffe://scoutquest.internal/service/orders",
+                    &["register:orders"]
+                ),
+                cert("spiffe://scoutquest.internal/ops", &["admin"]),
+            ]
+        )
+        .is_ok());
+        assert!(validate_config(
+            &config,
+            &[cert("spiffe://scoutquest.internal/service/ops", &[])]
+        )
+        .is_err());
+        assert!(validate_config(
+            &config,
+            &[cert(
+                "spiffe://scoutquest.internal/service/orders",
+                &["read", "admin"]
+            )]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_issue_from_csr() {
+        let dir = TempDir::new().unwrap();
+        let ca = CertificateAuthority::load_or_create(&pki_config(&dir)).unwrap();
+
+        let key = KeyPair::generate().unwrap();
+        let csr = CertificateParams::new(vec!["ignored".to_string()])
+            .unwrap()
+            .serialize_request(&key)
+            .unwrap()
+            .pem()
+            .unwrap();
+
+        let issued = ca
+            .issue("orders", time::Duration::hours(1), Some(&csr))
+            .unwrap();
+        assert!(issued.private_key.is_none());
+
+        let leaf = CertificateDer::from_pem_slice(issued.certificate.as_bytes()).unwrap();
+        let (_, cert) = x509_parser::parse_x509_certificate(&leaf).unwrap();
+        assert_eq!(cert.public_key().raw, key.subject_public_key_info());
+        assert_eq!(
+            ClientIdentity::from_der(&leaf).unwrap().uris,
+            vec!["spiffe://scoutquest.internal/service/orders"]
+        );
+
+        assert!(ca
+            .issue("orders", time::Duration::hours(1), Some("not a csr"))
+            .is_err());
+    }
+}
@@ -1,12 +1,155 @@
 //! Automatic certificate generation for ScoutQuest Server
 
-use super::{certificates_exist, ensure_cert_directory, TlsError};
-use rcgen::{CertificateParams, DistinguishedName, DnType};
-use std::path::Path;
+use super::reload::CertificateReloader;
+use super::utils::write_private_key;
+use super::{certificates_exist, ensure_cert_directory, CertificateAuthority, TlsError};
+use crate::ScoutQuestTlsConfig;
+use chrono::{DateTime, Utc};
+use rcgen::{CertificateParams, DistinguishedName, DnType, SignatureAlgorithm};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
 
+/// Names the generated server certificates are always valid for
+const SERVER_SANS: [&str; 4] = ["localhost", "127.0.0.1", "scoutquest", "scoutquest-server"];
+
+/// Interval between two expiry checks of the server certificate
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// What generated server certificates are issued for
+#[derive(Debug, Clone)]
+pub struct ServerCertificateSpec {
+    /// Default names followed by the configured `subject_alt_names`
+    pub sans: Vec<String>,
+    pub validity: time::Duration,
+    pub algorithm: &'static SignatureAlgorithm,
+    /// Auto-managed certificates expiring within this period are regenerated
+    pub renew_before: time::Duration,
+}
+
+impl Default for ServerCertificateSpec {
+    fn default() -> Self {
+        Self {
+            sans: SERVER_SANS.map(String::from).to_vec(),
+            validity: time::Duration::days(365),
+            algorithm: &rcgen::PKCS_ECDSA_P256_SHA256,
+            renew_before: time::Duration::days(30),
+        }
+    }
+}
+
+impl ServerCertificateSpec {
+    pub fn from_config(config: &ScoutQuestTlsConfig) -> Result<Self, TlsError> {
+        let defaults = Self::default();
+
+        let mut sans = defaults.sans;
+        for san in &config.subject_alt_names {
+            if !sans.contains(san) {
+                sans.push(san.clone());
+            }
+        }
+
+        let validity = match config.validity_days {
+            Some(0) => {
+                return Err(TlsError::InvalidConfiguration(
+                    "validity_days must be positive".to_string(),
+                ))
+            }
+            Some(days) => time::Duration::days(days.into()),
+            None => defaults.validity,
+        };
+
+        let renew_before = config
+            .renew_before_days
+            .map(|days| time::Duration::days(days.into()))
+            .unwrap_or(defaults.renew_before);
+        if renew_before >= validity {
+            return Err(TlsError::InvalidConfiguration(
+                "renew_before_days must be shorter than validity_days".to_string(),
+            ));
+        }
+
+        let algorithm = match config.key_algorithm.as_deref() {
+            None => defaults.algorithm,
+            Some(name) => key_algorithm(name)?,
+        };
+
+        Ok(Self {
+            sans,
+            validity,
+            algorithm,
+            renew_before,
+        })
+    }
+
+    /// Why the certificate in `cert_pem` should be regenerated, if it should
+    fn renewal_reason(&self, cert_pem: &str) -> Option<String> {
+        let info = match CertificateInfo::parse(cert_pem) {
+            Ok(info) => info,
+            Err(e) => return Some(e.to_string()),
+        };
+
+        let renew_at =
+            info.not_after - chrono::Duration::seconds(self.renew_before.whole_seconds());
+        if Utc::now() >= renew_at {
+            return Some(format!("it expires on {}", info.not_after));
+        }
+
+        let missing: Vec<&str> = self
+            .sans
+            .iter()
+            .filter(|san| !info.sans.contains(san))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Some(format!("it is not valid for {}", missing.join(", ")));
+        }
+
+        None
+    }
+}
+
+/// Parses a `key_algorithm` setting
+fn key_algorithm(name: &str) -> Result<&'static SignatureAlgorithm, TlsError> {
+    match name {
+        "ecdsa-p256" => Ok(&rcgen::PKCS_ECDSA_P256_SHA256),
+        "ecdsa-p384" => Ok(&rcgen::PKCS_ECDSA_P384_SHA384),
+        "ed25519" => Ok(&rcgen::PKCS_ED25519),
+        _ => Err(TlsError::InvalidConfiguration(format!(
+            "Invalid key algorithm: {}. Supported algorithms: ecdsa-p256, ecdsa-p384, ed25519",
+            name
+        ))),
+    }
+}
+
+/// Expiry and names of a PEM certificate
+pub struct CertificateInfo {
+    pub not_after: DateTime<Utc>,
+    pub sans: Vec<String>,
+}
+
+impl CertificateInfo {
+    pub fn parse(cert_pem: &str) -> Result<Self, TlsError> {
+        let (_, pem) = x509_parser::pem::parse_x509_pem(cert_pem.as_bytes())
+            .map_err(|e| TlsError::CertificateLoad(format!("Invalid PEM certificate: {}", e)))?;
+        let (_, cert) = x509_parser::parse_x509_certificate(&pem.contents)
+            .map_err(|e| TlsError::CertificateLoad(format!("Invalid certificate: {}", e)))?;
+
+        Ok(Self {
+            not_after: DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+                .unwrap_or_default(),
+            sans: super::ClientIdentity::from_der(&pem.contents)?.sans,
+        })
+    }
+}
+
 /// Generates a self-signed certificate and private key
-pub async fn generate_self_signed_cert(cert_path: &Path, key_path: &Path) -> Result<(), TlsError> {
+pub async fn generate_self_signed_cert(
+    cert_path: &Path,
+    key_path: &Path,
+    spec: &ServerCertificateSpec,
+) -> Result<(), TlsError> {
     tracing::info!("🔐 Generating self-signed certificate...");
     tracing::info!("   Certificate: {}", cert_path.display());
     tracing::info!("   Private key: {}", key_path.display());
@@ -17,13 +160,8 @@ pub async fn generate_self_signed_cert(cert_path: &Path, key_path: &Path) -> Res
     }
 
     // Create certificate parameters
-    let mut params = CertificateParams::new(vec![
-        "localhost".to_string(),
-        "127.0.0.1".to_string(),
-        "scoutquest".to_string(),
-        "scoutquest-server".to_string(),
-    ])
-    .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+    let mut params = CertificateParams::new(spec.sans.clone())
+        .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
 
     // Set certificate distinguished name
     params.distinguished_name = DistinguishedName::new();
@@ -35,15 +173,15 @@ pub async fn generate_self_signed_cert(cert_path: &Path, key_path: &Path) -> Res
         .push(DnType::OrganizationName, "ScoutQuest");
     params.distinguished_name.push(DnType::CountryName, "US");
 
-    // Set certificate validity period (1 year)
+    // Set certificate validity period
     let not_before = time::OffsetDateTime::now_utc();
-    let not_after = not_before + time::Duration::days(365);
+    let not_after = not_before + spec.validity;
     params.not_before = not_before;
     params.not_after = not_after;
 
     // Generate the self-signed certificate
-    let key_pair =
-        rcgen::KeyPair::generate().map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
+    let key_pair = rcgen::KeyPair::generate_for(spec.algorithm)
+        .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
     let cert = params
         .self_signed(&key_pair)
         .map_err(|e| TlsError::CertificateGeneration(e.to_string()))?;
@@ -56,17 +194,8 @@ pub async fn generate_self_signed_cert(cert_path: &Path, key_path: &Path) -> Res
     fs::write(cert_path, cert_pem).await?;
     tracing::info!("✅ Certificate written to: {}", cert_path.display());
 
-    // Write private key file with restricted permissions
-    fs::write(key_path, key_pem).await?;
-
-    // Set restrictive permissions on private key (Unix only)
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(key_path).await?.permissions();
-        perms.set_mode(0o600); // Read/write for owner only
-        fs::set_permissions(key_path, perms).await?;
-    }
+    // Write private key file, readable by its owner only
+    write_private_key(key_path, &key_pem)?;
 
     tracing::info!("✅ Private key written to: {}", key_path.display());
     tracing::info!("🔐 Self-signed certificate generation completed successfully");
@@ -124,31 +253,93 @@ pub async fn validate_private_key(key_path: &Path) -> Result<(), TlsError> {
     Ok(())
 }
 
-/// Ensures certificates exist, generating them if necessary
+/// Ensures certificates exist, generating them if necessary. Auto-managed
+/// certificates are regenerated when they are about to expire, miss one of
+/// the configured names, or were not issued by the internal CA when it is
+/// enabled; others only get a warning when expiring.
 pub async fn ensure_certificates(
     cert_path: &Path,
     key_path: &Path,
     auto_generate: bool,
+    spec: &ServerCertificateSpec,
+    ca: Option<&CertificateAuthority>,
 ) -> Result<(), TlsError> {
     if certificates_exist(cert_path, key_path) {
         // Validate existing certificates
         validate_certificate(cert_path).await?;
         validate_private_key(key_path).await?;
-        tracing::info!("🔐 Using existing TLS certificates");
-        return Ok(());
-    }
 
-    if !auto_generate {
+        let cert_pem = fs::read_to_string(cert_path).await?;
+        let reason = match ca {
+            Some(ca) if auto_generate && !ca.is_issuer_of(&cert_pem) => {
+                Some("it was not issued by the internal CA".to_string())
+            }
+            _ => spec.renewal_reason(&cert_pem),
+        };
+
+        match reason {
+            Some(reason) if auto_generate => {
+                tracing::info!("🔄 Regenerating the TLS certificate: {}", reason);
+            }
+            Some(reason) => {
+                tracing::warn!(
+                    "⚠️ TLS certificate {} should be replaced: {}",
+                    cert_path.display(),
+                    reason
+                );
+                return Ok(());
+            }
+            None => {
+                tracing::info!("🔐 Using existing TLS certificates");
+                return Ok(());
+            }
+        }
+    } else if !auto_generate {
         return Err(TlsError::InvalidConfiguration(
             "TLS certificates not found and auto_generate is disabled".to_string(),
         ));
     }
 
     // Generate new certificates
-    generate_self_signed_cert(cert_path, key_path).await?;
+    match ca {
+        Some(ca) => ca.issue_server_certificate(cert_path, key_path, spec)?,
+        None => generate_self_signed_cert(cert_path, key_path, spec).await?,
+    }
     Ok(())
 }
 
+/// Checks the certificate expiry every hour, regenerating auto-managed
+/// certificates ahead of time and serving the new one right away
+pub fn start_certificate_renewal(
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    auto_generate: bool,
+    spec: ServerCertificateSpec,
+    ca: Option<Arc<CertificateAuthority>>,
+    reloader: Arc<CertificateReloader>,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(RENEWAL_CHECK_INTERVAL);
+        // The first tick completes immediately, startup already checked
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) =
+                ensure_certificates(&cert_path, &key_path, auto_generate, &spec, ca.as_deref())
+                    .await
+            {
+                tracing::error!("❌ Failed to renew the TLS certificate: {}", e);
+                continue;
+            }
+            match reloader.reload_if_changed() {
+                Ok(true) => tracing::info!("🔐 Renewed TLS certificate now served"),
+                Ok(false) => {}
+                Err(e) => tracing::error!("❌ Failed to load the renewed TLS certificate: {}", e),
+            }
+        }
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,7 +351,9 @@ mod tests {
         let cert_path = temp_dir.path().join("test.crt");
         let key_path = temp_dir.path().join("test.key");
 
-        let result = generate_self_signed_cert(&cert_path, &key_path).await;
+        let result =
+            generate_self_signed_cert(&cert_path, &key_path, &ServerCertificateSpec::default())
+                .await;
         assert!(result.is_ok());
         assert!(cert_path.exists());
         assert!(key_path.exists());
@@ -181,19 +374,156 @@ mod tests {
         let cert_path = temp_dir.path().join("auto.crt");
         let key_path = temp_dir.path().join("auto.key");
 
-        let result = ensure_certificates(&cert_path, &key_path, true).await;
+        let result = ensure_certificates(
+            &cert_path,
+            &key_path,
+            true,
+            &ServerCertificateSpec::default(),
+            None,
+        )
+        .await;
         assert!(result.is_ok());
         assert!(cert_path.exists());
         assert!(key_path.exists());
     }
 
+    #[tokio::test]
+    async fn test_ensure_certificates_from_internal_ca() {
+        let temp_dir = TempDir::new().unwrap();
+        let cert_path = temp_dir.path().join("server.crt");
+        let key_path = temp_dir.path().join("server.key");
+        generate_self_signed_cert(&cert_path, &key_path, &ServerCertificateSpec::default())
+            .await
+            .unwrap();
+
+        let ca = CertificateAuthority::load_or_create(&crate::PkiConfig {
+            enabled: true,
+            ca_dir: temp_dir.path().join("pki").to_string_lossy().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // The self-signed certificate is replaced by one from the CA
+        ensure_certificates(
+            &cert_path,
+            &key_path,
+            true,
+            &ServerCertificateSpec::default(),
+            Some(&ca),
+        )
+        .await
+        .unwrap();
+        let cert_content = fs::read_to_string(&cert_path).await.unwrap();
+        assert!(ca.is_issuer_of(&cert_content));
+    }
+
     #[tokio::test]
     async fn test_ensure_certificates_no_auto_generate() {
         let temp_dir = TempDir::new().unwrap();
         let cert_path = temp_dir.path().join("missing.crt");
         let key_path = temp_dir.path().join("missing.key");
 
-        let result = ensure_certificates(&cert_path, &key_path, false).await;
+        let result = ensure_certificates(
+            &cert_path,
+            &key_path,
+            false,
+            &ServerCertificateSpec::default(),
+            None,
+        )
+        .await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_auto_managed_certificates_renewed() {
+        let temp_dir = TempDir::new().unwrap();
+        let cert_path = temp_dir.path().join("server.crt");
+        let key_path = temp_dir.path().join("server.key");
+
+        let short_lived = ServerCertificateSpec {
+            validity: time::Duration::days(10),
+            ..Default::default()
+        };
+        generate_self_signed_cert(&cert_path, &key_path, &short_lived)
+            .await
+            .unwrap();
+        let original = fs::read_to_string(&cert_path).await.unwrap();
+
+        // Still valid for longer than the renewal window
+        let spec = ServerCertificateSpec {
+            renew_before: time::Duration::days(5),
+            ..Default::default()
+        };
+        ensure_certificates(&cert_path, &key_path, true, &spec, None)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&cert_path).await.unwrap(), original);
+
+        // Not renewed when the certificate is not auto-managed
+        let spec = ServerCertificateSpec {
+            renew_before: time::Duration::days(30),
+            ..Default::default()
+        };
+        ensure_certificates(&cert_path, &key_path, false, &spec, None)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&cert_path).await.unwrap(), original);
+
+        ensure_certificates(&cert_path, &key_path, true, &spec, None)
+            .await
+            .unwrap();
+        let renewed = fs::read_to_string(&cert_path).await.unwrap();
+        assert_ne!(renewed, original);
+        assert!(
+            CertificateInfo::parse(&renewed).unwrap().not_after
+                > Utc::now() + chrono::Duration::days(300)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_certificate_regenerated_for_new_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let cert_path = temp_dir.path().join("server.crt");
+        let key_path = temp_dir.path().join("server.key");
+        generate_self_signed_cert(&cert_path, &key_path, &ServerCertificateSpec::default())
+            .await
+            .unwrap();
+
+        let spec = ServerCertificateSpec::from_config(&ScoutQuestTlsConfig {
+            subject_alt_names: vec!["discovery.internal".to_string(), "10.0.0.5".to_string()],
+            key_algorithm: Some("ecdsa-p384".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        ensure_certificates(&cert_path, &key_path, true, &spec, None)
+            .await
+            .unwrap();
+
+        let info = CertificateInfo::parse(&fs::read_to_string(&cert_path).await.unwrap()).unwrap();
+        assert!(info.sans.contains(&"localhost".to_string()));
+        assert!(info.sans.contains(&"discovery.internal".to_string()));
+        assert!(info.sans.contains(&"10.0.0.5".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_certificate_settings() {
+        let invalid = [
+            ScoutQuestTlsConfig {
+                key_algorithm: Some("rsa-1024".to_string()),
+                ..Default::default()
+            },
+            ScoutQuestTlsConfig {
+                validity_days: Some(0),
+                ..Default::default()
+            },
+            ScoutQuestTlsConfig {
+                validity_days: Some(7),
+                renew_before_days: Some(30),
+                ..Default::default()
+            },
+        ];
+        for config in invalid {
+            assert!(ServerCertificateSpec::from_config(&config).is_err());
+        }
+    }
 }
@@ -0,0 +1,358 @@
+//! Mutual TLS: client certificate verification and identity extraction
+
+use super::TlsError;
+use axum::{middleware::AddExtension, Extension};
+use axum_server::{
+    accept::Accept,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use rustls::pki_types::{pem::PemObject, CertificateDer};
+use rustls::server::{danger::ClientCertVerifier, WebPkiClientVerifier};
+use rustls::RootCertStore;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+use x509_parser::extensions::GeneralName;
+
+/// Identity of a client that presented a certificate chained to
+/// `client_ca_path`, available to handlers as a request extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIdentity {
+    /// Subject common name
+    pub common_name: Option<String>,
+    /// DNS names, URIs, emails and IP addresses of the subject alternative
+    /// name extension
+    pub sans: Vec<String>,
+    /// URIs of the subject alternative name extension, the identities
+    /// `[[security.client_certs]]` entries match
+    pub uris: Vec<String>,
+}
+
+impl ClientIdentity {
+    /// Reads the identity of a DER-encoded leaf certificate
+    pub fn from_der(der: &[u8]) -> Result<Self, TlsError> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der)
+            .map_err(|e| TlsError::CertificateLoad(format!("Invalid client certificate: {}", e)))?;
+
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string);
+
+        let sans = match cert.subject_alternative_name() {
+            Ok(Some(extension)) => extension
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(name)
+                    | GeneralName::URI(name)
+                    | GeneralName::RFC822Name(name) => Some(name.to_string()),
+                    GeneralName::IPAddress(bytes) => ip_from_bytes(bytes).map(|ip| ip.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        let uris = match cert.subject_alternative_name() {
+            Ok(Some(extension)) => extension
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::URI(uri) => Some(uri.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        Ok(Self {
+            common_name,
+            sans,
+            uris,
+        })
+    }
+
+    /// Name reported in logs and the audit trail: the common name, or the
+    /// first subject alternative name
+    pub fn name(&self) -> Option<&str> {
+        self.common_name
+            .as_deref()
+            .or_else(|| self.sans.first().map(String::as_str))
+    }
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => <[u8; 4]>::try_from(bytes).ok().map(IpAddr::from),
+        16 => <[u8; 16]>::try_from(bytes).ok().map(IpAddr::from),
+        _ => None,
+    }
+}
+
+/// Verifier accepting only client certificates chained to the CAs of
+/// `ca_path`
+pub fn client_verifier(ca_path: &Path) -> Result<Arc<dyn ClientCertVerifier>, TlsError> {
+    let mut roots = RootCertStore::empty();
+    let certs = CertificateDer::pem_file_iter(ca_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            TlsError::CertificateLoad(format!(
+                "Failed to read client CA {}: {}",
+                ca_path.display(),
+                e
+            ))
+        })?;
+
+    for cert in certs {
+        roots.add(cert)?;
+    }
+    if roots.is_empty() {
+        return Err(TlsError::CertificateLoad(format!(
+            "No CA certificate found in {}",
+            ca_path.display()
+        )));
+    }
+
+    WebPkiClientVerifier::builder(Arc::new(roots))
+        .build()
+        .map_err(|e| TlsError::InvalidConfiguration(format!("Invalid client CA: {}", e)))
+}
+
+/// TLS acceptor that exposes the verified client certificate to handlers as
+/// a [`ClientIdentity`] extension
+#[derive(Debug, Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl ClientCertAcceptor {
+    pub fn new(config: RustlsConfig) -> Self {
+        Self {
+            inner: RustlsAcceptor::new(config),
+        }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, ClientIdentity>;
+    type Future = Pin<Box<dyn Future<Output = io::Result<(Self::Stream, Self::Service)>> + Send>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+
+            // The verifier rejects handshakes without a certificate, so the
+            // leaf is always there once the handshake succeeded
+            let leaf = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::PermissionDenied, "No client certificate")
+                })?;
+            let identity = ClientIdentity::from_der(leaf)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            tracing::debug!(
+                "🔏 Client certificate accepted: {}",
+                identity.name().unwrap_or("<unnamed>")
+            );
+
+            Ok((stream, Extension(identity).layer(service)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::load_rustls_config;
+    use crate::ScoutQuestTlsConfig;
+    use axum::{routing::get, Router};
+    use rcgen::{
+        BasicConstraints, CertificateParams, DistinguishedName, DnType, IsCa, Issuer, KeyPair,
+        SanType,
+    };
+    use rustls::pki_types::{PrivateKeyDer, ServerName};
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    struct TestPki {
+        dir: TempDir,
+        ca: rcgen::Certificate,
+        issuer: Issuer<'static, KeyPair>,
+    }
+
+    impl TestPki {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(vec![]).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "ScoutQuest Test CA");
+            let key = KeyPair::generate().unwrap();
+            let ca = params.self_signed(&key).unwrap();
+
+            let dir = TempDir::new().unwrap();
+            std::fs::write(dir.path().join("ca.crt"), ca.pem()).unwrap();
+
+            Self {
+                dir,
+                ca,
+                issuer: Issuer::new(params, key),
+            }
+        }
+
+        /// Leaf certificate signed by the CA, with its key
+        fn issue(&self, common_name: &str, sans: Vec<String>) -> (rcgen::Certificate, KeyPair) {
+            let mut params = CertificateParams::new(sans).unwrap();
+            params
+                .distinguished_name
+                .push(DnType::CommonName, common_name);
+            let key = KeyPair::generate().unwrap();
+            let cert = params.signed_by(&key, &self.issuer).unwrap();
+            (cert, key)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.dir.path().join(name).to_string_lossy().to_string()
+        }
+    }
+
+    #[test]
+    fn test_identity_from_certificate() {
+        let pki = TestPki::new();
+        let (cert, _) = pki.issue(
+            "orders-api",
+            vec!["orders.internal".to_string(), "10.0.0.7".to_string()],
+        );
+
+        let identity = ClientIdentity::from_der(cert.der()).unwrap();
+        assert_eq!(identity.common_name.as_deref(), Some("orders-api"));
+        assert_eq!(identity.sans, vec!["orders.internal", "10.0.0.7"]);
+        assert_eq!(identity.name(), Some("orders-api"));
+        assert!(identity.uris.is_empty());
+
+        // Without a common name, the first SAN names the client
+        let mut params = CertificateParams::new(vec![]).unwrap();
+        params.distinguished_name = DistinguishedName::new();
+        params.subject_alt_names = vec![SanType::URI(
+            "spiffe://scoutquest/orders".try_into().unwrap(),
+        )];
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &pki.issuer).unwrap();
+        let identity = ClientIdentity::from_der(cert.der()).unwrap();
+        assert_eq!(identity.name(), Some("spiffe://scoutquest/orders"));
+        assert_eq!(identity.uris, vec!["spiffe://scoutquest/orders"]);
+    }
+
+    async fn start_server(pki: &TestPki) -> SocketAddr {
+        let (cert, key) = pki.issue("localhost", vec!["localhost".to_string()]);
+        std::fs::write(pki.path("server.crt"), cert.pem()).unwrap();
+        std::fs::write(pki.path("server.key"), key.serialize_pem()).unwrap();
+
+        let tls_config = ScoutQuestTlsConfig {
+            enabled: true,
+            verify_peer: true,
+            client_ca_path: Some(pki.path("ca.crt")),
+            ..Default::default()
+        };
+        let rustls_config = load_rustls_config(
+            Path::new(&pki.path("server.crt")),
+            Path::new(&pki.path("server.key")),
+            &tls_config,
+        )
+        .await
+        .unwrap();
+
+        let app = Router::new().route(
+            "/whoami",
+            get(
+                |Extension(identity): Extension<ClientIdentity>| async move {
+                    identity.name().unwrap_or_default().to_string()
+                },
+            ),
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum_server::from_tcp(listener)
+                .unwrap()
+                .acceptor(ClientCertAcceptor::new(rustls_config))
+                .serve(app.into_make_service())
+                .await
+        });
+        address
+    }
+
+    /// Sends `GET /whoami` over TLS, presenting `client` when given
+    async fn whoami(
+        pki: &TestPki,
+        address: SocketAddr,
+        client: Option<(rcgen::Certificate, KeyPair)>,
+    ) -> io::Result<String> {
+        let mut roots = RootCertStore::empty();
+        roots.add(pki.ca.der().clone()).unwrap();
+        let builder = rustls::ClientConfig::builder().with_root_certificates(roots);
+        let config = match client {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(
+                    vec![cert.der().clone()],
+                    PrivateKeyDer::try_from(key.serialize_der()).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+
+        let stream = tokio::net::TcpStream::connect(address).await?;
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+        stream
+            .write_all(b"GET /whoami HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_required() {
+        let pki = TestPki::new();
+        let address = start_server(&pki).await;
+
+        let client = pki.issue("deployer", vec![]);
+        let response = whoami(&pki, address, Some(client)).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("deployer"));
+
+        // TLS 1.3 clients only learn about the rejection when reading
+        assert!(whoami(&pki, address, None).await.is_err());
+
+        let untrusted = TestPki::new().issue("intruder", vec![]);
+        assert!(whoami(&pki, address, Some(untrusted)).await.is_err());
+    }
+}
@@ -1,6 +1,6 @@
 //! TLS configuration utilities and validation
 
-use super::TlsError;
+use super::{ServerCertificateSpec, TlsError, TlsPolicy};
 use crate::models::ScoutQuestTlsConfig;
 use std::path::{Path, PathBuf};
 
@@ -26,6 +26,30 @@ pub fn validate_tls_config(config: &ScoutQuestTlsConfig) -> Result<(), TlsError>
         validate_tls_version(max_version)?;
     }
 
+    // Validate the versions, cipher suites and ALPN protocols together
+    TlsPolicy::from_config(config)?;
+
+    // Validate the settings of generated certificates
+    ServerCertificateSpec::from_config(config)?;
+
+    // Client certificates can only be verified against a known CA
+    if config.verify_peer {
+        match &config.client_ca_path {
+            None => {
+                return Err(TlsError::InvalidConfiguration(
+                    "verify_peer requires client_ca_path".to_string(),
+                ))
+            }
+            Some(client_ca_path) if !Path::new(client_ca_path).exists() => {
+                return Err(TlsError::InvalidConfiguration(format!(
+                    "Client CA file not found: {}",
+                    client_ca_path
+                )))
+            }
+            Some(_) => {}
+        }
+    }
+
     // Validate custom certificate paths if provided
     if let (Some(ref cert_path), Some(ref key_path)) = (&config.cert_path, &config.key_path) {
         if !config.auto_generate {
@@ -135,4 +159,20 @@ mod tests {
         assert_eq!(cert_path, PathBuf::from("/custom/cert.pem"));
         assert_eq!(key_path, PathBuf::from("/custom/key.pem"));
     }
+
+    #[test]
+    fn test_verify_peer_requires_client_ca() {
+        let mut config = ScoutQuestTlsConfig {
+            enabled: true,
+            verify_peer: true,
+            ..Default::default()
+        };
+        assert!(validate_tls_config(&config).is_err());
+
+        config.client_ca_path = Some("/nonexistent/ca.crt".to_string());
+        assert!(validate_tls_config(&config).is_err());
+
+        config.verify_peer = false;
+        assert!(validate_tls_config(&config).is_ok());
+    }
 }
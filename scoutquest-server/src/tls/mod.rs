@@ -5,14 +5,25 @@
 //! - HTTPS server with Rustls
 //! - Certificate management utilities
 //! - TLS configuration handling
+//! - Client certificate authentication (mutual TLS)
+//! - Built-in certificate authority
+//! - Live reload of rotated certificates
 
+pub mod ca;
 pub mod cert_gen;
+pub mod client_auth;
 pub mod config;
+pub mod policy;
+pub mod redirect;
+pub mod reload;
 pub mod server;
 pub mod utils;
 
+pub use ca::CertificateAuthority;
 pub use cert_gen::*;
+pub use client_auth::ClientIdentity;
 pub use config::*;
+pub use policy::TlsPolicy;
 pub use server::*;
 
 use std::fmt;
@@ -0,0 +1,235 @@
+//! Protocol versions, cipher suites and ALPN protocols offered by the HTTPS
+//! server
+
+use super::TlsError;
+use crate::ScoutQuestTlsConfig;
+use rustls::crypto::{aws_lc_rs, CryptoProvider};
+use rustls::{SupportedCipherSuite, SupportedProtocolVersion};
+use std::sync::Arc;
+
+const ALPN_PROTOCOLS: [&str; 2] = ["h2", "http/1.1"];
+
+/// Effective TLS policy of a [`ScoutQuestTlsConfig`]
+#[derive(Debug, Clone)]
+pub struct TlsPolicy {
+    versions: Vec<&'static SupportedProtocolVersion>,
+    cipher_suites: Vec<SupportedCipherSuite>,
+    alpn_protocols: Vec<String>,
+}
+
+impl TlsPolicy {
+    /// Resolves the configured versions, cipher suites and ALPN protocols.
+    /// Rustls does not implement TLS 1.0 and 1.1, so a lower minimum
+    /// version still starts at TLS 1.2.
+    pub fn from_config(config: &ScoutQuestTlsConfig) -> Result<Self, TlsError> {
+        let min = version_rank(config.min_version.as_deref().unwrap_or("1.2"))?;
+        let max = version_rank(config.max_version.as_deref().unwrap_or("1.3"))?;
+        if min > max {
+            return Err(TlsError::InvalidConfiguration(format!(
+                "min_version {} is above max_version {}",
+                config.min_version.as_deref().unwrap_or("1.2"),
+                config.max_version.as_deref().unwrap_or("1.3")
+            )));
+        }
+
+        let versions: Vec<&'static SupportedProtocolVersion> =
+            [(2, &rustls::version::TLS12), (3, &rustls::version::TLS13)]
+                .into_iter()
+                .filter(|(rank, _)| (min..=max).contains(rank))
+                .map(|(_, version)| version)
+                .collect();
+        if versions.is_empty() {
+            return Err(TlsError::InvalidConfiguration(
+                "TLS versions below 1.2 are not supported, raise max_version".to_string(),
+            ));
+        }
+
+        let cipher_suites = match &config.cipher_suites {
+            None => aws_lc_rs::DEFAULT_CIPHER_SUITES.to_vec(),
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    aws_lc_rs::ALL_CIPHER_SUITES
+                        .iter()
+                        .find(|suite| suite_name(suite) == name.as_str())
+                        .copied()
+                        .ok_or_else(|| {
+                            TlsError::InvalidConfiguration(format!(
+                                "Unsupported cipher suite: {}. Supported suites: {}",
+                                name,
+                                aws_lc_rs::ALL_CIPHER_SUITES
+                                    .iter()
+                                    .map(suite_name)
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        // Each enabled version needs a suite, or its handshakes would fail
+        for version in &versions {
+            if !cipher_suites
+                .iter()
+                .any(|suite| suite.version() == *version)
+            {
+                return Err(TlsError::InvalidConfiguration(format!(
+                    "No cipher suite allowed for {}",
+                    version_name(version)
+                )));
+            }
+        }
+
+        let alpn_protocols = match &config.alpn_protocols {
+            None => ALPN_PROTOCOLS.map(String::from).to_vec(),
+            Some(protocols) => {
+                if let Some(protocol) = protocols
+                    .iter()
+                    .find(|protocol| !ALPN_PROTOCOLS.contains(&protocol.as_str()))
+                {
+                    return Err(TlsError::InvalidConfiguration(format!(
+                        "Unsupported ALPN protocol: {}. Supported protocols: {}",
+                        protocol,
+                        ALPN_PROTOCOLS.join(", ")
+                    )));
+                }
+                protocols.clone()
+            }
+        };
+
+        Ok(Self {
+            versions,
+            cipher_suites,
+            alpn_protocols,
+        })
+    }
+
+    /// Rustls server configuration builder restricted to this policy
+    pub fn server_config_builder(
+        &self,
+    ) -> Result<rustls::ConfigBuilder<rustls::ServerConfig, rustls::WantsVerifier>, TlsError> {
+        let provider = CryptoProvider {
+            cipher_suites: self.cipher_suites.clone(),
+            ..aws_lc_rs::default_provider()
+        };
+        Ok(
+            rustls::ServerConfig::builder_with_provider(Arc::new(provider))
+                .with_protocol_versions(&self.versions)?,
+        )
+    }
+
+    pub fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        self.alpn_protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect()
+    }
+
+    /// Policy reported by the startup log and `/info`
+    pub fn describe(&self) -> serde_json::Value {
+        serde_json::json!({
+            "versions": self.versions.iter().map(|v| version_name(v)).collect::<Vec<_>>(),
+            "cipher_suites": self.cipher_suites.iter().map(suite_name).collect::<Vec<_>>(),
+            "alpn_protocols": self.alpn_protocols,
+        })
+    }
+
+    pub fn log(&self) {
+        let versions: Vec<_> = self.versions.iter().map(|v| version_name(v)).collect();
+        tracing::info!("   Protocol versions: {}", versions.join(", "));
+        tracing::info!(
+            "   Cipher suites: {}",
+            self.cipher_suites
+                .iter()
+                .map(suite_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        tracing::info!("   ALPN protocols: {}", self.alpn_protocols.join(", "));
+    }
+}
+
+/// Minor version of a configured TLS version, "1.2" being 2
+fn version_rank(version: &str) -> Result<u8, TlsError> {
+    match version {
+        "1.0" => Ok(0),
+        "1.1" => Ok(1),
+        "1.2" => Ok(2),
+        "1.3" => Ok(3),
+        _ => Err(TlsError::InvalidConfiguration(format!(
+            "Invalid TLS version: {}. Supported versions: 1.0, 1.1, 1.2, 1.3",
+            version
+        ))),
+    }
+}
+
+fn version_name(version: &SupportedProtocolVersion) -> &'static str {
+    if *version == rustls::version::TLS13 {
+        "TLSv1.3"
+    } else {
+        "TLSv1.2"
+    }
+}
+
+fn suite_name(suite: &SupportedCipherSuite) -> &'static str {
+    suite.suite().as_str().unwrap_or("unknown")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min: &str, max: &str, cipher_suites: Option<&[&str]>) -> Result<TlsPolicy, TlsError> {
+        TlsPolicy::from_config(&ScoutQuestTlsConfig {
+            min_version: Some(min.to_string()),
+            max_version: Some(max.to_string()),
+            cipher_suites: cipher_suites.map(|names| names.iter().map(|n| n.to_string()).collect()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_policy_from_config() {
+        let description = policy("1.2", "1.3", None).unwrap().describe();
+        assert_eq!(
+            description["versions"],
+            serde_json::json!(["TLSv1.2", "TLSv1.3"])
+        );
+        assert_eq!(
+            description["alpn_protocols"],
+            serde_json::json!(["h2", "http/1.1"])
+        );
+
+        let tls13_only = policy("1.3", "1.3", Some(&["TLS13_AES_256_GCM_SHA384"])).unwrap();
+        assert_eq!(
+            tls13_only.describe()["cipher_suites"],
+            serde_json::json!(["TLS13_AES_256_GCM_SHA384"])
+        );
+
+        // TLS 1.0 and 1.1 are not implemented, TLS 1.2 is the floor
+        let legacy = policy("1.0", "1.2", None).unwrap();
+        assert_eq!(
+            legacy.describe()["versions"],
+            serde_json::json!(["TLSv1.2"])
+        );
+
+        assert!(policy("1.3", "1.2", None).is_err());
+        assert!(policy("1.0", "1.1", None).is_err());
+        assert!(policy("1.2", "1.3", Some(&["TLS_RSA_WITH_RC4_128_SHA"])).is_err());
+        // TLS 1.2 enabled without a TLS 1.2 suite
+        assert!(policy("1.2", "1.3", Some(&["TLS13_AES_128_GCM_SHA256"])).is_err());
+
+        let http1_only = TlsPolicy::from_config(&ScoutQuestTlsConfig {
+            alpn_protocols: Some(vec!["http/1.1".to_string()]),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(http1_only.alpn_protocols(), vec![b"http/1.1".to_vec()]);
+        assert!(TlsPolicy::from_config(&ScoutQuestTlsConfig {
+            alpn_protocols: Some(vec!["spdy/3".to_string()]),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
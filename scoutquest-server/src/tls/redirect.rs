@@ -0,0 +1,196 @@
+//! Plain HTTP listener redirecting to HTTPS, and the HSTS header of the
+//! HTTPS side
+
+use crate::ScoutQuestTlsConfig;
+use axum::{
+    extract::Request,
+    http::{header, uri::Authority, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Json, Router,
+};
+use std::net::SocketAddr;
+
+/// Redirect target of a plain HTTP request: the configured canonical host
+/// or the request Host, with the HTTPS port, path and query
+fn https_location(
+    headers: &HeaderMap,
+    uri: &Uri,
+    https_port: u16,
+    canonical_host: Option<&str>,
+) -> Option<String> {
+    let host = match canonical_host {
+        Some(host) => host.to_string(),
+        None => {
+            let authority: Authority = headers.get(header::HOST)?.to_str().ok()?.parse().ok()?;
+            authority.host().to_string()
+        }
+    };
+
+    let path_and_query = uri.path_and_query().map_or("/", |pq| pq.as_str());
+    Some(match https_port {
+        443 => format!("https://{}{}", host, path_and_query),
+        port => format!("https://{}:{}{}", host, port, path_and_query),
+    })
+}
+
+/// Router of the redirect listener, answering `/health` itself so load
+/// balancers probing plain HTTP keep working
+fn redirect_router(https_port: u16, canonical_host: Option<String>) -> Router {
+    Router::new()
+        .route(
+            "/health",
+            get(|| async { Json(serde_json::json!({ "status": "UP" })) }),
+        )
+        .fallback(move |headers: HeaderMap, uri: Uri| async move {
+            match https_location(&headers, &uri, https_port, canonical_host.as_deref()) {
+                Some(location) => {
+                    tracing::debug!("🔄 Redirecting HTTP request to: {}", location);
+                    Redirect::permanent(&location).into_response()
+                }
+                None => (StatusCode::BAD_REQUEST, "Missing or invalid Host header").into_response(),
+            }
+        })
+}
+
+/// Starts an HTTP redirect server that redirects all traffic to HTTPS
+pub(crate) async fn start_http_redirect_server(
+    host: &str,
+    http_port: u16,
+    https_port: u16,
+    canonical_host: Option<String>,
+) -> anyhow::Result<()> {
+    let http_addr = SocketAddr::from((host.parse::<std::net::IpAddr>()?, http_port));
+
+    tracing::info!("🔄 Starting HTTP redirect server on http://{}", http_addr);
+    tracing::info!("   Redirecting to HTTPS port: {}", https_port);
+    if let Some(canonical_host) = &canonical_host {
+        tracing::info!("   Canonical host: {}", canonical_host);
+    }
+
+    // Start HTTP redirect server in background
+    let listener = tokio::net::TcpListener::bind(http_addr).await?;
+    let redirect_app = redirect_router(https_port, canonical_host);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, redirect_app).await {
+            tracing::error!("HTTP redirect server error: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+/// Value of the Strict-Transport-Security header, `None` when HSTS is off
+pub(crate) fn hsts_header(tls_config: &ScoutQuestTlsConfig) -> Option<HeaderValue> {
+    let max_age = tls_config.hsts_max_age_seconds.filter(|&age| age > 0)?;
+    let value = if tls_config.hsts_include_subdomains {
+        format!("max-age={}; includeSubDomains", max_age)
+    } else {
+        format!("max-age={}", max_age)
+    };
+    HeaderValue::from_str(&value).ok()
+}
+
+/// Adds the Strict-Transport-Security header to the responses of `app`
+pub(crate) fn with_hsts(app: Router, tls_config: &ScoutQuestTlsConfig) -> Router {
+    let Some(hsts) = hsts_header(tls_config) else {
+        return app;
+    };
+
+    app.layer(middleware::from_fn(move |req: Request, next: Next| {
+        let hsts = hsts.clone();
+        async move {
+            let mut response: Response = next.run(req).await;
+            response
+                .headers_mut()
+                .entry(header::STRICT_TRANSPORT_SECURITY)
+                .or_insert(hsts);
+            response
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(host: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_str(host).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_https_location() {
+        let uri: Uri = "/api/services?tag=web&limit=5".parse().unwrap();
+        assert_eq!(
+            https_location(&headers("registry.example.com:80"), &uri, 443, None).as_deref(),
+            Some("https://registry.example.com/api/services?tag=web&limit=5")
+        );
+        assert_eq!(
+            https_location(&headers("[::1]:3001"), &"/".parse().unwrap(), 8443, None).as_deref(),
+            Some("https://[::1]:8443/")
+        );
+        assert_eq!(
+            https_location(
+                &headers("10.0.0.4"),
+                &uri,
+                443,
+                Some("registry.example.com")
+            )
+            .as_deref(),
+            Some("https://registry.example.com/api/services?tag=web&limit=5")
+        );
+        assert_eq!(https_location(&HeaderMap::new(), &uri, 443, None), None);
+        assert_eq!(https_location(&headers("bad host/"), &uri, 443, None), None);
+    }
+
+    #[test]
+    fn test_hsts_header() {
+        assert!(hsts_header(&ScoutQuestTlsConfig::default()).is_none());
+        let tls_config = ScoutQuestTlsConfig {
+            hsts_max_age_seconds: Some(31_536_000),
+            hsts_include_subdomains: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            hsts_header(&tls_config).unwrap(),
+            "max-age=31536000; includeSubDomains"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_server() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, redirect_router(8443, None)).await;
+        });
+
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let response = client
+            .get(format!(
+                "http://{}/api/discovery/users?healthy=true",
+                address
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://127.0.0.1:8443/api/discovery/users?healthy=true"
+        );
+
+        let response = client
+            .get(format!("http://{}/health", address))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,220 @@
+//! Live reload of the TLS certificates
+//!
+//! The certificate, private key and client CA files are polled for changes
+//! and the server configuration is swapped in place, so rotated
+//! certificates are served to new connections without a restart. Files
+//! that fail to load are logged and the previous certificate keeps serving.
+
+use super::server::build_server_config;
+use crate::ScoutQuestTlsConfig;
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Outcome of the certificate reloads, reported by `/metrics`
+#[derive(Debug, Default)]
+pub struct CertificateReloadStats {
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    last: Mutex<Option<LastReload>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LastReload {
+    at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl CertificateReloadStats {
+    fn record(&self, error: Option<String>) {
+        let counter = match error {
+            Some(_) => &self.failed,
+            None => &self.succeeded,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Some(LastReload {
+            at: Utc::now(),
+            error,
+        });
+    }
+
+    pub fn metrics(&self) -> serde_json::Value {
+        serde_json::json!({
+            "succeeded": self.succeeded.load(Ordering::Relaxed),
+            "failed": self.failed.load(Ordering::Relaxed),
+            "last": *self.last.lock().unwrap_or_else(|e| e.into_inner()),
+        })
+    }
+}
+
+pub struct CertificateReloader {
+    rustls_config: RustlsConfig,
+    tls_config: ScoutQuestTlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    /// Modification times of the watched files when last loaded
+    modified: Mutex<Vec<Option<SystemTime>>>,
+    stats: Arc<CertificateReloadStats>,
+}
+
+impl CertificateReloader {
+    /// Watches the files `rustls_config` was just loaded from
+    pub fn new(
+        rustls_config: RustlsConfig,
+        tls_config: &ScoutQuestTlsConfig,
+        cert_path: &Path,
+        key_path: &Path,
+        stats: Arc<CertificateReloadStats>,
+    ) -> Self {
+        let reloader = Self {
+            rustls_config,
+            tls_config: tls_config.clone(),
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            modified: Mutex::new(Vec::new()),
+            stats,
+        };
+        *reloader.modified.lock().unwrap_or_else(|e| e.into_inner()) = reloader.modified_times();
+        reloader
+    }
+
+    fn watched_paths(&self) -> Vec<&Path> {
+        let mut paths = vec![self.cert_path.as_path(), self.key_path.as_path()];
+        if let Some(client_ca_path) = self
+            .tls_config
+            .client_ca_path
+            .as_deref()
+            .filter(|_| self.tls_config.verify_peer)
+        {
+            paths.push(Path::new(client_ca_path));
+        }
+        paths
+    }
+
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        self.watched_paths()
+            .into_iter()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    /// Swaps the server configuration when one of the files changed. A
+    /// failed load is only retried once the files change again.
+    pub fn reload_if_changed(&self) -> anyhow::Result<bool> {
+        let modified = self.modified_times();
+        {
+            let mut last = self.modified.lock().unwrap_or_else(|e| e.into_inner());
+            if *last == modified {
+                return Ok(false);
+            }
+            *last = modified;
+        }
+
+        match build_server_config(&self.cert_path, &self.key_path, &self.tls_config) {
+            Ok(server_config) => {
+                self.rustls_config
+                    .reload_from_config(Arc::new(server_config));
+                self.stats.record(None);
+                Ok(true)
+            }
+            Err(e) => {
+                self.stats.record(Some(e.to_string()));
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Polls the certificate files every `reload_interval_seconds`
+    pub fn start_watching(self: Arc<Self>) {
+        let interval = self.tls_config.reload_interval_seconds.unwrap_or(30);
+        if interval == 0 {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+            loop {
+                ticker.tick().await;
+                match self.reload_if_changed() {
+                    Ok(true) => tracing::info!(
+                        "🔐 TLS certificate reloaded from {}",
+                        self.cert_path.display()
+                    ),
+                    Ok(false) => {}
+                    Err(e) => tracing::error!(
+                        "❌ Failed to reload TLS certificate, keeping the current one: {}",
+                        e
+                    ),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{generate_self_signed_cert, ServerCertificateSpec};
+    use tempfile::TempDir;
+
+    /// Bumps the modification time, as writes within the same tick of a
+    /// coarse clock would not change it
+    fn touch(path: &Path, seconds: u64) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_serving_on_bad_files() {
+        let dir = TempDir::new().unwrap();
+        let cert_path = dir.path().join("server.crt");
+        let key_path = dir.path().join("server.key");
+        generate_self_signed_cert(&cert_path, &key_path, &ServerCertificateSpec::default())
+            .await
+            .unwrap();
+
+        let tls_config = ScoutQuestTlsConfig::default();
+        let rustls_config = RustlsConfig::from_config(Arc::new(
+            build_server_config(&cert_path, &key_path, &tls_config).unwrap(),
+        ));
+        let stats = Arc::new(CertificateReloadStats::default());
+        let reloader = CertificateReloader::new(
+            rustls_config.clone(),
+            &tls_config,
+            &cert_path,
+            &key_path,
+            stats.clone(),
+        );
+        assert!(!reloader.reload_if_changed().unwrap());
+
+        let original = rustls_config.get_inner();
+        std::fs::write(&cert_path, "not a certificate").unwrap();
+        touch(&cert_path, 1);
+        assert!(reloader.reload_if_changed().is_err());
+        assert!(Arc::ptr_eq(&original, &rustls_config.get_inner()));
+        // Not retried until the files change again
+        assert!(!reloader.reload_if_changed().unwrap());
+
+        generate_self_signed_cert(&cert_path, &key_path, &ServerCertificateSpec::default())
+            .await
+            .unwrap();
+        touch(&cert_path, 2);
+        touch(&key_path, 2);
+        assert!(reloader.reload_if_changed().unwrap());
+        assert!(!Arc::ptr_eq(&original, &rustls_config.get_inner()));
+
+        let metrics = stats.metrics();
+        assert_eq!(metrics["succeeded"], 1);
+        assert_eq!(metrics["failed"], 1);
+        assert!(metrics["last"]["error"].is_null());
+    }
+}
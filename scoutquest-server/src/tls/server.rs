@@ -1,18 +1,36 @@
 //! HTTPS server implementation with Rustls
 
+use super::client_auth::{client_verifier, ClientCertAcceptor};
+use super::redirect::{hsts_header, start_http_redirect_server, with_hsts};
+use super::reload::{CertificateReloadStats, CertificateReloader};
 use super::utils::{log_tls_info, sanitize_path_for_logging};
-use super::{ensure_certificates, get_certificate_paths, validate_tls_config, TlsError};
+use super::{
+    ensure_certificates, get_certificate_paths, start_certificate_renewal, validate_tls_config,
+    CertificateAuthority, CertificateInfo, ServerCertificateSpec, TlsError, TlsPolicy,
+};
 use crate::{AppConfig, ScoutQuestTlsConfig, ServerConfig};
 use axum::Router;
 use axum_server::tls_rustls::RustlsConfig;
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
 use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
 
 /// Starts the HTTPS server with TLS configuration
 pub async fn start_https_server(
     app: Router,
     server_config: &ServerConfig,
     tls_config: &ScoutQuestTlsConfig,
+    ca: Option<Arc<CertificateAuthority>>,
+    reload_stats: Arc<CertificateReloadStats>,
 ) -> anyhow::Result<()> {
+    // Clients are verified against the internal CA unless told otherwise
+    let mut tls_config = tls_config.clone();
+    if let (Some(ca), None) = (&ca, &tls_config.client_ca_path) {
+        tls_config.client_ca_path = Some(ca.cert_path().to_string_lossy().to_string());
+    }
+    let tls_config = &tls_config;
+
     // Validate TLS configuration
     validate_tls_config(tls_config)?;
 
@@ -26,11 +44,36 @@ pub async fn start_https_server(
     // Get certificate paths
     let (cert_path, key_path) = get_certificate_paths(tls_config);
 
-    // Ensure certificates exist (generate if needed)
-    ensure_certificates(&cert_path, &key_path, tls_config.auto_generate).await?;
+    // Ensure certificates exist (generate or renew if needed)
+    let spec = ServerCertificateSpec::from_config(tls_config)?;
+    ensure_certificates(
+        &cert_path,
+        &key_path,
+        tls_config.auto_generate,
+        &spec,
+        ca.as_deref(),
+    )
+    .await?;
+    let expiry = CertificateInfo::parse(&std::fs::read_to_string(&cert_path)?)?.not_after;
 
     // Load TLS configuration
     let rustls_config = load_rustls_config(&cert_path, &key_path, tls_config).await?;
+    let reloader = Arc::new(CertificateReloader::new(
+        rustls_config.clone(),
+        tls_config,
+        &cert_path,
+        &key_path,
+        reload_stats,
+    ));
+    reloader.clone().start_watching();
+    start_certificate_renewal(
+        cert_path.clone(),
+        key_path.clone(),
+        tls_config.auto_generate,
+        spec,
+        ca,
+        reloader,
+    );
 
     // Create server address
     let addr = SocketAddr::from((
@@ -43,75 +86,107 @@ pub async fn start_https_server(
     tracing::info!("   Auto-generate: {}", tls_config.auto_generate);
     tracing::info!("   Certificate: {}", sanitize_path_for_logging(&cert_path));
     tracing::info!("   Private key: {}", sanitize_path_for_logging(&key_path));
+    tracing::info!("   Certificate expires: {}", expiry);
     tracing::info!("   Verify peer: {}", tls_config.verify_peer);
+    if let Some(hsts) = hsts_header(tls_config) {
+        tracing::info!("   HSTS: {}", hsts.to_str().unwrap_or_default());
+    }
+    TlsPolicy::from_config(tls_config)?.log();
+    match tls_config.reload_interval_seconds.unwrap_or(30) {
+        0 => tracing::info!("   Certificate reload: disabled"),
+        interval => tracing::info!("   Certificate reload: every {}s", interval),
+    }
+    if let Some(client_ca_path) = tls_config
+        .client_ca_path
+        .as_deref()
+        .filter(|_| tls_config.verify_peer)
+    {
+        tracing::info!(
+            "   Client CA: {}",
+            sanitize_path_for_logging(Path::new(client_ca_path))
+        );
+    }
 
     // Start HTTP redirect server if enabled
     if tls_config.redirect_http.unwrap_or(false) {
         let http_port = tls_config.http_port.unwrap_or(3001);
-        start_http_redirect_server(&server_config.host, http_port, server_config.port).await?;
+        start_http_redirect_server(
+            &server_config.host,
+            http_port,
+            server_config.port,
+            tls_config.canonical_host.clone(),
+        )
+        .await?;
     }
 
     // Start HTTPS server
+    let app = with_hsts(app, tls_config);
     let listener = std::net::TcpListener::bind(addr)?;
-    axum_server::from_tcp_rustls(listener, rustls_config)?
-        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
-        .await?;
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+    if tls_config.verify_peer {
+        axum_server::from_tcp(listener)?
+            .acceptor(ClientCertAcceptor::new(rustls_config))
+            .serve(make_service)
+            .await?;
+    } else {
+        axum_server::from_tcp_rustls(listener, rustls_config)?
+            .serve(make_service)
+            .await?;
+    }
 
     Ok(())
 }
 
 /// Loads Rustls configuration from certificate files
-async fn load_rustls_config(
-    cert_path: &std::path::Path,
-    key_path: &std::path::Path,
-    _tls_config: &ScoutQuestTlsConfig,
+pub(crate) async fn load_rustls_config(
+    cert_path: &Path,
+    key_path: &Path,
+    tls_config: &ScoutQuestTlsConfig,
 ) -> Result<RustlsConfig, TlsError> {
     tracing::info!("🔐 Loading TLS certificates...");
 
-    let rustls_config = RustlsConfig::from_pem_file(cert_path, key_path)
-        .await
-        .map_err(|e| TlsError::CertificateLoad(format!("Failed to load TLS config: {}", e)))?;
+    let server_config = build_server_config(cert_path, key_path, tls_config)?;
 
     tracing::info!("✅ TLS certificates loaded successfully");
-    Ok(rustls_config)
+    Ok(RustlsConfig::from_config(Arc::new(server_config)))
 }
 
-/// Starts an HTTP redirect server that redirects all traffic to HTTPS
-async fn start_http_redirect_server(
-    host: &str,
-    http_port: u16,
-    https_port: u16,
-) -> anyhow::Result<()> {
-    use axum::{http::Uri, response::Redirect, routing::any};
-
-    let redirect_app = Router::new().route(
-        "/*path",
-        any(move |uri: Uri| async move {
-            let https_uri = if https_port == 443 {
-                format!("https://localhost{}", uri.path())
-            } else {
-                format!("https://localhost:{}{}", https_port, uri.path())
-            };
-
-            tracing::debug!("🔄 Redirecting HTTP request to: {}", https_uri);
-            Redirect::permanent(&https_uri)
-        }),
-    );
-
-    let http_addr = SocketAddr::from((host.parse::<std::net::IpAddr>()?, http_port));
-
-    tracing::info!("🔄 Starting HTTP redirect server on http://{}", http_addr);
-    tracing::info!("   Redirecting to HTTPS port: {}", https_port);
+/// Builds the Rustls server configuration, requiring client certificates
+/// chained to `client_ca_path` when `verify_peer` is set
+pub(crate) fn build_server_config(
+    cert_path: &Path,
+    key_path: &Path,
+    tls_config: &ScoutQuestTlsConfig,
+) -> Result<rustls::ServerConfig, TlsError> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            TlsError::CertificateLoad(format!(
+                "Failed to read certificate {}: {}",
+                cert_path.display(),
+                e
+            ))
+        })?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| {
+        TlsError::CertificateLoad(format!(
+            "Failed to read private key {}: {}",
+            key_path.display(),
+            e
+        ))
+    })?;
 
-    // Start HTTP redirect server in background
-    let listener = tokio::net::TcpListener::bind(http_addr).await?;
-    tokio::spawn(async move {
-        if let Err(e) = axum::serve(listener, redirect_app).await {
-            tracing::error!("HTTP redirect server error: {}", e);
+    let policy = TlsPolicy::from_config(tls_config)?;
+    let builder = policy.server_config_builder()?;
+    let builder = match tls_config.client_ca_path.as_deref() {
+        Some(client_ca_path) if tls_config.verify_peer => {
+            builder.with_client_cert_verifier(client_verifier(Path::new(client_ca_path))?)
         }
-    });
+        _ => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder.with_single_cert(certs, key)?;
+    server_config.alpn_protocols = policy.alpn_protocols();
 
-    Ok(())
+    Ok(server_config)
 }
 
 /// Starts the regular HTTP server (fallback when TLS is disabled)
@@ -135,11 +210,16 @@ pub async fn start_http_server(app: Router, server_config: &ServerConfig) -> any
 }
 
 /// Main server startup function that decides between HTTP and HTTPS
-pub async fn start_server(app: Router, config: &AppConfig) -> anyhow::Result<()> {
+pub async fn start_server(
+    app: Router,
+    config: &AppConfig,
+    ca: Option<Arc<CertificateAuthority>>,
+    reload_stats: Arc<CertificateReloadStats>,
+) -> anyhow::Result<()> {
     // Check if TLS is enabled
     if let Some(tls_config) = &config.tls {
         if tls_config.enabled {
-            return start_https_server(app, &config.server, tls_config).await;
+            return start_https_server(app, &config.server, tls_config, ca, reload_stats).await;
         }
     }
 
@@ -178,4 +258,56 @@ mod tests {
         assert!(tls_config.enabled);
         assert_eq!(tls_config.cert_dir, "/tmp/test-certs");
     }
+
+    #[tokio::test]
+    async fn test_min_version_enforced() {
+        use super::super::generate_self_signed_cert;
+        use rustls::pki_types::ServerName;
+        use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let cert_path = dir.path().join("server.crt");
+        let key_path = dir.path().join("server.key");
+        generate_self_signed_cert(&cert_path, &key_path, &ServerCertificateSpec::default())
+            .await
+            .unwrap();
+
+        let tls_config = ScoutQuestTlsConfig {
+            min_version: Some("1.3".to_string()),
+            alpn_protocols: Some(vec!["http/1.1".to_string()]),
+            ..Default::default()
+        };
+        let server_config = build_server_config(&cert_path, &key_path, &tls_config).unwrap();
+        assert_eq!(server_config.alpn_protocols, vec![b"http/1.1".to_vec()]);
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let _ = acceptor.accept(stream).await;
+            }
+        });
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_file(&cert_path).unwrap())
+            .unwrap();
+        let handshake = |versions: &'static [&'static rustls::SupportedProtocolVersion]| {
+            let config = rustls::ClientConfig::builder_with_protocol_versions(versions)
+                .with_root_certificates(roots.clone())
+                .with_no_client_auth();
+            async move {
+                let stream = tokio::net::TcpStream::connect(address).await?;
+                TlsConnector::from(Arc::new(config))
+                    .connect(ServerName::try_from("localhost").unwrap(), stream)
+                    .await
+            }
+        };
+
+        static TLS12_ONLY: &[&rustls::SupportedProtocolVersion] = &[&rustls::version::TLS12];
+        static TLS13_ONLY: &[&rustls::SupportedProtocolVersion] = &[&rustls::version::TLS13];
+        assert!(handshake(TLS13_ONLY).await.is_ok());
+        assert!(handshake(TLS12_ONLY).await.is_err());
+    }
 }
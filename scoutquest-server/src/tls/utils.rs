@@ -1,5 +1,7 @@
 //! TLS utilities and helper functions
 
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::path::Path;
 
 /// Logs TLS configuration information
@@ -36,11 +38,53 @@ pub fn sanitize_path_for_logging(path: &Path) -> String {
     }
 }
 
+/// Writes a private key readable by its owner only.
+///
+/// The key goes to a new file created with mode 0600, so it is never
+/// readable by others, which then replaces `path`.
+pub fn write_private_key(path: &Path, pem: &str) -> std::io::Result<()> {
+    let tmp_path = path.with_extension("key.tmp");
+    // Left over by an interrupted write, possibly with other permissions
+    match fs::remove_file(&tmp_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&tmp_path)?;
+    file.write_all(pem.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
 
+    #[cfg(unix)]
+    #[test]
+    fn test_private_key_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("server.key");
+        fs::write(&path, "old key").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private_key(&path, "new key").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new key");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
     #[test]
     fn test_sanitize_path_for_logging() {
         let path = PathBuf::from("/etc/ssl/certs/server.crt");
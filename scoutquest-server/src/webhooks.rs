@@ -0,0 +1,647 @@
+//! Outbound webhook notifications for registry events
+//!
+//! Each `[[webhooks]]` entry gets its own queue and worker, so a slow or
+//! failing endpoint never delays the others and events reach each endpoint
+//! in order. Deliveries are retried with exponential backoff, and the
+//! outcome of recent deliveries is kept for `GET /api/admin/webhooks`.
+//!
+//! Payloads are signed with HMAC-SHA256 when a secret is configured: the
+//! `X-ScoutQuest-Signature` header holds `sha256=<hex digest of the body>`.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use uuid::Uuid;
+
+use crate::events::EventFilter;
+use crate::models::{EventType, ServiceEvent};
+use crate::registry::ServiceRegistry;
+use crate::WebhookConfig;
+
+pub const SIGNATURE_HEADER: &str = "x-scoutquest-signature";
+pub const EVENT_HEADER: &str = "x-scoutquest-event";
+pub const DELIVERY_HEADER: &str = "x-scoutquest-delivery";
+
+/// Events waiting for delivery per webhook before new ones are dropped
+const QUEUE_SIZE: usize = 1000;
+/// Deliveries kept per webhook for the admin endpoint
+const RECENT_DELIVERIES: usize = 50;
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Retrying,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryRecord {
+    pub id: String,
+    pub event_sequence: u64,
+    pub event_type: EventType,
+    pub service_name: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WebhookStats {
+    pub delivered: u64,
+    pub failed: u64,
+    /// Events not queued because the webhook was too far behind
+    pub dropped: u64,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub recent_deliveries: VecDeque<DeliveryRecord>,
+}
+
+/// Delivery status of one webhook, as reported by the admin endpoint
+#[derive(Debug, Serialize)]
+pub struct WebhookReport {
+    pub name: String,
+    pub url: String,
+    pub event_types: Vec<EventType>,
+    pub services: Vec<String>,
+    pub signed: bool,
+    #[serde(flatten)]
+    pub stats: WebhookStats,
+}
+
+struct Webhook {
+    name: String,
+    config: WebhookConfig,
+    filter: EventFilter,
+    stats: Mutex<WebhookStats>,
+}
+
+/// A webhook and the queue of its delivery worker, once started
+struct Registered {
+    webhook: Arc<Webhook>,
+    queue: Option<mpsc::Sender<ServiceEvent>>,
+}
+
+pub struct WebhookDispatcher {
+    webhooks: Arc<RwLock<Vec<Registered>>>,
+    /// Set once `start` spawned the event router
+    started: AtomicBool,
+    http_client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(configs: &[WebhookConfig]) -> anyhow::Result<Self> {
+        let webhooks = build_webhooks(configs)?
+            .into_iter()
+            .map(|webhook| Registered {
+                webhook,
+                queue: None,
+            })
+            .collect();
+
+        Ok(Self {
+            webhooks: Arc::new(RwLock::new(webhooks)),
+            started: AtomicBool::new(false),
+            http_client: reqwest::Client::new(),
+        })
+    }
+
+    /// Starts one delivery worker per webhook, fed from the registry events
+    pub fn start(&self, registry: Arc<ServiceRegistry>) {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let mut webhooks = self.write_webhooks();
+        for registered in webhooks.iter_mut() {
+            registered.queue = Some(self.spawn_worker(&registered.webhook));
+        }
+
+        // Runs even without webhooks, as a reload may add some
+        let events = registry.subscribe_events();
+        tokio::spawn(route_events(registry, events, self.webhooks.clone()));
+
+        if !webhooks.is_empty() {
+            tracing::info!(
+                "🪝 Webhook dispatcher started ({} webhooks)",
+                webhooks.len()
+            );
+        }
+    }
+
+    fn spawn_worker(&self, webhook: &Arc<Webhook>) -> mpsc::Sender<ServiceEvent> {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(run_worker(
+            webhook.clone(),
+            self.http_client.clone(),
+            receiver,
+        ));
+        sender
+    }
+
+    /// Replaces the webhooks with `configs`. Unchanged webhooks keep their
+    /// queue and statistics, removed ones finish the deliveries already
+    /// queued.
+    pub fn reload(&self, configs: &[WebhookConfig]) -> anyhow::Result<()> {
+        let webhooks = build_webhooks(configs)?;
+        let started = self.started.load(Ordering::SeqCst);
+
+        let mut current = self.write_webhooks();
+        let mut previous: Vec<Registered> = current.drain(..).collect();
+        for webhook in webhooks {
+            let registered = match previous
+                .iter()
+                .position(|registered| registered.webhook.config == webhook.config)
+            {
+                Some(index) => previous.swap_remove(index),
+                None => Registered {
+                    queue: started.then(|| self.spawn_worker(&webhook)),
+                    webhook,
+                },
+            };
+            current.push(registered);
+        }
+
+        Ok(())
+    }
+
+    pub fn status(&self) -> Vec<WebhookReport> {
+        self.read_webhooks()
+            .iter()
+            .map(|Registered { webhook, .. }| WebhookReport {
+                name: webhook.name.clone(),
+                url: webhook.config.url.clone(),
+                event_types: webhook.config.event_types.clone(),
+                services: webhook.config.services.clone(),
+                signed: webhook.config.secret.is_some(),
+                stats: webhook.lock_stats().clone(),
+            })
+            .collect()
+    }
+
+    fn read_webhooks(&self) -> RwLockReadGuard<'_, Vec<Registered>> {
+        self.webhooks.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_webhooks(&self) -> RwLockWriteGuard<'_, Vec<Registered>> {
+        self.webhooks.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn build_webhooks(configs: &[WebhookConfig]) -> anyhow::Result<Vec<Arc<Webhook>>> {
+    configs
+        .iter()
+        .map(|config| {
+            reqwest::Url::parse(&config.url)
+                .map_err(|e| anyhow::anyhow!("Invalid webhook URL '{}': {}", config.url, e))?;
+
+            Ok(Arc::new(Webhook {
+                name: config.name.clone().unwrap_or_else(|| config.url.clone()),
+                filter: EventFilter {
+                    services: config.services.clone(),
+                    event_types: config.event_types.clone(),
+                    tags: vec![],
+                },
+                config: config.clone(),
+                stats: Mutex::new(WebhookStats::default()),
+            }))
+        })
+        .collect()
+}
+
+async fn route_events(
+    registry: Arc<ServiceRegistry>,
+    mut events: broadcast::Receiver<ServiceEvent>,
+    webhooks: Arc<RwLock<Vec<Registered>>>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                // Every cluster member sees the same events, only the leader notifies
+                if !registry.is_leader() {
+                    continue;
+                }
+
+                let webhooks = webhooks.read().unwrap_or_else(|e| e.into_inner());
+                for Registered { webhook, queue } in webhooks.iter() {
+                    let Some(queue) = queue else {
+                        continue;
+                    };
+                    if !webhook.filter.matches(&event) {
+                        continue;
+                    }
+                    if queue.try_send(event.clone()).is_err() {
+                        tracing::warn!("🪝 Webhook {} queue full, event dropped", webhook.name);
+                        webhook.lock_stats().dropped += 1;
+                    }
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(count)) => {
+                tracing::warn!("🪝 Webhook dispatcher lagging, {} events dropped", count);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn run_worker(
+    webhook: Arc<Webhook>,
+    http_client: reqwest::Client,
+    mut queue: mpsc::Receiver<ServiceEvent>,
+) {
+    while let Some(event) = queue.recv().await {
+        webhook.deliver(&http_client, event).await;
+    }
+}
+
+impl Webhook {
+    async fn deliver(&self, http_client: &reqwest::Client, event: ServiceEvent) {
+        let delivery_id = Uuid::new_v4().to_string();
+        let body = match serde_json::to_vec(&serde_json::json!({
+            "id": delivery_id,
+            "webhook": self.name,
+            "event": event
+        })) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!("❌ Failed to encode webhook payload: {}", e);
+                return;
+            }
+        };
+        let event_type = serde_json::to_value(&event.event_type)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default();
+
+        let now = Utc::now();
+        self.record(DeliveryRecord {
+            id: delivery_id.clone(),
+            event_sequence: event.sequence,
+            event_type: event.event_type.clone(),
+            service_name: event.service_name.clone(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            response_status: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        });
+
+        let mut backoff = Duration::from_millis(self.config.initial_backoff_ms).min(MAX_BACKOFF);
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+
+            let mut request = http_client
+                .post(&self.config.url)
+                .timeout(Duration::from_secs(self.config.timeout_seconds))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, &event_type)
+                .header(DELIVERY_HEADER, &delivery_id)
+                .body(body.clone());
+            if let Some(secret) = &self.config.secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+            }
+
+            let (response_status, error) = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    self.complete(
+                        &delivery_id,
+                        attempts,
+                        Some(response.status().as_u16()),
+                        None,
+                    );
+                    return;
+                }
+                Ok(response) => (
+                    Some(response.status().as_u16()),
+                    format!("HTTP {}", response.status()),
+                ),
+                Err(e) => (None, e.to_string()),
+            };
+
+            if attempts > self.config.max_retries {
+                tracing::warn!(
+                    "🪝 Webhook {} delivery {} failed after {} attempts: {}",
+                    self.name,
+                    delivery_id,
+                    attempts,
+                    error
+                );
+                self.complete(&delivery_id, attempts, response_status, Some(error));
+                return;
+            }
+
+            self.update(&delivery_id, |record| {
+                record.status = DeliveryStatus::Retrying;
+                record.attempts = attempts;
+                record.response_status = response_status;
+                record.error = Some(error.clone());
+            });
+
+            tokio::time::sleep(backoff).await;
+            backoff = backoff.saturating_mul(2).min(MAX_BACKOFF);
+        }
+    }
+
+    fn complete(
+        &self,
+        delivery_id: &str,
+        attempts: u32,
+        response_status: Option<u16>,
+        error: Option<String>,
+    ) {
+        let delivered = error.is_none();
+        self.update(delivery_id, |record| {
+            record.status = if delivered {
+                DeliveryStatus::Delivered
+            } else {
+                DeliveryStatus::Failed
+            };
+            record.attempts = attempts;
+            record.response_status = response_status;
+            record.error = error;
+        });
+
+        let mut stats = self.lock_stats();
+        if delivered {
+            stats.delivered += 1;
+            stats.last_success = Some(Utc::now());
+        } else {
+            stats.failed += 1;
+            stats.last_failure = Some(Utc::now());
+        }
+    }
+
+    fn record(&self, record: DeliveryRecord) {
+        let mut stats = self.lock_stats();
+        if stats.recent_deliveries.len() == RECENT_DELIVERIES {
+            stats.recent_deliveries.pop_front();
+        }
+        stats.recent_deliveries.push_back(record);
+    }
+
+    fn update(&self, delivery_id: &str, change: impl FnOnce(&mut DeliveryRecord)) {
+        let mut stats = self.lock_stats();
+        if let Some(record) = stats
+            .recent_deliveries
+            .iter_mut()
+            .rev()
+            .find(|record| record.id == delivery_id)
+        {
+            change(record);
+            record.updated_at = Utc::now();
+        }
+    }
+
+    fn lock_stats(&self) -> MutexGuard<'_, WebhookStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Value of the signature header for `body`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{InstanceStatus, RegisterServiceRequest};
+    use axum::{body::Bytes, extract::State, http::HeaderMap, http::StatusCode, routing::post};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone, Default)]
+    struct Receiver {
+        calls: Arc<AtomicUsize>,
+        received: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+    }
+
+    /// Fails the first call, accepts the next ones
+    async fn receive(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        if receiver.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        receiver.received.lock().unwrap().push((headers, body));
+        StatusCode::NO_CONTENT
+    }
+
+    async fn start_receiver() -> (String, Receiver) {
+        let receiver = Receiver::default();
+        let app = axum::Router::new()
+            .route("/hook", post(receive))
+            .with_state(receiver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        (format!("http://{}/hook", address), receiver)
+    }
+
+    fn register_request(service_name: &str) -> RegisterServiceRequest {
+        RegisterServiceRequest {
+            service_name: service_name.to_string(),
+            host: "localhost".to_string(),
+            port: 3000,
+            secure: None,
+            metadata: None,
+            tags: None,
+            health_check: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delivery_retried_and_signed() {
+        let (url, receiver) = start_receiver().await;
+        let dispatcher = WebhookDispatcher::new(&[WebhookConfig {
+            name: Some("on-call".to_string()),
+            url,
+            services: vec!["orders".to_string()],
+            secret: Some("s3cret".to_string()),
+            initial_backoff_ms: 10,
+            ..Default::default()
+        }])
+        .unwrap();
+
+        let registry = Arc::new(ServiceRegistry::new());
+        dispatcher.start(registry.clone());
+        registry
+            .register_instance(register_request("users"))
+            .await
+            .unwrap();
+        registry
+            .register_instance(register_request("orders"))
+            .await
+            .unwrap();
+
+        let mut delivered = false;
+        for _ in 0..100 {
+            if dispatcher.status()[0].stats.delivered == 1 {
+                delivered = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(delivered);
+
+        let report = &dispatcher.status()[0];
+        let record = &report.stats.recent_deliveries[0];
+        assert_eq!(report.stats.recent_deliveries.len(), 1);
+        assert_eq!(record.status, DeliveryStatus::Delivered);
+        assert_eq!(record.attempts, 2);
+        assert_eq!(record.service_name, "orders");
+
+        let received = receiver.received.lock().unwrap();
+        let (headers, body) = &received[0];
+        assert_eq!(headers[SIGNATURE_HEADER], sign("s3cret", body).as_str());
+        assert_eq!(headers[EVENT_HEADER], "ServiceRegistered");
+
+        let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload["webhook"], "on-call");
+        assert_eq!(payload["event"]["service_name"], "orders");
+    }
+
+    #[tokio::test]
+    async fn test_flapping_instance_delivered() {
+        let (url, receiver) = start_receiver().await;
+        let dispatcher = WebhookDispatcher::new(&[WebhookConfig {
+            url,
+            event_types: vec![EventType::InstanceFlapping],
+            initial_backoff_ms: 10,
+            ..Default::default()
+        }])
+        .unwrap();
+
+        let registry =
+            Arc::new(ServiceRegistry::new().with_flap_detection(3, Duration::from_secs(60)));
+        dispatcher.start(registry.clone());
+        let instance = registry
+            .register_instance(register_request("users"))
+            .await
+            .unwrap();
+        for status in [
+            InstanceStatus::Down,
+            InstanceStatus::Up,
+            InstanceStatus::Down,
+        ] {
+            registry
+                .update_instance_status(&instance.id, status)
+                .await
+                .unwrap();
+        }
+
+        for _ in 0..100 {
+            if dispatcher.status()[0].stats.delivered == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let report = &dispatcher.status()[0];
+        assert_eq!(report.stats.delivered, 1);
+        assert_eq!(report.stats.recent_deliveries.len(), 1);
+        let received = receiver.received.lock().unwrap();
+        assert_eq!(received[0].0[EVENT_HEADER], "InstanceFlapping");
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_recorded() {
+        let dispatcher = WebhookDispatcher::new(&[WebhookConfig {
+            url: "http://127.0.0.1:9/unreachable".to_string(),
+            max_retries: 1,
+            initial_backoff_ms: 10,
+            ..Default::default()
+        }])
+        .unwrap();
+
+        let registry = Arc::new(ServiceRegistry::new());
+        dispatcher.start(registry.clone());
+        registry
+            .register_instance(register_request("users"))
+            .await
+            .unwrap();
+
+        for _ in 0..100 {
+            if dispatcher.status()[0].stats.failed == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let report = &dispatcher.status()[0];
+        assert_eq!(report.name, "http://127.0.0.1:9/unreachable");
+        assert_eq!(report.stats.failed, 1);
+        let record = &report.stats.recent_deliveries[0];
+        assert_eq!(record.status, DeliveryStatus::Failed);
+        assert_eq!(record.attempts, 2);
+        assert!(record.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_unchanged_webhooks() {
+        let kept = WebhookConfig {
+            url: "http://127.0.0.1:9/kept".to_string(),
+            ..Default::default()
+        };
+        let dispatcher = WebhookDispatcher::new(&[
+            kept.clone(),
+            WebhookConfig {
+                url: "http://127.0.0.1:9/removed".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        dispatcher.start(Arc::new(ServiceRegistry::new()));
+        dispatcher.read_webhooks()[0].webhook.lock_stats().delivered = 3;
+
+        let added = WebhookConfig {
+            url: "http://127.0.0.1:9/added".to_string(),
+            ..Default::default()
+        };
+        dispatcher.reload(&[added, kept]).unwrap();
+        let status = dispatcher.status();
+        assert_eq!(status.len(), 2);
+        assert_eq!(status[0].url, "http://127.0.0.1:9/added");
+        assert!(dispatcher.read_webhooks()[0].queue.is_some());
+        assert_eq!(status[1].url, "http://127.0.0.1:9/kept");
+        assert_eq!(status[1].stats.delivered, 3);
+
+        // Invalid webhooks leave the current ones in place
+        assert!(dispatcher
+            .reload(&[WebhookConfig {
+                url: "not a url".to_string(),
+                ..Default::default()
+            }])
+            .is_err());
+        assert_eq!(dispatcher.status().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_url_rejected() {
+        let result = WebhookDispatcher::new(&[WebhookConfig {
+            url: "not a url".to_string(),
+            ..Default::default()
+        }]);
+        assert!(result.is_err());
+    }
+}
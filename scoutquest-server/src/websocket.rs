@@ -0,0 +1,323 @@
+//! Real-time event stream over WebSocket
+//!
+//! Clients connect to `/ws` and manage subscriptions with JSON messages:
+//!
+//! ```json
+//! {"type": "subscribe", "id": "orders", "services": ["orders"], "event_types": ["InstanceStatusChanged"], "tags": ["prod"]}
+//! {"type": "unsubscribe", "id": "orders"}
+//! ```
+//!
+//! A new connection receives nothing until it subscribes; a subscription
+//! without criteria receives every event. Each matching event is pushed once,
+//! listing the subscriptions it matched. When the client falls too far behind
+//! the registry, it gets an `events_dropped` message with the number of
+//! events it missed instead of a silent gap.
+//!
+//! A connection holds at most [`MAX_SUBSCRIPTIONS`] subscriptions of at most
+//! [`MAX_FILTER_VALUES`] criteria each; subscriptions over the limits are
+//! answered with an error.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::sync::broadcast;
+
+use crate::events::EventFilter;
+use crate::models::ServiceEvent;
+use crate::AppState;
+
+/// Subscriptions a single connection can hold
+const MAX_SUBSCRIPTIONS: usize = 32;
+
+/// Services, event types and tags a single subscription can list
+const MAX_FILTER_VALUES: usize = 64;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe {
+        id: Option<String>,
+        #[serde(flatten)]
+        filter: EventFilter,
+    },
+    Unsubscribe {
+        id: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Subscribed {
+        id: String,
+        filter: EventFilter,
+    },
+    Unsubscribed {
+        id: String,
+    },
+    Event {
+        subscriptions: Vec<String>,
+        event: ServiceEvent,
+    },
+    EventsDropped {
+        count: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+pub async fn websocket_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    let events = state.registry.subscribe_events();
+    ws.on_upgrade(move |socket| handle_socket(socket, events))
+}
+
+async fn handle_socket(mut socket: WebSocket, mut events: broadcast::Receiver<ServiceEvent>) {
+    let mut subscriptions = Subscriptions::default();
+
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => Some(subscriptions.handle(&text)),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => None,
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    let matched = subscriptions.matching(&event);
+                    (!matched.is_empty()).then_some(ServerMessage::Event {
+                        subscriptions: matched,
+                        event,
+                    })
+                }
+                Err(broadcast::error::RecvError::Lagged(count)) => {
+                    tracing::warn!("📡 WebSocket client lagging, {} events dropped", count);
+                    Some(ServerMessage::EventsDropped { count })
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+
+        let Some(reply) = reply else {
+            continue;
+        };
+
+        let text = match serde_json::to_string(&reply) {
+            Ok(text) => text,
+            Err(e) => {
+                tracing::error!("❌ Failed to encode WebSocket message: {}", e);
+                continue;
+            }
+        };
+
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Active subscriptions of one connection, by id
+#[derive(Default)]
+struct Subscriptions {
+    filters: BTreeMap<String, EventFilter>,
+    next_id: u64,
+}
+
+impl Subscriptions {
+    fn handle(&mut self, text: &str) -> ServerMessage {
+        match serde_json::from_str::<ClientMessage>(text) {
+            Ok(ClientMessage::Subscribe { id, filter }) => {
+                let values = filter.services.len() + filter.event_types.len() + filter.tags.len();
+                if values > MAX_FILTER_VALUES {
+                    return ServerMessage::Error {
+                        message: format!(
+                            "Subscription lists {} criteria, at most {} are allowed",
+                            values, MAX_FILTER_VALUES
+                        ),
+                    };
+                }
+
+                // Replacing an existing subscription is always allowed
+                let replaces = id.as_ref().is_some_and(|id| self.filters.contains_key(id));
+                if !replaces && self.filters.len() >= MAX_SUBSCRIPTIONS {
+                    return ServerMessage::Error {
+                        message: format!(
+                            "Subscription limit reached ({} per connection)",
+                            MAX_SUBSCRIPTIONS
+                        ),
+                    };
+                }
+
+                let id = id.unwrap_or_else(|| {
+                    self.next_id += 1;
+                    format!("sub-{}", self.next_id)
+                });
+                self.filters.insert(id.clone(), filter.clone());
+                ServerMessage::Subscribed { id, filter }
+            }
+            Ok(ClientMessage::Unsubscribe { id }) => match self.filters.remove(&id) {
+                Some(_) => ServerMessage::Unsubscribed { id },
+                None => ServerMessage::Error {
+                    message: format!("Unknown subscription: {}", id),
+                },
+            },
+            Err(e) => ServerMessage::Error {
+                message: format!("Invalid message: {}", e),
+            },
+        }
+    }
+
+    fn matching(&self, event: &ServiceEvent) -> Vec<String> {
+        self.filters
+            .iter()
+            .filter(|(_, filter)| filter.matches(event))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RegisterServiceRequest;
+    use crate::registry::ServiceRegistry;
+    use axum::{routing::get, Router};
+    use futures_util::{SinkExt, StreamExt};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message as ClientFrame;
+
+    type Client = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn start_server(registry: Arc<ServiceRegistry>) -> String {
+        let state = AppState::for_tests(registry);
+        let app = Router::new()
+            .route("/ws", get(websocket_handler))
+            .with_state(state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        format!("ws://{}/ws", address)
+    }
+
+    async fn send(client: &mut Client, message: serde_json::Value) {
+        client
+            .send(ClientFrame::Text(message.to_string().into()))
+            .await
+            .unwrap();
+    }
+
+    async fn receive(client: &mut Client) -> serde_json::Value {
+        let frame = tokio::time::timeout(Duration::from_secs(2), client.next())
+            .await
+            .expect("no message received")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(frame.to_text().unwrap()).unwrap()
+    }
+
+    fn register_request(service_name: &str) -> RegisterServiceRequest {
+        RegisterServiceRequest {
+            service_name: service_name.to_string(),
+            host: "localhost".to_string(),
+            port: 3000,
+            secure: None,
+            metadata: None,
+            tags: Some(vec!["prod".to_string()]),
+            health_check: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscription_receives_matching_events() {
+        let registry = Arc::new(ServiceRegistry::new());
+        let url = start_server(registry.clone()).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        send(
+            &mut client,
+            serde_json::json!({"type": "subscribe", "id": "orders", "services": ["orders"], "tags": ["prod"]}),
+        )
+        .await;
+        let reply = receive(&mut client).await;
+        assert_eq!(reply["type"], "subscribed");
+        assert_eq!(reply["id"], "orders");
+
+        registry
+            .register_instance(register_request("users"))
+            .await
+            .unwrap();
+        registry
+            .register_instance(register_request("orders"))
+            .await
+            .unwrap();
+
+        let reply = receive(&mut client).await;
+        assert_eq!(reply["type"], "event");
+        assert_eq!(reply["subscriptions"], serde_json::json!(["orders"]));
+        assert_eq!(reply["event"]["service_name"], "orders");
+        assert_eq!(reply["event"]["event_type"], "ServiceRegistered");
+
+        send(
+            &mut client,
+            serde_json::json!({"type": "unsubscribe", "id": "orders"}),
+        )
+        .await;
+        assert_eq!(receive(&mut client).await["type"], "unsubscribed");
+
+        send(
+            &mut client,
+            serde_json::json!({"type": "unsubscribe", "id": "orders"}),
+        )
+        .await;
+        assert_eq!(receive(&mut client).await["type"], "error");
+    }
+
+    #[test]
+    fn test_subscriptions_generate_ids_and_reject_bad_messages() {
+        let mut subscriptions = Subscriptions::default();
+
+        let reply = subscriptions.handle(r#"{"type": "subscribe"}"#);
+        assert!(matches!(reply, ServerMessage::Subscribed { ref id, .. } if id == "sub-1"));
+
+        let reply = subscriptions.handle(r#"{"type": "subscribe", "event_types": ["Bogus"]}"#);
+        assert!(matches!(reply, ServerMessage::Error { .. }));
+        assert_eq!(subscriptions.filters.len(), 1);
+    }
+
+    #[test]
+    fn test_subscriptions_are_capped() {
+        let mut subscriptions = Subscriptions::default();
+        for _ in 0..MAX_SUBSCRIPTIONS {
+            let reply = subscriptions.handle(r#"{"type": "subscribe"}"#);
+            assert!(matches!(reply, ServerMessage::Subscribed { .. }));
+        }
+
+        let reply = subscriptions.handle(r#"{"type": "subscribe"}"#);
+        assert!(matches!(reply, ServerMessage::Error { ref message } if message.contains("limit")));
+        assert_eq!(subscriptions.filters.len(), MAX_SUBSCRIPTIONS);
+
+        // Existing subscriptions can still be replaced
+        let reply =
+            subscriptions.handle(r#"{"type": "subscribe", "id": "sub-1", "tags": ["prod"]}"#);
+        assert!(matches!(reply, ServerMessage::Subscribed { .. }));
+
+        subscriptions.handle(r#"{"type": "unsubscribe", "id": "sub-2"}"#);
+        let services: Vec<String> = (0..=MAX_FILTER_VALUES).map(|i| format!("s{}", i)).collect();
+        let oversized = serde_json::json!({"type": "subscribe", "services": services});
+        let reply = subscriptions.handle(&oversized.to_string());
+        assert!(matches!(reply, ServerMessage::Error { .. }));
+        assert_eq!(subscriptions.filters.len(), MAX_SUBSCRIPTIONS - 1);
+    }
+}